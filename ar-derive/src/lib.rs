@@ -0,0 +1,455 @@
+//! `#[derive(Readable, Writable, SerializedSize)]` for the registry's format structures.
+//!
+//! Fields are read and written in declaration order, each through its own `Readable`/`Writable`
+//! impl and inside a `spans::field` scope named after the field. Attributes describe the parts of
+//! the format that aren't plain fields:
+//!
+//! - `#[magic(start = EXPR, end = EXPR)]` on the struct: `u32` constants before and after the
+//!   fields, checked when reading.
+//! - `#[len(u32)]` on a `Vec<T>`: the elements are prefixed by their count as a `u32` or `i32`,
+//!   recorded as a `count` span (`#[count = "bundle_count"]` renames it).
+//! - `#[flag(u32)]` on an `Option<T>`: the value is prefixed by a presence flag of 0 or 1.
+//! - `#[cond(EXPR)]` on an `Option<T>`: the value is present when `EXPR` holds. Earlier fields are
+//!   in scope by reference.
+//! - `#[since(Version)]` on any field: the field only exists from that `AssetRegistryVersion` on,
+//!   and is `Default::default()` in older registries.
+//!
+//! The generated code refers to the `read`, `write`, `spans` and `asset_registry_version` modules
+//! of the crate using it.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, Fields, GenericArgument, Ident, Lit, LitStr, Path,
+    PathArguments, Type,
+};
+
+#[proc_macro_derive(Readable, attributes(magic, len, count, flag, cond, since))]
+pub fn derive_readable(input: TokenStream) -> TokenStream {
+    expand(parse_macro_input!(input as DeriveInput), readable)
+}
+
+#[proc_macro_derive(Writable, attributes(magic, len, count, flag, cond, since))]
+pub fn derive_writable(input: TokenStream) -> TokenStream {
+    expand(parse_macro_input!(input as DeriveInput), writable)
+}
+
+#[proc_macro_derive(SerializedSize, attributes(magic, len, count, flag, cond, since))]
+pub fn derive_serialized_size(input: TokenStream) -> TokenStream {
+    expand(parse_macro_input!(input as DeriveInput), serialized_size)
+}
+
+fn expand(input: DeriveInput, f: fn(&Struct) -> TokenStream2) -> TokenStream {
+    match Struct::parse(&input) {
+        Ok(s) => f(&s).into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+struct Struct {
+    name: Ident,
+    start_magic: Option<Expr>,
+    end_magic: Option<Expr>,
+    fields: Vec<Field>,
+}
+
+struct Field {
+    ident: Ident,
+    ty: Type,
+    kind: Kind,
+    since: Option<Path>,
+}
+
+enum Kind {
+    Plain,
+    /// A `Vec` prefixed by its length.
+    Array {
+        elem: Type,
+        len: Type,
+        count: LitStr,
+    },
+    /// An `Option` prefixed by a presence flag.
+    Flagged {
+        inner: Type,
+        flag: Type,
+    },
+    /// An `Option` whose presence depends on earlier fields.
+    Conditional {
+        inner: Type,
+        cond: Expr,
+    },
+}
+
+impl Struct {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        if !input.generics.params.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &input.generics,
+                "generic format structures are not supported",
+            ));
+        }
+        let Data::Struct(data) = &input.data else {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "only structs can be derived",
+            ));
+        };
+        let Fields::Named(named) = &data.fields else {
+            return Err(syn::Error::new_spanned(
+                &data.fields,
+                "only structs with named fields can be derived",
+            ));
+        };
+
+        let mut start_magic = None;
+        let mut end_magic = None;
+        for attr in &input.attrs {
+            if attr.path().is_ident("magic") {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("start") {
+                        start_magic = Some(meta.value()?.parse()?);
+                    } else if meta.path.is_ident("end") {
+                        end_magic = Some(meta.value()?.parse()?);
+                    } else {
+                        return Err(meta.error("expected `start` or `end`"));
+                    }
+                    Ok(())
+                })?;
+            }
+        }
+
+        let fields = named
+            .named
+            .iter()
+            .map(Field::parse)
+            .collect::<syn::Result<_>>()?;
+        Ok(Struct {
+            name: input.ident.clone(),
+            start_magic,
+            end_magic,
+            fields,
+        })
+    }
+}
+
+impl Field {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field.ident.clone().expect("named field");
+        let (mut len, mut count, mut flag, mut cond, mut since) = (None, None, None, None, None);
+        for attr in &field.attrs {
+            let path = attr.path();
+            if path.is_ident("len") {
+                len = Some(attr.parse_args::<Type>()?);
+            } else if path.is_ident("flag") {
+                flag = Some(attr.parse_args::<Type>()?);
+            } else if path.is_ident("count") {
+                match &attr.meta.require_name_value()?.value {
+                    Expr::Lit(lit) => match &lit.lit {
+                        Lit::Str(s) => count = Some(s.clone()),
+                        other => return Err(syn::Error::new_spanned(other, "expected a string")),
+                    },
+                    other => return Err(syn::Error::new_spanned(other, "expected a string")),
+                }
+            } else if path.is_ident("cond") {
+                cond = Some(attr.parse_args::<Expr>()?);
+            } else if path.is_ident("since") {
+                since = Some(attr.parse_args::<Path>()?);
+            }
+        }
+
+        let ty = field.ty.clone();
+        let wrapped = |wrapper: &str, attr: &str| {
+            generic_arg(&ty, wrapper).ok_or_else(|| {
+                syn::Error::new_spanned(&ty, format!("`#[{attr}]` needs a `{wrapper}<T>` field"))
+            })
+        };
+        let kind = match (len, flag, cond) {
+            (None, None, None) => Kind::Plain,
+            (Some(len), None, None) => Kind::Array {
+                elem: wrapped("Vec", "len")?,
+                len,
+                count: count
+                    .take()
+                    .unwrap_or_else(|| LitStr::new("count", ident.span())),
+            },
+            (None, Some(flag), None) => Kind::Flagged {
+                inner: wrapped("Option", "flag")?,
+                flag,
+            },
+            (None, None, Some(cond)) => Kind::Conditional {
+                inner: wrapped("Option", "cond")?,
+                cond,
+            },
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &ident,
+                    "`#[len]`, `#[flag]` and `#[cond]` are mutually exclusive",
+                ))
+            }
+        };
+        if let Some(count) = count {
+            return Err(syn::Error::new_spanned(count, "`#[count]` needs `#[len]`"));
+        }
+        Ok(Field {
+            ident,
+            ty,
+            kind,
+            since,
+        })
+    }
+
+    /// The types whose `Readable`/`Writable` impls this field uses.
+    fn bound(&self) -> &Type {
+        match &self.kind {
+            Kind::Plain => &self.ty,
+            Kind::Array { elem, .. } => elem,
+            Kind::Flagged { inner, .. } | Kind::Conditional { inner, .. } => inner,
+        }
+    }
+
+    /// Wrap the code handling this field in its version check, if it has one.
+    fn gate(&self, present: TokenStream2, absent: TokenStream2) -> TokenStream2 {
+        match &self.since {
+            Some(since) => quote! {
+                if crate::asset_registry_version::AssetRegistryVersion::current()
+                    >= crate::asset_registry_version::AssetRegistryVersion::#since
+                {
+                    #present
+                } else {
+                    #absent
+                }
+            },
+            None => present,
+        }
+    }
+}
+
+/// `T` in `wrapper<T>`.
+fn generic_arg(ty: &Type, wrapper: &str) -> Option<Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(ty) if args.args.len() == 1 => Some(ty.clone()),
+        _ => None,
+    }
+}
+
+fn readable(s: &Struct) -> TokenStream2 {
+    let name = &s.name;
+    let span_name = format!("{name}_read");
+    let bounds = s.fields.iter().map(Field::bound);
+    let magic = |magic: &Option<Expr>, field: &str| {
+        let what = field.replace('_', " ");
+        magic.as_ref().map(|magic| {
+            quote! {
+                let expected: u32 = #magic;
+                let magic = crate::spans::field(#field, || {
+                    <u32 as crate::read::Readable<R>>::read(reader)
+                })?;
+                if magic != expected {
+                    return Err(::color_eyre::eyre::eyre!(
+                        "{} {} mismatch: expected {:X} but found {:X}",
+                        stringify!(#name),
+                        #what,
+                        expected,
+                        magic
+                    ));
+                }
+            }
+        })
+    };
+    let (start, end) = (
+        magic(&s.start_magic, "start_magic"),
+        magic(&s.end_magic, "end_magic"),
+    );
+    let mut previous = vec![];
+    let fields = s.fields.iter().map(|field| {
+        let ident = &field.ident;
+        let label = ident.to_string();
+        let read = match &field.kind {
+            Kind::Plain => {
+                let ty = &field.ty;
+                quote! {
+                    crate::spans::field(#label, || <#ty as crate::read::Readable<R>>::read(reader))?
+                }
+            }
+            Kind::Array { elem, len, count } => quote! {{
+                let count = crate::read::ArrayLen::count(crate::spans::field(#count, || {
+                    <#len as crate::read::Readable<R>>::read(reader)
+                })?)?;
+                crate::spans::field(#label, || {
+                    crate::read::read_array(count, reader, <#elem as crate::read::Readable<R>>::read)
+                })?
+            }},
+            Kind::Flagged { inner, flag } => quote! {
+                crate::spans::field(#label, || -> ::color_eyre::eyre::Result<_> {
+                    Ok(match <#flag as crate::read::Readable<R>>::read(reader)? {
+                        0 => None,
+                        1 => Some(<#inner as crate::read::Readable<R>>::read(reader)?),
+                        other => {
+                            return Err(::color_eyre::eyre::eyre!(
+                                "unexpected {} presence flag {}",
+                                #label,
+                                other
+                            ))
+                        }
+                    })
+                })?
+            },
+            Kind::Conditional { inner, cond } => quote! {
+                if { #(let #previous = &#previous;)* #cond } {
+                    Some(crate::spans::field(#label, || {
+                        <#inner as crate::read::Readable<R>>::read(reader)
+                    })?)
+                } else {
+                    None
+                }
+            },
+        };
+        let read = field.gate(read, quote! { ::core::default::Default::default() });
+        previous.push(ident.clone());
+        quote! { let #ident = #read; }
+    });
+    let fields = fields.collect::<Vec<_>>();
+    let idents = s.fields.iter().map(|f| &f.ident);
+    quote! {
+        impl<R: ::std::io::Read> crate::read::Readable<R> for #name
+        where
+            #(#bounds: crate::read::Readable<R>,)*
+        {
+            #[cfg_attr(
+                feature = "trace-fields",
+                ::tracing::instrument(name = #span_name, skip_all)
+            )]
+            fn read(reader: &mut R) -> ::color_eyre::eyre::Result<Self> {
+                #start
+                #(#fields)*
+                #end
+                Ok(#name { #(#idents),* })
+            }
+        }
+    }
+}
+
+fn writable(s: &Struct) -> TokenStream2 {
+    let name = &s.name;
+    let span_name = format!("{name}_write");
+    let bounds = s.fields.iter().map(Field::bound);
+    let magic = |magic: &Option<Expr>| {
+        magic.as_ref().map(|magic| {
+            quote! {
+                let magic: u32 = #magic;
+                crate::write::Writable::write(&magic, writer)?;
+            }
+        })
+    };
+    let (start, end) = (magic(&s.start_magic), magic(&s.end_magic));
+    let mut previous = vec![];
+    let fields = s.fields.iter().map(|field| {
+        let ident = &field.ident;
+        let label = ident.to_string();
+        let write = match &field.kind {
+            Kind::Plain => quote! { crate::write::Writable::write(&self.#ident, writer)?; },
+            Kind::Array { len, .. } => quote! {
+                let len = <#len as crate::read::ArrayLen>::from_len(#label, self.#ident.len())?;
+                crate::write::Writable::write(&len, writer)?;
+                crate::write::write_array(writer, &self.#ident, |w, e| {
+                    crate::write::Writable::write(e, w)
+                })?;
+            },
+            Kind::Flagged { flag, .. } => quote! {
+                match &self.#ident {
+                    Some(value) => {
+                        crate::write::Writable::write(&<#flag as From<u8>>::from(1), writer)?;
+                        crate::write::Writable::write(value, writer)?;
+                    }
+                    None => crate::write::Writable::write(&<#flag as From<u8>>::from(0), writer)?,
+                }
+            },
+            Kind::Conditional { cond, .. } => quote! {
+                let present: bool = { #(let #previous = &self.#previous;)* #cond };
+                if present != self.#ident.is_some() {
+                    return Err(::color_eyre::eyre::eyre!(
+                        "{}.{} is {} but its condition `{}` is {}",
+                        stringify!(#name),
+                        #label,
+                        if present { "absent" } else { "present" },
+                        stringify!(#cond),
+                        present
+                    ));
+                }
+                if let Some(value) = &self.#ident {
+                    crate::write::Writable::write(value, writer)?;
+                }
+            },
+        };
+        previous.push(ident.clone());
+        field.gate(write, quote! {})
+    });
+    let fields = fields.collect::<Vec<_>>();
+    quote! {
+        impl<W: ::std::io::Write> crate::write::Writable<W> for #name
+        where
+            #(#bounds: crate::write::Writable<W>,)*
+        {
+            #[cfg_attr(
+                feature = "trace-fields",
+                ::tracing::instrument(name = #span_name, skip_all)
+            )]
+            fn write(&self, writer: &mut W) -> ::color_eyre::eyre::Result<()> {
+                #start
+                #(#fields)*
+                #end
+                Ok(())
+            }
+        }
+    }
+}
+
+fn serialized_size(s: &Struct) -> TokenStream2 {
+    let name = &s.name;
+    let magics = [&s.start_magic, &s.end_magic]
+        .iter()
+        .filter(|m| m.is_some())
+        .count() as u64
+        * 4;
+    let fields = s.fields.iter().map(|field| {
+        let ident = &field.ident;
+        let size = match &field.kind {
+            Kind::Plain => quote! { crate::write::SerializedSize::serialized_size(&self.#ident) },
+            Kind::Array { len, .. } => quote! {
+                ::core::mem::size_of::<#len>() as u64
+                    + crate::write::SerializedSize::serialized_size(self.#ident.as_slice())
+            },
+            Kind::Flagged { flag, .. } => quote! {
+                ::core::mem::size_of::<#flag>() as u64
+                    + self
+                        .#ident
+                        .as_ref()
+                        .map_or(0, crate::write::SerializedSize::serialized_size)
+            },
+            Kind::Conditional { .. } => quote! {
+                self.#ident
+                    .as_ref()
+                    .map_or(0, crate::write::SerializedSize::serialized_size)
+            },
+        };
+        field.gate(size, quote! { 0 })
+    });
+    quote! {
+        impl crate::write::SerializedSize for #name {
+            fn serialized_size(&self) -> u64 {
+                #magics #(+ #fields)*
+            }
+        }
+    }
+}
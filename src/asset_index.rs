@@ -0,0 +1,262 @@
+//! Lookup tables over the assets of a registry, like the `CachedAssetsBy*` maps of UE's
+//! `FAssetRegistryState`.
+//!
+//! Building an [`AssetIndex`] resolves every asset once; afterwards finding assets by object path,
+//! package, folder, class or tag is a hash lookup. Keys are compared ignoring ASCII case, as
+//! `FName`s are. The index borrows the registry, so an edit can't leave it stale.
+
+use std::collections::{BTreeSet, HashMap};
+
+use color_eyre::eyre::Result as EResult;
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::assets::{AssetData, ResolvedAssetData};
+use crate::query::{Comparison, Field, Query};
+
+#[derive(Debug, Clone)]
+pub struct AssetIndex<'a> {
+    registry: &'a AssetRegistry,
+    by_object_path: HashMap<String, usize>,
+    by_package_name: HashMap<String, Vec<usize>>,
+    by_package_path: HashMap<String, Vec<usize>>,
+    by_class: HashMap<String, Vec<usize>>,
+    by_tag: HashMap<String, Vec<usize>>,
+    by_tag_value: HashMap<(String, String), Vec<usize>>,
+}
+
+impl AssetRegistry {
+    /// Build the lookup tables over the assets. Fails on names or tags that don't resolve.
+    #[instrument(name = "AssetRegistry_index", skip_all)]
+    pub fn index(&self) -> EResult<AssetIndex<'_>> {
+        let mut index = AssetIndex {
+            registry: self,
+            by_object_path: HashMap::with_capacity(self.assets.assets.len()),
+            by_package_name: HashMap::new(),
+            by_package_path: HashMap::new(),
+            by_class: HashMap::new(),
+            by_tag: HashMap::new(),
+            by_tag_value: HashMap::new(),
+        };
+        for (i, asset) in self.assets.assets.iter().enumerate() {
+            let asset = asset.resolve(&self.names, &self.store)?;
+            index
+                .by_object_path
+                .entry(key(&asset.object_path))
+                .or_insert(i);
+            for (map, name) in [
+                (&mut index.by_package_name, &asset.package_name),
+                (&mut index.by_package_path, &asset.package_path),
+                (&mut index.by_class, &asset.asset_class),
+            ] {
+                map.entry(key(name)).or_default().push(i);
+            }
+            for (tag, value) in &asset.tags {
+                index.by_tag.entry(key(tag)).or_default().push(i);
+                index
+                    .by_tag_value
+                    .entry((key(tag), value.clone()))
+                    .or_default()
+                    .push(i);
+            }
+        }
+        debug!(
+            assets = index.by_object_path.len(),
+            classes = index.by_class.len(),
+            tags = index.by_tag.len()
+        );
+        Ok(index)
+    }
+}
+
+fn key(name: &str) -> String {
+    name.to_ascii_lowercase()
+}
+
+impl<'a> AssetIndex<'a> {
+    fn assets(&self, indices: Option<&Vec<usize>>) -> Vec<&'a AssetData> {
+        let assets = &self.registry.assets.assets;
+        indices.map_or_else(Vec::new, |indices| {
+            indices.iter().map(|&i| &assets[i]).collect()
+        })
+    }
+
+    /// Index into [`AssetDataCollection::assets`][crate::assets::AssetDataCollection] of the
+    /// asset with the given object path, e.g. `/Game/Foo/Bar.Bar`.
+    pub fn position(&self, object_path: &str) -> Option<usize> {
+        self.by_object_path.get(&key(object_path)).copied()
+    }
+
+    pub fn get_by_object_path(&self, object_path: &str) -> Option<&'a AssetData> {
+        self.position(object_path)
+            .map(|i| &self.registry.assets.assets[i])
+    }
+
+    /// The assets of a package, e.g. `/Game/Foo/Bar`.
+    pub fn get_by_package_name(&self, package_name: &str) -> Vec<&'a AssetData> {
+        self.assets(self.by_package_name.get(&key(package_name)))
+    }
+
+    /// The assets directly in a folder, e.g. `/Game/Foo`.
+    pub fn get_by_package_path(&self, package_path: &str) -> Vec<&'a AssetData> {
+        self.assets(self.by_package_path.get(&key(package_path)))
+    }
+
+    pub fn get_by_class(&self, class: &str) -> Vec<&'a AssetData> {
+        self.assets(self.by_class.get(&key(class)))
+    }
+
+    /// The assets that have a tag, whatever its value.
+    pub fn get_by_tag(&self, tag: &str) -> Vec<&'a AssetData> {
+        self.assets(self.by_tag.get(&key(tag)))
+    }
+
+    /// The assets whose tag has exactly this value, as shown by
+    /// [`StoreValue::resolve`][crate::store_data::StoreValue::resolve].
+    pub fn get_by_tag_value(&self, tag: &str, value: &str) -> Vec<&'a AssetData> {
+        self.assets(self.by_tag_value.get(&(key(tag), value.to_string())))
+    }
+
+    /// The assets matching a [`Query`], in registry order, like
+    /// [`AssetRegistry::assets_matching`] but only resolving the assets the lookup tables can't
+    /// rule out. Building the index resolves every asset, so this only pays off when the index
+    /// serves several queries.
+    #[instrument(name = "AssetIndex_assets_matching", skip_all)]
+    pub fn assets_matching(&self, query: &Query) -> EResult<Vec<ResolvedAssetData>> {
+        let registry = self.registry;
+        let candidates = match self.candidates(query) {
+            Some(candidates) => candidates.into_iter().collect(),
+            None => (0..registry.assets.assets.len()).collect::<Vec<_>>(),
+        };
+        let mut matches = vec![];
+        for &i in &candidates {
+            let asset = registry.assets.assets[i].resolve(&registry.names, &registry.store)?;
+            if query.matches(&asset) {
+                matches.push(asset);
+            }
+        }
+        debug!(candidates = candidates.len(), matches = matches.len());
+        Ok(matches)
+    }
+
+    /// A superset of the indices of the assets matching `query`, or `None` for all of them.
+    fn candidates(&self, query: &Query) -> Option<BTreeSet<usize>> {
+        let lookup = |map: &HashMap<String, Vec<usize>>, name: &str| {
+            Some(map.get(&key(name)).into_iter().flatten().copied().collect())
+        };
+        match query {
+            Query::And(a, b) => match (self.candidates(a), self.candidates(b)) {
+                (Some(a), Some(b)) => Some(&a & &b),
+                (a, b) => a.or(b),
+            },
+            Query::Or(a, b) => Some(&self.candidates(a)? | &self.candidates(b)?),
+            // Equality on tag values ignores case, so only the tag's presence narrows.
+            Query::Has(Field::Tag(tag)) | Query::Compare(Field::Tag(tag), _) => {
+                lookup(&self.by_tag, tag)
+            }
+            Query::Compare(Field::Class, Comparison::Eq(class)) => lookup(&self.by_class, class),
+            Query::Compare(Field::PackageName, Comparison::Eq(package)) => {
+                lookup(&self.by_package_name, package)
+            }
+            Query::Compare(Field::PackagePath, Comparison::Eq(folder)) => {
+                lookup(&self.by_package_path, folder)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{RegistryBuilder, TagValue};
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/Weapons/BP_Rifle.BP_Rifle", "Blueprint")
+            .tag("ParentClass", TagValue::Name("BP_GunBase".to_string()));
+        builder
+            .add_asset("/Game/Weapons/BP_Pistol.BP_Pistol", "Blueprint")
+            .tag("ParentClass", TagValue::Name("BP_Sidearm".to_string()));
+        builder
+            .add_asset(
+                "/Game/Weapons/BP_Pistol.BP_Pistol_C",
+                "BlueprintGeneratedClass",
+            )
+            .tag("NativeParentClass", "Actor");
+        builder.add_asset("/Game/Maps/Arena.Arena", "World");
+        builder.build().unwrap()
+    }
+
+    fn paths(registry: &AssetRegistry, assets: Vec<&AssetData>) -> Vec<String> {
+        assets
+            .into_iter()
+            .map(|a| registry.names.try_resolve(a.object_path).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_index() {
+        let registry = fixture();
+        let index = registry.index().unwrap();
+        let paths = |assets| paths(&registry, assets);
+
+        assert_eq!(index.position("/game/maps/arena.arena"), Some(3));
+        assert_eq!(
+            index.get_by_object_path("/Game/Weapons/BP_Rifle.BP_Rifle"),
+            Some(&registry.assets.assets[0])
+        );
+        assert_eq!(index.get_by_object_path("/Game/Missing.Missing"), None);
+        assert_eq!(
+            paths(index.get_by_package_name("/Game/Weapons/BP_Pistol")),
+            [
+                "/Game/Weapons/BP_Pistol.BP_Pistol",
+                "/Game/Weapons/BP_Pistol.BP_Pistol_C"
+            ]
+        );
+        assert_eq!(paths(index.get_by_package_path("/Game/Weapons")).len(), 3);
+        assert_eq!(
+            paths(index.get_by_class("blueprint")),
+            [
+                "/Game/Weapons/BP_Rifle.BP_Rifle",
+                "/Game/Weapons/BP_Pistol.BP_Pistol"
+            ]
+        );
+        assert_eq!(paths(index.get_by_tag("ParentClass")).len(), 2);
+        assert_eq!(
+            paths(index.get_by_tag_value("parentclass", "BP_Sidearm")),
+            ["/Game/Weapons/BP_Pistol.BP_Pistol"]
+        );
+        assert!(index
+            .get_by_tag_value("ParentClass", "bp_sidearm")
+            .is_empty());
+        assert!(index.get_by_class("Texture2D").is_empty());
+    }
+
+    #[test]
+    fn test_assets_matching() {
+        let registry = fixture();
+        let index = registry.index().unwrap();
+        for expr in [
+            "class = blueprint",
+            "class = Blueprint and tag.ParentClass = bp_sidearm",
+            "folder = /game/weapons and not tag.ParentClass",
+            "package = /Game/Weapons/BP_Pistol or class = World",
+            "tag.NativeParentClass or name ~ \"BP_*\"",
+            "not class = Blueprint",
+            "class = Texture2D",
+        ] {
+            let query = Query::parse(expr).unwrap();
+            assert_eq!(
+                index.assets_matching(&query).unwrap(),
+                registry.assets_matching(&query).unwrap(),
+                "{expr}"
+            );
+        }
+        let query = Query::parse("tag.ParentClass = bp_sidearm").unwrap();
+        assert_eq!(index.candidates(&query).unwrap().len(), 2);
+    }
+}
@@ -1,37 +1,80 @@
-use std::io::{Read, Write};
+use std::io::{Read, Seek, Write};
 
-use color_eyre::eyre::Result as EResult;
+use color_eyre::eyre::{eyre, Result as EResult};
 use tracing::*;
 
 use crate::asset_registry_header::AssetRegistryHeader;
 use crate::asset_registry_version::AssetRegistryVersion;
+use crate::assets::{AssetDataCollection, AssetPackageDataCollection};
+use crate::dependencies::DependencySection;
 use crate::names_batch::NamesBatch;
 use crate::read::Readable;
-use crate::write::Writable;
+use crate::spans;
+use crate::store_data::StoreData;
+use crate::write::{SerializedSize, Writable};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct AssetRegistry {
     pub names: NamesBatch,
+    pub store: StoreData,
+    pub assets: AssetDataCollection,
+    pub dependencies: DependencySection,
+    pub package_data: AssetPackageDataCollection,
 }
 
-impl<W: Write> Writable<W> for AssetRegistry {
+impl<W: Write + Seek> Writable<W> for AssetRegistry {
     #[instrument(name = "AssetRegistry_write", skip_all)]
     fn write(&self, writer: &mut W) -> EResult<()> {
-        AssetRegistryHeader {
-            version: AssetRegistryVersion::LATEST_VERSION,
-        }
-        .write(writer)?;
-        self.names.write(writer)?;
-        Ok(())
+        let version = AssetRegistryVersion::LATEST_VERSION;
+        AssetRegistryHeader { version }.write(writer)?;
+        version.scope(|| {
+            self.names.write(writer)?;
+            self.store.write(writer)?;
+            self.assets.write(writer)?;
+            self.dependencies.write(writer)?;
+            self.package_data.write(writer)
+        })
+    }
+}
+
+impl SerializedSize for AssetRegistry {
+    fn serialized_size(&self) -> u64 {
+        let version = AssetRegistryVersion::LATEST_VERSION;
+        AssetRegistryHeader { version }.serialized_size()
+            + version.scope(|| {
+                self.names.serialized_size()
+                    + self.store.serialized_size()
+                    + self.assets.serialized_size()
+                    + self.dependencies.serialized_size()
+                    + self.package_data.serialized_size()
+            })
     }
 }
 
 impl<R: Read> Readable<R> for AssetRegistry {
     #[instrument(name = "AssetRegistry_read", skip_all)]
     fn read(reader: &mut R) -> EResult<Self> {
-        let _ = AssetRegistryHeader::read(reader)?;
-        let names = NamesBatch::read(reader)?;
-        Ok(AssetRegistry { names })
+        let header = spans::field("header", || AssetRegistryHeader::read(reader))?;
+        header.version.scope(|| {
+            let names = spans::field("names", || NamesBatch::read(reader))?;
+            let store = spans::field("store", || StoreData::read(reader))?;
+            let assets = spans::field("assets", || AssetDataCollection::read(reader))?;
+            let dependencies = spans::field("dependencies", || DependencySection::read(reader))?;
+            let package_data =
+                spans::field("package_data", || AssetPackageDataCollection::read(reader))?;
+            // A misparse can land on plausible data, so insist on consuming the whole input.
+            let trailing = std::io::copy(reader, &mut std::io::sink())?;
+            if trailing != 0 {
+                return Err(eyre!("{} trailing bytes after the package data", trailing));
+            }
+            Ok(AssetRegistry {
+                names,
+                store,
+                assets,
+                dependencies,
+                package_data,
+            })
+        })
     }
 }
 
@@ -48,7 +91,7 @@ mod tests {
             hashes: vec![0xDEAD_BEEF],
             headers: vec![SerializedNameHeader {
                 is_utf16: false,
-                len: 2,
+                len: 1,
             }],
             strings: vec!["a".to_string()],
         };
@@ -57,6 +100,7 @@ mod tests {
         let mut writer = Cursor::new(&mut buf);
         AssetRegistry {
             names: names.clone(),
+            ..Default::default()
         }
         .write(&mut writer)
         .unwrap();
@@ -66,4 +110,68 @@ mod tests {
 
         assert_eq!(asset_registry.names, names);
     }
+
+    #[test]
+    fn test_serialized_size() {
+        use crate::builder::{RegistryBuilder, TagValue};
+        use crate::dependencies::{EDependencyCategory, EDependencyProperty};
+
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/Maps/Überwelt.Überwelt", "World")
+            .tag("Title", "Überwelt")
+            .tag("Label", TagValue::Text("Map".to_string()))
+            .bundle("Client", &["/Game/Icons/Map.Map"]);
+        builder.add_asset("/Game/Icons/Map.Map", "Texture2D");
+        builder.add_dependency(
+            "/Game/Maps/Überwelt",
+            "/Game/Icons/Map",
+            EDependencyCategory::Package,
+            EDependencyProperty::HARD,
+        );
+        builder
+            .add_package_data("/Game/Maps/Überwelt", 1234)
+            .cooked_hash([7; 16]);
+        builder.add_package_data("/Game/Icons/Map", 56);
+        let registry = builder.build().unwrap();
+
+        let mut buf = vec![];
+        registry.write(&mut Cursor::new(&mut buf)).unwrap();
+        assert_eq!(registry.serialized_size(), buf.len() as u64);
+        assert_eq!(
+            AssetRegistry::read(&mut Cursor::new(&buf)).unwrap(),
+            registry
+        );
+    }
+
+    #[test]
+    fn test_minimal() {
+        let buf = include_bytes!("../test_assets/minimal.bin");
+        let mut reader = Cursor::new(&buf[..]);
+        let registry = AssetRegistry::read(&mut reader).unwrap();
+        assert_eq!(reader.position(), buf.len() as u64);
+
+        assert_eq!(registry.names.strings.len(), 571);
+        assert_eq!(registry.assets.assets.len(), 155);
+        assert_eq!(registry.store.wide_strings.len(), 1);
+        assert_eq!(registry.store.numberless_pairs.len(), 1471);
+        assert!(registry.dependencies.nodes.is_empty());
+        assert_eq!(registry.serialized_size(), buf.len() as u64);
+
+        let mut written = vec![];
+        registry.write(&mut Cursor::new(&mut written)).unwrap();
+        assert!(written == buf, "rewriting the registry changed its bytes");
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let mut buf = vec![];
+        AssetRegistry::default()
+            .write(&mut Cursor::new(&mut buf))
+            .unwrap();
+        buf.push(0);
+
+        let err = AssetRegistry::read(&mut Cursor::new(&buf)).unwrap_err();
+        assert_eq!(err.to_string(), "1 trailing bytes after the package data");
+    }
 }
@@ -4,7 +4,8 @@ use color_eyre::eyre::{eyre, Result as EResult, WrapErr};
 use tracing::*;
 
 use crate::read::Readable;
-use crate::write::Writable;
+use crate::spans;
+use crate::write::{SerializedSize, Writable};
 
 use crate::asset_registry_version::AssetRegistryVersion;
 
@@ -26,12 +27,17 @@ impl<W: Write> Writable<W> for AssetRegistryHeader {
     }
 }
 
+impl SerializedSize for AssetRegistryHeader {
+    fn serialized_size(&self) -> u64 {
+        ASSET_REGISTRY_VERSION_GUID.len() as u64 + self.version.serialized_size()
+    }
+}
+
 impl<R: Read> Readable<R> for AssetRegistryHeader {
     #[instrument(name = "AssetRegistryHeader_read", skip_all)]
     fn read(reader: &mut R) -> EResult<Self> {
         let mut guid = [0u8; 16];
-        reader
-            .read_exact(&mut guid)
+        spans::field("guid", || reader.read_exact(&mut guid))
             .wrap_err_with(|| "failed to read GUID")?;
         if guid != ASSET_REGISTRY_VERSION_GUID {
             return Err(eyre!(
@@ -41,7 +47,7 @@ impl<R: Read> Readable<R> for AssetRegistryHeader {
             ));
         }
 
-        let version = AssetRegistryVersion::read(reader)?;
+        let version = spans::field("version", || AssetRegistryVersion::read(reader))?;
         Ok(AssetRegistryHeader { version })
     }
 }
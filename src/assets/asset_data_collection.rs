@@ -5,11 +5,12 @@ use color_eyre::eyre::Result as EResult;
 use tracing::*;
 
 use crate::read::{read_array, Readable};
-use crate::write::{write_array, Writable};
+use crate::spans;
+use crate::write::{count_u32, write_array, SerializedSize, Writable};
 
 use super::AssetData;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct AssetDataCollection {
     pub assets: Vec<AssetData>,
 }
@@ -17,16 +18,23 @@ pub struct AssetDataCollection {
 impl<W: Write> Writable<W> for AssetDataCollection {
     #[instrument(name = "AssetDataCollection_write", skip_all)]
     fn write(&self, writer: &mut W) -> EResult<()> {
-        writer.write_u32::<LE>(self.assets.len() as u32)?;
+        writer.write_u32::<LE>(count_u32("assets", self.assets.len())?)?;
         write_array(writer, &self.assets, |w, a| a.write(w))?;
         Ok(())
     }
 }
 
+impl SerializedSize for AssetDataCollection {
+    fn serialized_size(&self) -> u64 {
+        4 + self.assets.serialized_size()
+    }
+}
+
 impl<R: Read> Readable<R> for AssetDataCollection {
     #[instrument(name = "AssetDataCollection_read", skip_all)]
     fn read(reader: &mut R) -> EResult<Self> {
-        let assets = read_array(reader.read_u32::<LE>()?, reader, AssetData::read)?;
+        let count = spans::field("count", || reader.read_u32::<LE>())?;
+        let assets = spans::field("assets", || read_array(count, reader, AssetData::read))?;
         Ok(AssetDataCollection { assets })
     }
 }
@@ -77,6 +85,8 @@ mod tests {
                         sub_path_string: FString::from("forklift"),
                     }],
                 }],
+                chunk_ids: vec![0, 7],
+                package_flags: 0x8000_0000,
             }],
         };
         let mut buf = vec![];
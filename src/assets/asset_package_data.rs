@@ -0,0 +1,40 @@
+use crate::read::Readable;
+use crate::unreal_types::FName;
+use crate::write::{SerializedSize, Writable};
+
+/// Per-package data, serialized as the package name followed by `FAssetPackageData`.
+#[derive(Debug, PartialEq, Clone, Readable, Writable, SerializedSize)]
+pub struct FAssetPackageData {
+    pub package_name: FName,
+    pub disk_size: i64,
+    pub package_guid: [u8; 16],
+    /// `FMD5Hash` of the cooked package; serialized as a `u32` validity flag followed by the hash
+    /// bytes when valid.
+    #[flag(u32)]
+    pub cooked_hash: Option<[u8; 16]>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let data = FAssetPackageData {
+            package_name: FName {
+                index: 42,
+                number: 0,
+            },
+            disk_size: 0x1234,
+            package_guid: [7u8; 16],
+            cooked_hash: Some([0xAB; 16]),
+        };
+        let mut buf = vec![];
+        let mut writer = Cursor::new(&mut buf);
+        data.write(&mut writer).unwrap();
+        let mut reader = Cursor::new(&buf);
+        let read_data = FAssetPackageData::read(&mut reader).unwrap();
+        assert_eq!(read_data, data);
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::{Read, Write};
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use color_eyre::eyre::Result as EResult;
+use tracing::*;
+
+use crate::read::{read_array, Readable};
+use crate::spans;
+use crate::write::{count_u32, write_array, SerializedSize, Writable};
+
+use super::FAssetPackageData;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AssetPackageDataCollection {
+    pub packages: Vec<FAssetPackageData>,
+}
+
+impl<W: Write> Writable<W> for AssetPackageDataCollection {
+    #[instrument(name = "AssetPackageDataCollection_write", skip_all)]
+    fn write(&self, writer: &mut W) -> EResult<()> {
+        writer.write_u32::<LE>(count_u32("packages", self.packages.len())?)?;
+        write_array(writer, &self.packages, |w, p| p.write(w))?;
+        Ok(())
+    }
+}
+
+impl SerializedSize for AssetPackageDataCollection {
+    fn serialized_size(&self) -> u64 {
+        4 + self.packages.serialized_size()
+    }
+}
+
+impl<R: Read> Readable<R> for AssetPackageDataCollection {
+    #[instrument(name = "AssetPackageDataCollection_read", skip_all)]
+    fn read(reader: &mut R) -> EResult<Self> {
+        let count = spans::field("count", || reader.read_u32::<LE>())?;
+        let packages = spans::field("packages", || {
+            read_array(count, reader, FAssetPackageData::read)
+        })?;
+        Ok(AssetPackageDataCollection { packages })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::unreal_types::FName;
+
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let data = AssetPackageDataCollection {
+            packages: vec![FAssetPackageData {
+                package_name: FName {
+                    index: 4723,
+                    number: 0,
+                },
+                disk_size: 1024,
+                package_guid: [1u8; 16],
+                cooked_hash: None,
+            }],
+        };
+        let mut buf = vec![];
+        let mut writer = Cursor::new(&mut buf);
+        data.write(&mut writer).unwrap();
+        let mut reader = Cursor::new(&buf);
+        let read_data = AssetPackageDataCollection::read(&mut reader).unwrap();
+        assert_eq!(read_data, data);
+    }
+}
@@ -1,8 +1,13 @@
 mod asset_bundle_entry;
 mod asset_data;
 mod asset_data_collection;
+mod asset_package_data;
+mod asset_package_data_collection;
 mod soft_object_path;
 
 pub use asset_bundle_entry::*;
 pub use asset_data::*;
+pub use asset_data_collection::*;
+pub use asset_package_data::*;
+pub use asset_package_data_collection::*;
 pub use soft_object_path::*;
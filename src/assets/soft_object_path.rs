@@ -1,41 +1,13 @@
-use std::io::{Read, Write};
-
-use color_eyre::eyre::{Result as EResult};
-use tracing::*;
-
 use crate::read::Readable;
 use crate::unreal_types::{FName, FString};
-use crate::write::Writable;
+use crate::write::{SerializedSize, Writable};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Readable, Writable, SerializedSize)]
 pub struct FSoftObjectPath {
     pub asset_path_name: FName,
     pub sub_path_string: FString,
 }
 
-impl<W: Write> Writable<W> for FSoftObjectPath {
-    #[instrument(name = "FSoftObjectPath_write", skip_all)]
-    fn write(&self, writer: &mut W) -> EResult<()> {
-        self.asset_path_name.write(writer)?;
-        self.sub_path_string.write(writer)?;
-        Ok(())
-    }
-}
-
-impl<R: Read> Readable<R> for FSoftObjectPath {
-    #[instrument(name = "FSoftObjectPath_read", skip_all)]
-    fn read(reader: &mut R) -> EResult<Self> {
-        let asset_path_name = FName::read(reader)?;
-        debug!(?asset_path_name);
-        let sub_path_string = FString::read(reader)?;
-        debug!(?sub_path_string);
-        Ok(FSoftObjectPath {
-            asset_path_name,
-            sub_path_string,
-        })
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
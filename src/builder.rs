@@ -0,0 +1,554 @@
+//! Authoring asset registries from scratch, e.g. for tests or for tools generating registry
+//! fragments for mods.
+//!
+//! Everything is described with plain strings; [`RegistryBuilder::build`] interns the names
+//! (computing their hashes and headers), deduplicates tag values in the store and packs every
+//! asset's tags into a contiguous run of pairs.
+//!
+//! ```ignore
+//! let mut builder = RegistryBuilder::new();
+//! builder
+//!     .add_asset("/Game/Foo/Bar.Bar", "Blueprint")
+//!     .tag("ParentClass", TagValue::Name("Actor".into()))
+//!     .bundle("Client", &["/Game/Foo/Baz.Baz"]);
+//! builder.add_dependency(
+//!     "/Game/Foo/Bar",
+//!     "/Game/Foo/Baz",
+//!     EDependencyCategory::Package,
+//!     EDependencyProperty::HARD | EDependencyProperty::GAME,
+//! );
+//! builder.add_package_data("/Game/Foo/Bar", 1234);
+//! let registry = builder.build()?;
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre::{eyre, Result as EResult};
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::assets::{
+    AssetData, AssetDataCollection, AssetPackageDataCollection, FAssetBundleEntry,
+    FAssetPackageData, FSoftObjectPath,
+};
+use crate::dependencies::{
+    DependencySection, EDependencyCategory, EDependencyProperty, FAssetIdentifier, FDependency,
+    FDependsNode, ResolvedIdentifier,
+};
+use crate::names_batch::{NamesBatch, NamesBuilder};
+use crate::store_data::{StoreBuilder, StoreValue};
+use crate::unreal_types::*;
+
+/// A tag value as written by a builder. Which store array it ends up in is derived from the
+/// variant and its contents.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TagValue {
+    /// Stored as an ANSI string if it's ASCII, as a wide string otherwise.
+    String(String),
+    Name(String),
+    /// An object path such as `/Game/Foo/Bar.Bar` together with the class of the object.
+    ExportPath {
+        class: String,
+        object_path: String,
+    },
+    Text(String),
+}
+
+impl From<&str> for TagValue {
+    fn from(value: &str) -> Self {
+        TagValue::String(value.to_string())
+    }
+}
+
+impl From<String> for TagValue {
+    fn from(value: String) -> Self {
+        TagValue::String(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetBuilder {
+    object_path: String,
+    class: String,
+    tags: Vec<(String, TagValue)>,
+    bundles: Vec<(String, Vec<String>)>,
+}
+
+impl AssetBuilder {
+    /// Describe an asset by its object path, e.g. `/Game/Foo/Bar.Bar`.
+    pub fn new(object_path: &str, class: &str) -> Self {
+        AssetBuilder {
+            object_path: object_path.to_string(),
+            class: class.to_string(),
+            tags: vec![],
+            bundles: vec![],
+        }
+    }
+
+    pub fn object_path(&self) -> &str {
+        &self.object_path
+    }
+
+    /// Set a tag, replacing any previous value of the same key.
+    pub fn tag(&mut self, key: &str, value: impl Into<TagValue>) -> &mut Self {
+        let value = value.into();
+        match self.tags.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.tags.push((key.to_string(), value)),
+        }
+        self
+    }
+
+    /// Add an asset bundle listing soft object paths, optionally with a `:SubPath` suffix.
+    pub fn bundle(&mut self, name: &str, paths: &[&str]) -> &mut Self {
+        self.bundles.push((
+            name.to_string(),
+            paths.iter().map(|p| p.to_string()).collect(),
+        ));
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PackageDataBuilder {
+    package_name: String,
+    disk_size: i64,
+    package_guid: [u8; 16],
+    cooked_hash: Option<[u8; 16]>,
+}
+
+impl PackageDataBuilder {
+    pub fn guid(&mut self, guid: [u8; 16]) -> &mut Self {
+        self.package_guid = guid;
+        self
+    }
+
+    pub fn cooked_hash(&mut self, hash: [u8; 16]) -> &mut Self {
+        self.cooked_hash = Some(hash);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DependencyEntry {
+    from: String,
+    to: String,
+    category: EDependencyCategory,
+    properties: EDependencyProperty,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RegistryBuilder {
+    assets: Vec<AssetBuilder>,
+    dependencies: Vec<DependencyEntry>,
+    packages: Vec<PackageDataBuilder>,
+}
+
+impl RegistryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an asset by its object path, e.g. `/Game/Foo/Bar.Bar`.
+    pub fn add_asset(&mut self, object_path: &str, class: &str) -> &mut AssetBuilder {
+        self.assets.push(AssetBuilder::new(object_path, class));
+        self.assets.last_mut().unwrap()
+    }
+
+    /// Add a dependency edge. `from` and `to` are asset identifiers as formatted by
+    /// [`ResolvedIdentifier`], e.g. `/Game/Foo/Bar` for a package or `Map:Entry` for a primary
+    /// asset id. Nodes are created on first mention.
+    pub fn add_dependency(
+        &mut self,
+        from: &str,
+        to: &str,
+        category: EDependencyCategory,
+        properties: EDependencyProperty,
+    ) -> &mut Self {
+        self.dependencies.push(DependencyEntry {
+            from: from.to_string(),
+            to: to.to_string(),
+            category,
+            properties,
+        });
+        self
+    }
+
+    pub fn add_package_data(
+        &mut self,
+        package_name: &str,
+        disk_size: i64,
+    ) -> &mut PackageDataBuilder {
+        self.packages.push(PackageDataBuilder {
+            package_name: package_name.to_string(),
+            disk_size,
+            package_guid: [0; 16],
+            cooked_hash: None,
+        });
+        self.packages.last_mut().unwrap()
+    }
+
+    #[instrument(name = "RegistryBuilder_build", skip_all)]
+    pub fn build(&self) -> EResult<AssetRegistry> {
+        let mut names = NamesBuilder::new(NamesBatch::HASH_VERSION);
+        let mut store = StoreBuilder::default();
+
+        let mut object_paths = HashSet::new();
+        let mut assets = vec![];
+        for asset in &self.assets {
+            if !object_paths.insert(asset.object_path.as_str()) {
+                return Err(eyre!("duplicate asset `{}`", asset.object_path));
+            }
+            assets.push(build_asset(&mut names, &mut store, asset)?);
+        }
+
+        let mut nodes = vec![];
+        let mut node_indices = HashMap::new();
+        let mut node = |names: &mut NamesBuilder, identifier: ResolvedIdentifier| -> EResult<i32> {
+            if let Some(&i) = node_indices.get(&identifier) {
+                return Ok(i);
+            }
+            let mut name = |n: &Option<String>| n.as_deref().map(|n| names.intern(n)).transpose();
+            nodes.push(FDependsNode {
+                identifier: FAssetIdentifier {
+                    package_name: name(&identifier.package_name)?,
+                    primary_asset_type: name(&identifier.primary_asset_type)?,
+                    object_name: name(&identifier.object_name)?,
+                    value_name: name(&identifier.value_name)?,
+                },
+                ..Default::default()
+            });
+            let i = nodes.len() as i32 - 1;
+            node_indices.insert(identifier, i);
+            Ok(i)
+        };
+        // Like the editor, every package containing an asset gets a node.
+        for asset in &self.assets {
+            let (package_name, _, _) = split_object_path(&asset.object_path)?;
+            node(&mut names, ResolvedIdentifier::package(package_name))?;
+        }
+        let mut edges = vec![];
+        for dependency in &self.dependencies {
+            let from = node(&mut names, ResolvedIdentifier::parse(&dependency.from))?;
+            let to = node(&mut names, ResolvedIdentifier::parse(&dependency.to))?;
+            edges.push((from, to, dependency));
+        }
+        for (from, to, dependency) in edges {
+            let from = &mut nodes[from as usize];
+            let edge = FDependency {
+                node: to,
+                properties: dependency.properties,
+            };
+            match dependency.category {
+                EDependencyCategory::Package => from.package_dependencies.push(edge),
+                EDependencyCategory::Manage => from.manage_dependencies.push(edge),
+                EDependencyCategory::SearchableName => from.name_dependencies.push(to),
+            }
+        }
+        let mut dependencies = DependencySection { nodes };
+        dependencies.rebuild_referencers();
+
+        let mut package_names = HashSet::new();
+        let mut packages = vec![];
+        for package in &self.packages {
+            if !package_names.insert(package.package_name.as_str()) {
+                return Err(eyre!(
+                    "duplicate package data for `{}`",
+                    package.package_name
+                ));
+            }
+            packages.push(FAssetPackageData {
+                package_name: names.intern(&package.package_name)?,
+                disk_size: package.disk_size,
+                package_guid: package.package_guid,
+                cooked_hash: package.cooked_hash,
+            });
+        }
+
+        debug!(
+            assets = assets.len(),
+            nodes = dependencies.nodes.len(),
+            packages = packages.len(),
+            names = names.names.strings.len()
+        );
+
+        Ok(AssetRegistry {
+            names: names.names,
+            store: store.store,
+            assets: AssetDataCollection { assets },
+            dependencies,
+            package_data: AssetPackageDataCollection { packages },
+        })
+    }
+}
+
+/// Split `/Game/Foo/Bar.Bar` into its package name `/Game/Foo/Bar`, package path `/Game/Foo` and
+/// asset name `Bar`.
+pub(crate) fn split_object_path(object_path: &str) -> EResult<(&str, &str, &str)> {
+    let malformed = || eyre!("malformed object path `{}`", object_path);
+    let (package_name, asset_name) = object_path.split_once('.').ok_or_else(malformed)?;
+    let (package_path, _) = package_name.rsplit_once('/').ok_or_else(malformed)?;
+    if !package_name.starts_with('/') || package_path.is_empty() || asset_name.is_empty() {
+        return Err(malformed());
+    }
+    Ok((package_name, package_path, asset_name))
+}
+
+pub(crate) fn build_asset(
+    names: &mut NamesBuilder,
+    store: &mut StoreBuilder,
+    asset: &AssetBuilder,
+) -> EResult<AssetData> {
+    let (package_name, package_path, asset_name) = split_object_path(&asset.object_path)?;
+
+    let map = asset
+        .tags
+        .iter()
+        .map(|(key, value)| Ok((names.intern(key)?, build_value(names, store, value)?)))
+        .collect::<EResult<Vec<_>>>()?;
+
+    let bundles = asset
+        .bundles
+        .iter()
+        .map(|(name, paths)| {
+            Ok(FAssetBundleEntry {
+                bundle_name: names.intern(name)?,
+                bundles: paths
+                    .iter()
+                    .map(|path| {
+                        let (path, sub_path) = path.split_once(':').unwrap_or((path, ""));
+                        Ok(FSoftObjectPath {
+                            asset_path_name: names.intern(path)?,
+                            sub_path_string: FString::from(sub_path),
+                        })
+                    })
+                    .collect::<EResult<_>>()?,
+            })
+        })
+        .collect::<EResult<_>>()?;
+
+    Ok(AssetData {
+        object_path: names.intern(&asset.object_path)?,
+        package_path: names.intern(package_path)?,
+        asset_class: names.intern(&asset.class)?,
+        package_name: names.intern(package_name)?,
+        asset_name: names.intern(asset_name)?,
+        tags: push_tags(store, &asset.object_path, &map)?,
+        bundles,
+        chunk_ids: vec![],
+        package_flags: 0,
+    })
+}
+
+/// Append an asset's tag map to the store, returning the packed handle.
+pub(crate) fn push_tags(
+    store: &mut StoreBuilder,
+    object_path: &str,
+    map: &[(FName, FValueId)],
+) -> EResult<u64> {
+    if map.len() > u16::MAX as usize {
+        return Err(eyre!(
+            "asset `{}` has {} tags, more than a tag map handle can address",
+            object_path,
+            map.len()
+        ));
+    }
+    Ok(store.push_map(map).to_int())
+}
+
+pub(crate) fn build_value(
+    names: &mut NamesBuilder,
+    store: &mut StoreBuilder,
+    value: &TagValue,
+) -> EResult<FValueId> {
+    let value = match value {
+        TagValue::String(s) if s.is_ascii() => StoreValue::AnsiString(s.clone()),
+        TagValue::String(s) => StoreValue::WideString(s.clone()),
+        TagValue::Name(n) => match names.intern(n)? {
+            n if n.number == 0 => StoreValue::NumberlessName(n),
+            n => StoreValue::Name(n),
+        },
+        TagValue::ExportPath { class, object_path } => {
+            let (package, object) = object_path
+                .split_once('.')
+                .ok_or_else(|| eyre!("malformed export path `{}`", object_path))?;
+            let path = FAssetRegistryExportPath {
+                class: names.intern(class)?,
+                object: names.intern(object)?,
+                package: names.intern(package)?,
+            };
+            match path.is_numberless() {
+                true => StoreValue::NumberlessExportPath(path),
+                false => StoreValue::ExportPath(path),
+            }
+        }
+        TagValue::Text(t) => StoreValue::LocalizedText(FText::from(t.as_str())),
+    };
+    Ok(store.push_value(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::Readable;
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> RegistryBuilder {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/Foo/Bar.Bar", "Blueprint")
+            .tag("ParentClass", TagValue::Name("Actor".to_string()))
+            .tag("Description", "shared")
+            .bundle("Client", &["/Game/Foo/Baz.Baz", "/Game/Foo/Baz.Baz:Sub"]);
+        builder
+            .add_asset("/Game/Foo/Baz.Baz", "Texture2D")
+            .tag("Description", "shared")
+            .tag(
+                "Generated",
+                TagValue::ExportPath {
+                    class: "BlueprintGeneratedClass".to_string(),
+                    object_path: "/Game/Foo/Bar.Bar_C".to_string(),
+                },
+            )
+            .tag("Instance", TagValue::Name("Actor_2".to_string()));
+        builder.add_dependency(
+            "/Game/Foo/Bar",
+            "/Game/Foo/Baz",
+            EDependencyCategory::Package,
+            EDependencyProperty::HARD | EDependencyProperty::GAME,
+        );
+        builder.add_dependency(
+            "Map:Entry",
+            "/Game/Foo/Bar",
+            EDependencyCategory::Manage,
+            EDependencyProperty::DIRECT,
+        );
+        builder
+            .add_package_data("/Game/Foo/Bar", 1234)
+            .guid([7; 16])
+            .cooked_hash([9; 16]);
+        builder
+    }
+
+    fn tags(registry: &AssetRegistry, asset: &AssetData) -> Vec<(String, StoreValue)> {
+        registry
+            .store
+            .pairs_for(FPartialMapHandle::from_int(asset.tags))
+            .unwrap()
+            .iter()
+            .map(|p| {
+                let value = registry
+                    .store
+                    .value(FValueId::from_int(p.value).unwrap())
+                    .unwrap();
+                (registry.names.try_resolve(p.key).unwrap(), value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_build() {
+        let registry = fixture().build().unwrap();
+        let names = &registry.names;
+
+        let [bar, baz] = &registry.assets.assets[..] else {
+            panic!("expected two assets");
+        };
+        assert_eq!(
+            names.try_resolve(bar.object_path).unwrap(),
+            "/Game/Foo/Bar.Bar"
+        );
+        assert_eq!(
+            names.try_resolve(bar.package_name).unwrap(),
+            "/Game/Foo/Bar"
+        );
+        assert_eq!(names.try_resolve(bar.package_path).unwrap(), "/Game/Foo");
+        assert_eq!(names.try_resolve(bar.asset_name).unwrap(), "Bar");
+        assert_eq!(names.try_resolve(bar.asset_class).unwrap(), "Blueprint");
+        assert_eq!(bar.bundles[0].bundles.len(), 2);
+        assert_eq!(
+            bar.bundles[0].bundles[1].sub_path_string,
+            FString::from("Sub")
+        );
+
+        // Equal values are stored once.
+        assert_eq!(registry.store.ansi_strings, vec!["shared"]);
+        assert_eq!(
+            tags(&registry, bar)[1],
+            (
+                "Description".to_string(),
+                StoreValue::AnsiString("shared".to_string())
+            )
+        );
+
+        // Baz has a numbered name value, but its keys are numberless.
+        let handle = FPartialMapHandle::from_int(baz.tags);
+        assert!(handle.has_numberless_keys);
+        assert_eq!(handle.num, 3);
+        assert_eq!(handle.pair_begin, 2);
+        let baz_tags = tags(&registry, baz);
+        assert!(matches!(baz_tags[1].1, StoreValue::NumberlessExportPath(_)));
+        assert!(matches!(baz_tags[2].1, StoreValue::Name(n) if n.number == 3));
+
+        for (s, hash) in names.strings.iter().zip(&names.hashes) {
+            assert_eq!(*hash, NamesBatch::hash(s), "{s}");
+        }
+
+        let nodes = &registry.dependencies.nodes;
+        let identifiers = nodes
+            .iter()
+            .map(|n| n.identifier.resolve(names).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            identifiers,
+            vec!["/Game/Foo/Bar", "/Game/Foo/Baz", "Map:Entry"]
+        );
+        assert_eq!(nodes[0].package_dependencies[0].node, 1);
+        assert_eq!(nodes[0].referencers, vec![2]);
+        assert_eq!(nodes[1].referencers, vec![0]);
+        assert_eq!(nodes[2].manage_dependencies[0].node, 0);
+
+        let package = &registry.package_data.packages[0];
+        assert_eq!(package.disk_size, 1234);
+        assert_eq!(package.cooked_hash, Some([9; 16]));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let registry = fixture().build().unwrap();
+        let mut buf = vec![];
+        let mut writer = Cursor::new(&mut buf);
+        registry.write(&mut writer).unwrap();
+        let mut reader = Cursor::new(&buf);
+        let read_registry = AssetRegistry::read(&mut reader).unwrap();
+        assert_eq!(read_registry, registry);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut builder = fixture();
+        builder.add_asset("/Game/Foo/Bar.Bar", "Blueprint");
+        assert!(builder.build().is_err());
+
+        let mut builder = RegistryBuilder::new();
+        builder.add_asset("/Game/Foo/Bar", "Blueprint");
+        assert!(builder.build().is_err());
+
+        let mut builder = RegistryBuilder::new();
+        builder.add_asset("Bar.Bar", "Blueprint");
+        assert!(builder.build().is_err());
+
+        let mut builder = fixture();
+        builder.add_package_data("/Game/Foo/Bar", 1);
+        assert!(builder.build().is_err());
+
+        // Names longer than a header can describe are rejected instead of truncated.
+        let long = "A".repeat(0x8000);
+        let mut builder = RegistryBuilder::new();
+        builder.add_asset(&format!("/Game/{long}.{long}"), "Blueprint");
+        assert!(builder.build().is_err());
+    }
+}
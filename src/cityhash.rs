@@ -0,0 +1,219 @@
+//! CityHash64 (v1.1), which Unreal uses to hash the lowercased strings of a [`NamesBatch`].
+//!
+//! [`NamesBatch`]: crate::names_batch::NamesBatch
+
+const K0: u64 = 0xc3a5c85c97cb3127;
+const K1: u64 = 0xb492b66fbe98f273;
+const K2: u64 = 0x9ae16a3b2f90404f;
+
+fn fetch64(s: &[u8], i: usize) -> u64 {
+    u64::from_le_bytes(s[i..i + 8].try_into().unwrap())
+}
+
+fn fetch32(s: &[u8], i: usize) -> u64 {
+    u32::from_le_bytes(s[i..i + 4].try_into().unwrap()) as u64
+}
+
+fn shift_mix(val: u64) -> u64 {
+    val ^ (val >> 47)
+}
+
+fn hash_len16(u: u64, v: u64) -> u64 {
+    hash_len16_mul(u, v, 0x9ddfea08eb382d69)
+}
+
+fn hash_len16_mul(u: u64, v: u64, mul: u64) -> u64 {
+    let mut a = (u ^ v).wrapping_mul(mul);
+    a ^= a >> 47;
+    let mut b = (v ^ a).wrapping_mul(mul);
+    b ^= b >> 47;
+    b.wrapping_mul(mul)
+}
+
+fn hash_len0to16(s: &[u8]) -> u64 {
+    let len = s.len();
+    if len >= 8 {
+        let mul = K2.wrapping_add(len as u64 * 2);
+        let a = fetch64(s, 0).wrapping_add(K2);
+        let b = fetch64(s, len - 8);
+        let c = b.rotate_right(37).wrapping_mul(mul).wrapping_add(a);
+        let d = a.rotate_right(25).wrapping_add(b).wrapping_mul(mul);
+        return hash_len16_mul(c, d, mul);
+    }
+    if len >= 4 {
+        let mul = K2.wrapping_add(len as u64 * 2);
+        let a = fetch32(s, 0);
+        return hash_len16_mul(len as u64 + (a << 3), fetch32(s, len - 4), mul);
+    }
+    if len > 0 {
+        let (a, b, c) = (s[0] as u32, s[len >> 1] as u32, s[len - 1] as u32);
+        let y = a + (b << 8);
+        let z = len as u32 + (c << 2);
+        return shift_mix((y as u64).wrapping_mul(K2) ^ (z as u64).wrapping_mul(K0))
+            .wrapping_mul(K2);
+    }
+    K2
+}
+
+fn hash_len17to32(s: &[u8]) -> u64 {
+    let len = s.len();
+    let mul = K2.wrapping_add(len as u64 * 2);
+    let a = fetch64(s, 0).wrapping_mul(K1);
+    let b = fetch64(s, 8);
+    let c = fetch64(s, len - 8).wrapping_mul(mul);
+    let d = fetch64(s, len - 16).wrapping_mul(K2);
+    hash_len16_mul(
+        a.wrapping_add(b)
+            .rotate_right(43)
+            .wrapping_add(c.rotate_right(30))
+            .wrapping_add(d),
+        a.wrapping_add(b.wrapping_add(K2).rotate_right(18))
+            .wrapping_add(c),
+        mul,
+    )
+}
+
+fn hash_len33to64(s: &[u8]) -> u64 {
+    let len = s.len();
+    let mul = K2.wrapping_add(len as u64 * 2);
+    let a = fetch64(s, 0).wrapping_mul(K2);
+    let b = fetch64(s, 8);
+    let c = fetch64(s, len - 24);
+    let d = fetch64(s, len - 32);
+    let e = fetch64(s, 16).wrapping_mul(K2);
+    let f = fetch64(s, 24).wrapping_mul(9);
+    let g = fetch64(s, len - 8);
+    let h = fetch64(s, len - 16).wrapping_mul(mul);
+    let u = a
+        .wrapping_add(g)
+        .rotate_right(43)
+        .wrapping_add(b.rotate_right(30).wrapping_add(c).wrapping_mul(9));
+    let v = (a.wrapping_add(g) ^ d).wrapping_add(f).wrapping_add(1);
+    let w = u
+        .wrapping_add(v)
+        .wrapping_mul(mul)
+        .swap_bytes()
+        .wrapping_add(h);
+    let x = e.wrapping_add(f).rotate_right(42).wrapping_add(c);
+    let y = v
+        .wrapping_add(w)
+        .wrapping_mul(mul)
+        .swap_bytes()
+        .wrapping_add(g)
+        .wrapping_mul(mul);
+    let z = e.wrapping_add(f).wrapping_add(c);
+    let a = x
+        .wrapping_add(z)
+        .wrapping_mul(mul)
+        .wrapping_add(y)
+        .swap_bytes()
+        .wrapping_add(b);
+    let b = shift_mix(
+        z.wrapping_add(a)
+            .wrapping_mul(mul)
+            .wrapping_add(d)
+            .wrapping_add(h),
+    )
+    .wrapping_mul(mul);
+    b.wrapping_add(x)
+}
+
+fn weak_hash_len32_with_seeds(s: &[u8], i: usize, mut a: u64, mut b: u64) -> (u64, u64) {
+    let (w, x, y, z) = (
+        fetch64(s, i),
+        fetch64(s, i + 8),
+        fetch64(s, i + 16),
+        fetch64(s, i + 24),
+    );
+    a = a.wrapping_add(w);
+    b = b.wrapping_add(a).wrapping_add(z).rotate_right(21);
+    let c = a;
+    a = a.wrapping_add(x).wrapping_add(y);
+    b = b.wrapping_add(a.rotate_right(44));
+    (a.wrapping_add(z), b.wrapping_add(c))
+}
+
+pub fn city_hash64(s: &[u8]) -> u64 {
+    let len = s.len();
+    if len <= 16 {
+        return hash_len0to16(s);
+    } else if len <= 32 {
+        return hash_len17to32(s);
+    } else if len <= 64 {
+        return hash_len33to64(s);
+    }
+
+    let mut x = fetch64(s, len - 40);
+    let mut y = fetch64(s, len - 16).wrapping_add(fetch64(s, len - 56));
+    let mut z = hash_len16(
+        fetch64(s, len - 48).wrapping_add(len as u64),
+        fetch64(s, len - 24),
+    );
+    let mut v = weak_hash_len32_with_seeds(s, len - 64, len as u64, z);
+    let mut w = weak_hash_len32_with_seeds(s, len - 32, y.wrapping_add(K1), x);
+    x = x.wrapping_mul(K1).wrapping_add(fetch64(s, 0));
+
+    for chunk in (0..(len - 1) & !63).step_by(64) {
+        x = x
+            .wrapping_add(y)
+            .wrapping_add(v.0)
+            .wrapping_add(fetch64(s, chunk + 8))
+            .rotate_right(37)
+            .wrapping_mul(K1);
+        y = y
+            .wrapping_add(v.1)
+            .wrapping_add(fetch64(s, chunk + 48))
+            .rotate_right(42)
+            .wrapping_mul(K1);
+        x ^= w.1;
+        y = y.wrapping_add(v.0).wrapping_add(fetch64(s, chunk + 40));
+        z = z.wrapping_add(w.0).rotate_right(33).wrapping_mul(K1);
+        v = weak_hash_len32_with_seeds(s, chunk, v.1.wrapping_mul(K1), x.wrapping_add(w.0));
+        w = weak_hash_len32_with_seeds(
+            s,
+            chunk + 32,
+            z.wrapping_add(w.1),
+            y.wrapping_add(fetch64(s, chunk + 16)),
+        );
+        std::mem::swap(&mut z, &mut x);
+    }
+
+    hash_len16(
+        hash_len16(v.0, w.0)
+            .wrapping_add(shift_mix(y).wrapping_mul(K1))
+            .wrapping_add(z),
+        hash_len16(v.1, w.1).wrapping_add(x),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_hashes() {
+        // Lowercased names and their hashes from `test_assets/minimal.bin`, covering every length
+        // bucket.
+        let cases: &[(&str, u64)] = &[
+            ("world", 16436542438370751598),
+            ("/game/maps", 15756284783577604072),
+            ("minimapuprojectentry_c", 850795641908635411),
+            ("/game/maps/minimapuprojectentry", 11901466460052063120),
+            (
+                "/game/maps/minimapuprojectentry.minimapuprojectentry_c",
+                3120651910779265299,
+            ),
+            (
+                "/engine/functions/engine_materialfunctions02/utility/makefloat2.makefloat2",
+                16620855974779845972,
+            ),
+            (
+                "/engine/enginematerials/widget3dpassthrough_translucent_onesided.widget3dpassthrough_translucent_onesided",
+                9095114207271848758,
+            ),
+        ];
+        for (s, hash) in cases {
+            assert_eq!(city_hash64(s.as_bytes()), *hash, "{s}");
+        }
+    }
+}
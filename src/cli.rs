@@ -0,0 +1,883 @@
+//! Command line interface: `<command> [--option[=value]...] <AssetRegistry.bin>`. Without a known
+//! command, the only argument is the registry to parse (writing a `trace.json` of the parse).
+
+use std::io::{IsTerminal as _, Write as _};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use color_eyre::eyre::{eyre, Result as EResult};
+use fs_err as fs;
+use tracing::*;
+
+use asset_register_bin_experiments::asset_registry::AssetRegistry;
+use asset_register_bin_experiments::asset_registry_ref::{map_file, AssetRegistryRef};
+use asset_register_bin_experiments::dependencies::{
+    AllowList, DependencyFilter, DependencyHit, EDependencyCategory, EDependencyProperty,
+    GraphFormat,
+};
+use asset_register_bin_experiments::hexdump::HexDump;
+use asset_register_bin_experiments::html::write_html;
+use asset_register_bin_experiments::lazy_registry::LazyRegistry;
+use asset_register_bin_experiments::merge::ConflictPolicy;
+use asset_register_bin_experiments::path_tree::PathTree;
+use asset_register_bin_experiments::query::{self, Query};
+use asset_register_bin_experiments::read::Readable as _;
+use asset_register_bin_experiments::rename::RedirectorPolicy;
+use asset_register_bin_experiments::validate::Severity;
+use asset_register_bin_experiments::write::Writable as _;
+
+const USAGE: &str = "\
+usage: asset-register-bin-experiments <AssetRegistry.bin>
+       asset-register-bin-experiments info <AssetRegistry.bin>
+       asset-register-bin-experiments find [--class=<class>] [--tags] <pattern> <AssetRegistry.bin>
+       asset-register-bin-experiments query [--json] <expression> <AssetRegistry.bin>
+       asset-register-bin-experiments stats [--top=<n>] <AssetRegistry.bin>
+       asset-register-bin-experiments tree [--root=<folder>] [--depth=<n>] [--assets] <AssetRegistry.bin>
+       asset-register-bin-experiments deps [--referencers] [--transitive] [--category=<package,manage,name>]
+              [--hard | --soft] [--game | --editor-only] [--build] <identifier> <AssetRegistry.bin>
+       asset-register-bin-experiments graph [--format=dot|graphml|csv] [--roots=<identifier,...>] [--depth=<n>]
+              [--category=...] [--hard | --soft] [--game | --editor-only] [--build] [--output=<file>] <AssetRegistry.bin>
+       asset-register-bin-experiments cycles [--allow-list=<file> [--update]] <AssetRegistry.bin>
+       asset-register-bin-experiments check [--deny-warnings] [--lenient] <AssetRegistry.bin>
+       asset-register-bin-experiments coverage [--gaps] <AssetRegistry.bin>
+       asset-register-bin-experiments spans [--json | --at=<offset>] <AssetRegistry.bin>
+       asset-register-bin-experiments hexdump [--range=<start>..<end>] [--full] [--no-color] <AssetRegistry.bin>
+       asset-register-bin-experiments html [--output=<file.html>] <AssetRegistry.bin>
+       asset-register-bin-experiments merge [--conflicts=overlay|base|error] --output=<file> <base.bin> <overlay.bin>...
+       asset-register-bin-experiments compact --output=<file> <AssetRegistry.bin>
+       asset-register-bin-experiments rename [--folder] [--redirectors] --output=<file> <from> <to> <AssetRegistry.bin>
+       asset-register-bin-experiments edit [--remove-package=<package>] [--remove-asset=<object path>]
+              [--set-class=<object path>=<class>] [--set-tag=<object path>:<key>=<value>]
+              [--remove-tag=<object path>:<key>]... --output=<file> <AssetRegistry.bin>";
+
+/// Positional arguments and `--key[=value]` options, in order.
+pub(crate) struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    pub(crate) fn parse(args: impl IntoIterator<Item = String>) -> Self {
+        let mut positional = vec![];
+        let mut options = vec![];
+        for arg in args {
+            match arg.strip_prefix("--") {
+                Some(option) => match option.split_once('=') {
+                    Some((key, value)) => options.push((key.to_string(), Some(value.to_string()))),
+                    None => options.push((option.to_string(), None)),
+                },
+                None => positional.push(arg),
+            }
+        }
+        Args {
+            positional,
+            options,
+        }
+    }
+
+    fn command(&self) -> Option<&str> {
+        self.positional.first().map(String::as_str)
+    }
+
+    /// The `i`th positional argument after the command.
+    fn positional(&self, i: usize, what: &str) -> EResult<&str> {
+        self.positional
+            .get(i + 1)
+            .map(String::as_str)
+            .ok_or_else(|| eyre!("missing {}\n{}", what, USAGE))
+    }
+
+    /// The positional arguments from the `i`th after the command on; at least one.
+    fn positionals(&self, i: usize, what: &str) -> EResult<&[String]> {
+        self.positional(i, what)?;
+        Ok(&self.positional[i + 1..])
+    }
+
+    /// All options, in the order given.
+    fn options(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.options
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_deref()))
+    }
+
+    /// The value of `--name=value`.
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(key, _)| key == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(key, _)| key == name)
+    }
+
+    /// Fail on options the command doesn't know about.
+    fn expect_options(&self, known: &[&str]) -> EResult<()> {
+        match self
+            .options
+            .iter()
+            .find(|(key, _)| !known.contains(&key.as_str()))
+        {
+            Some((key, _)) => Err(eyre!("unknown option `--{}`\n{}", key, USAGE)),
+            None => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn run(args: Args) -> EResult<ExitCode> {
+    match args.command() {
+        Some("info") => info(&args),
+        Some("find") => find(&args),
+        Some("query") => query(&args),
+        Some("stats") => stats(&args),
+        Some("tree") => tree(&args),
+        Some("deps") => deps(&args),
+        Some("graph") => graph(&args),
+        Some("cycles") => cycles(&args),
+        Some("check") => check(&args),
+        Some("coverage") => coverage(&args),
+        Some("spans") => spans(&args),
+        Some("hexdump") => hexdump(&args),
+        Some("html") => html(&args),
+        Some("merge") => merge(&args),
+        Some("compact") => compact(&args),
+        Some("rename") => rename(&args),
+        Some("edit") => edit(&args),
+        Some(path) => parse(Path::new(path)),
+        None => Err(eyre!(
+            "please specify path to test AssetRegister.bin\n{}",
+            USAGE
+        )),
+    }
+}
+
+fn read_file(path: &Path) -> EResult<Vec<u8>> {
+    if !path.exists() {
+        return Err(eyre!(
+            "the path specified `{}` cannot be found, please double check your input",
+            path.display()
+        ));
+    }
+    let raw = fs::read(path)?;
+    info!(asset_register_len = raw.len());
+    Ok(raw)
+}
+
+/// Read a registry through a memory map, which is much faster than [`read_file`] and
+/// [`AssetRegistry::read`] for large registries.
+fn read_registry(path: &Path) -> EResult<AssetRegistry> {
+    let map = map_file(path)?;
+    info!(asset_register_len = map.len());
+    Ok(AssetRegistryRef::parse(&map)?.into_owned())
+}
+
+fn parse(path: &Path) -> EResult<ExitCode> {
+    if !cfg!(feature = "trace-fields") {
+        warn!("built without the `trace-fields` feature, trace.json will only show sections");
+    }
+    let raw = read_file(path)?;
+    let mut reader = std::io::Cursor::new(&raw);
+
+    let asset_registry =
+        ser_hex::CounterSubscriber::read("trace.json", &mut reader, AssetRegistry::read)?;
+
+    debug!("asset_registry = {:#?}", asset_registry);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the version and the sections of the registry without decoding their contents.
+fn info(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&[])?;
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let map = map_file(&path)?;
+    let registry = LazyRegistry::open(&map)?;
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}: {} bytes", path.display(), map.len())?;
+    for section in registry.sections()? {
+        writeln!(
+            stdout,
+            "{:#010X}..{:#010X} {:>10} {:<12} {}",
+            section.range.start,
+            section.range.end,
+            section.range.len(),
+            section.name,
+            section.contents
+        )?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// List the assets whose object path contains a pattern (ignoring ASCII case), optionally only
+/// those of a class, and with `--tags` their tags. Only the matching assets are decoded.
+fn find(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["class", "tags"])?;
+    let pattern = args.positional(0, "pattern")?;
+    let path = PathBuf::from(args.positional(1, "path to AssetRegistry.bin")?);
+    let map = map_file(&path)?;
+    let registry = LazyRegistry::open(&map)?;
+    let mut stdout = std::io::stdout().lock();
+    for i in registry.find(pattern)? {
+        let asset = registry.asset(i)?;
+        let class = registry.resolve(asset.asset_class)?;
+        if args
+            .option("class")
+            .is_some_and(|wanted| !class.eq_ignore_ascii_case(wanted))
+        {
+            continue;
+        }
+        writeln!(
+            stdout,
+            "{} ({})",
+            registry.resolve(asset.object_path)?,
+            class
+        )?;
+        if args.flag("tags") {
+            for (key, value) in registry.tags(&asset)? {
+                writeln!(stdout, "    {key} = {value}")?;
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// List the assets matching a query expression (see [`query`]), as a table with a column
+/// for each tag the query mentions or as JSON.
+fn query(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["json"])?;
+    let expr = args.positional(0, "query expression")?;
+    let path = PathBuf::from(args.positional(1, "path to AssetRegistry.bin")?);
+    let query = Query::parse(expr)?;
+    let assets = read_registry(&path)?.assets_matching(&query)?;
+    let output = match args.flag("json") {
+        true => query::to_json(&assets),
+        false => query::to_table(&assets, &query.tag_keys()),
+    };
+    write!(std::io::stdout().lock(), "{output}")?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the statistics of a registry, with the `--top` (default 20) most frequent tag keys and
+/// values.
+fn stats(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["top"])?;
+    let top = match args.option("top") {
+        Some(top) => top
+            .parse()
+            .map_err(|e| eyre!("invalid `--top={}`: {}", top, e))?,
+        None => 20,
+    };
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let map = map_file(&path)?;
+    let sections = LazyRegistry::open(&map)?.sections()?;
+    let stats = AssetRegistryRef::parse(&map)?
+        .into_owned()
+        .stats(top, &sections)?;
+    write!(std::io::stdout().lock(), "{stats}")?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the folders under `--root` (default `/`) down to `--depth` levels, each with the number
+/// of assets and the disk size of the packages it contains, including sub-folders. With
+/// `--assets`, the assets directly in each folder are listed too.
+fn tree(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["root", "depth", "assets"])?;
+    let root = args.option("root").unwrap_or(PathTree::ROOT);
+    let depth = match args.option("depth") {
+        Some(depth) => depth
+            .parse()
+            .map_err(|e| eyre!("invalid `--depth={}`: {}", depth, e))?,
+        None => usize::MAX,
+    };
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let registry = read_registry(&path)?;
+    let tree = registry.path_tree()?;
+
+    let mut folders = vec![];
+    tree.walk(root, depth, &mut |folder, depth| {
+        folders.push((folder, depth));
+        true
+    })?;
+
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    for (folder_path, depth) in folders {
+        let folder = tree.get(folder_path).unwrap();
+        let indent = "  ".repeat(depth);
+        let name = match depth {
+            0 => folder_path,
+            _ => PathTree::name(folder_path),
+        };
+        writeln!(
+            stdout,
+            "{:>8} {:>12}  {}{}",
+            folder.total_assets, folder.total_size, indent, name
+        )?;
+        if args.flag("assets") {
+            for &i in &folder.assets {
+                let asset = &registry.assets.assets[i];
+                writeln!(
+                    stdout,
+                    "{:>21}  {}  {} ({})",
+                    "",
+                    indent,
+                    registry.names.try_resolve(asset.asset_name)?,
+                    registry.names.try_resolve(asset.asset_class)?
+                )?;
+            }
+        }
+    }
+    stdout.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the dependencies (or with `--referencers`, the referencers) of a node such as
+/// `/Game/Maps/Arena`, following only the selected categories and properties. With
+/// `--transitive`, every node reached is printed with the chain of edges that leads to it.
+fn deps(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&[
+        "referencers",
+        "transitive",
+        "category",
+        "hard",
+        "soft",
+        "game",
+        "editor-only",
+        "build",
+    ])?;
+    let identifier = args.positional(0, "dependency node identifier")?;
+    let path = PathBuf::from(args.positional(1, "path to AssetRegistry.bin")?);
+    let filter = dependency_filter(args)?;
+    let registry = read_registry(&path)?;
+    let graph = registry.dependency_graph()?;
+    let node = graph.find(identifier)?;
+
+    let hits = match (args.flag("referencers"), args.flag("transitive")) {
+        (false, false) => graph
+            .dependencies(node, &filter)
+            .into_iter()
+            .map(|edge| DependencyHit {
+                node: edge.to,
+                path: vec![edge],
+            })
+            .collect(),
+        (true, false) => graph
+            .referencers(node, &filter)
+            .into_iter()
+            .map(|edge| DependencyHit {
+                node: edge.from,
+                path: vec![edge],
+            })
+            .collect(),
+        (false, true) => graph.transitive_dependencies(node, &filter),
+        (true, true) => graph.transitive_referencers(node, &filter),
+    };
+
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    for hit in hits {
+        match hit.path.as_slice() {
+            [edge] => writeln!(stdout, "{}  ({})", graph.identifier(hit.node), edge.label())?,
+            path => {
+                writeln!(stdout, "{}", graph.identifier(hit.node))?;
+                for edge in path {
+                    writeln!(
+                        stdout,
+                        "    {} -> {}  ({})",
+                        graph.identifier(edge.from),
+                        graph.identifier(edge.to),
+                        edge.label()
+                    )?;
+                }
+            }
+        }
+    }
+    stdout.flush()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The [`DependencyFilter`] selected by the `deps` options; everything by default.
+fn dependency_filter(args: &Args) -> EResult<DependencyFilter> {
+    let mut filter = DependencyFilter::default();
+    if let Some(categories) = args.option("category") {
+        filter.categories = categories
+            .split(',')
+            .map(|category| match category {
+                "package" => Ok(EDependencyCategory::Package),
+                "manage" => Ok(EDependencyCategory::Manage),
+                "name" => Ok(EDependencyCategory::SearchableName),
+                _ => Err(eyre!("invalid `--category={}`\n{}", categories, USAGE)),
+            })
+            .collect::<EResult<_>>()?;
+    }
+    for (flag, property, required) in [
+        ("hard", EDependencyProperty::HARD, true),
+        ("soft", EDependencyProperty::HARD, false),
+        ("game", EDependencyProperty::GAME, true),
+        ("editor-only", EDependencyProperty::GAME, false),
+        ("build", EDependencyProperty::BUILD, true),
+    ] {
+        if args.flag(flag) {
+            match required {
+                true => filter.required = filter.required | property,
+                false => filter.excluded = filter.excluded | property,
+            }
+        }
+    }
+    Ok(filter)
+}
+
+/// Export the dependency graph, or with `--roots` the part reachable from those nodes within
+/// `--depth` edges, as DOT (the default), GraphML or CSV. The `deps` options select the edges.
+/// Output goes to `--output` or stdout; CSV needs `--output=<prefix>` and writes
+/// `<prefix>.nodes.csv` and `<prefix>.edges.csv`.
+fn graph(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&[
+        "format",
+        "roots",
+        "depth",
+        "output",
+        "category",
+        "hard",
+        "soft",
+        "game",
+        "editor-only",
+        "build",
+    ])?;
+    let depth = match args.option("depth") {
+        Some(depth) => depth
+            .parse()
+            .map_err(|e| eyre!("invalid `--depth={}`: {}", depth, e))?,
+        None => usize::MAX,
+    };
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let filter = dependency_filter(args)?;
+    let registry = read_registry(&path)?;
+    let graph = registry.dependency_graph()?;
+    let roots = match args.option("roots") {
+        Some(roots) => roots
+            .split(',')
+            .map(|root| graph.find(root))
+            .collect::<EResult<Vec<_>>>()?,
+        None => vec![],
+    };
+    let export = graph.export(&roots, depth, &filter)?;
+
+    let format: GraphFormat = args.option("format").unwrap_or("dot").parse()?;
+    let outputs = match format {
+        GraphFormat::Dot => vec![(args.option("output").map(PathBuf::from), export.to_dot())],
+        GraphFormat::GraphMl => vec![(
+            args.option("output").map(PathBuf::from),
+            export.to_graphml(),
+        )],
+        GraphFormat::Csv => {
+            let prefix = args
+                .option("output")
+                .ok_or_else(|| eyre!("`--format=csv` needs `--output=<prefix>`\n{}", USAGE))?;
+            vec![
+                (
+                    Some(PathBuf::from(format!("{prefix}.nodes.csv"))),
+                    export.to_nodes_csv(),
+                ),
+                (
+                    Some(PathBuf::from(format!("{prefix}.edges.csv"))),
+                    export.to_edges_csv(),
+                ),
+            ]
+        }
+    };
+    for (output, contents) in outputs {
+        match output {
+            Some(output) => {
+                fs::write(&output, contents)?;
+                info!("wrote {}", output.display());
+            }
+            None => write!(std::io::stdout().lock(), "{contents}")?,
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print every cycle of hard package dependencies with the edges closing it, marking those not
+/// in the `--allow-list` file as new; fail if there are new cycles. With `--update`, the
+/// allow-list is rewritten with the current cycles instead.
+fn cycles(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["allow-list", "update"])?;
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let allow_list_path = args.option("allow-list").map(PathBuf::from);
+    let registry = read_registry(&path)?;
+    let graph = registry.dependency_graph()?;
+    let cycles = graph.hard_cycles();
+
+    if args.flag("update") {
+        let allow_list_path = allow_list_path
+            .ok_or_else(|| eyre!("`--update` needs `--allow-list=<file>`\n{}", USAGE))?;
+        let allow_list = AllowList {
+            cycles: cycles.iter().map(|cycle| cycle.key(&graph)).collect(),
+        };
+        fs::write(&allow_list_path, allow_list.to_string())?;
+        info!(
+            "wrote {} cycles to {}",
+            allow_list.cycles.len(),
+            allow_list_path.display()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    let allow_list = match &allow_list_path {
+        Some(path) => AllowList::parse(&fs::read_to_string(path)?),
+        None => AllowList::default(),
+    };
+
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    let mut new = 0;
+    for cycle in &cycles {
+        let key = cycle.key(&graph);
+        let status = match allow_list.contains(&key) {
+            true => "allowed",
+            false => {
+                new += 1;
+                "new"
+            }
+        };
+        writeln!(
+            stdout,
+            "{} cycle of {} packages:",
+            status,
+            cycle.members.len()
+        )?;
+        for &member in &cycle.members {
+            writeln!(stdout, "    {}", graph.identifier(member))?;
+        }
+        for edge in &cycle.edges {
+            writeln!(
+                stdout,
+                "    {} -> {}  ({})",
+                graph.identifier(edge.from),
+                graph.identifier(edge.to),
+                edge.label()
+            )?;
+        }
+    }
+    writeln!(stdout, "{} cycles, {} new", cycles.len(), new)?;
+    stdout.flush()?;
+
+    let current = cycles
+        .iter()
+        .map(|cycle| cycle.key(&graph))
+        .collect::<std::collections::BTreeSet<_>>();
+    for stale in allow_list.cycles.difference(&current) {
+        warn!("allowed cycle no longer exists: {}", stale);
+    }
+    Ok(match new {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    })
+}
+
+/// Print every validation finding; fail if there are errors (or warnings, with
+/// `--deny-warnings`). With `--lenient`, parse problems are reported alongside the findings
+/// instead of aborting the check.
+fn check(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["deny-warnings", "lenient"])?;
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let (registry, diagnostics) = match args.flag("lenient") {
+        true => AssetRegistry::read_lenient(&read_file(&path)?),
+        false => (read_registry(&path)?, vec![]),
+    };
+
+    let findings = registry.validate();
+    let mut stdout = std::io::stdout().lock();
+    for diagnostic in &diagnostics {
+        writeln!(stdout, "{diagnostic}")?;
+    }
+    for finding in &findings {
+        writeln!(stdout, "{finding}")?;
+    }
+    let severities = diagnostics
+        .iter()
+        .map(|d| d.severity)
+        .chain(findings.iter().map(|f| f.severity))
+        .collect::<Vec<_>>();
+    let errors = severities.iter().filter(|s| **s == Severity::Error).count();
+    let warnings = severities.len() - errors;
+    writeln!(
+        stdout,
+        "{}: {} error(s), {} warning(s)",
+        path.display(),
+        errors,
+        warnings
+    )?;
+
+    let failed = errors > 0 || (args.flag("deny-warnings") && warnings > 0);
+    Ok(match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    })
+}
+
+/// Print which structure consumed which bytes (or only the unconsumed ranges, with `--gaps`),
+/// followed by the parse diagnostics.
+fn coverage(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["gaps"])?;
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let raw = read_file(&path)?;
+    let (coverage, diagnostics) = AssetRegistry::coverage(&raw);
+
+    let mut stdout = std::io::stdout().lock();
+    match args.flag("gaps") {
+        true => {
+            for gap in coverage.gaps() {
+                writeln!(
+                    stdout,
+                    "{:#010X}..{:#010X} {:>8}",
+                    gap.start,
+                    gap.end,
+                    gap.end - gap.start
+                )?;
+            }
+        }
+        false => write!(stdout, "{coverage}")?,
+    }
+    for diagnostic in &diagnostics {
+        writeln!(stdout, "{diagnostic}")?;
+    }
+    writeln!(
+        stdout,
+        "{}: {} of {} bytes covered ({:.1}%)",
+        path.display(),
+        coverage.covered_bytes(),
+        coverage.len,
+        100.0 * coverage.covered_bytes() as f64 / coverage.len.max(1) as f64
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal offset.
+fn parse_offset(s: &str) -> EResult<u64> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|e| eyre!("invalid offset `{}`: {}", s, e))
+}
+
+/// Print the span of every parsed structure and field, as text or JSON, or only the spans
+/// containing `--at`. The file is parsed leniently; diagnostics go to stderr.
+fn spans(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["json", "at"])?;
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let raw = read_file(&path)?;
+    let ((_, diagnostics), spans) = AssetRegistry::read_lenient_with_spans(&raw);
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let selected = match args.option("at") {
+        Some(at) => spans.spans_at(parse_offset(at)?),
+        None if args.flag("json") => {
+            write!(stdout, "{}", spans.to_json())?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => spans.spans.iter().collect(),
+    };
+    for span in selected {
+        writeln!(
+            stdout,
+            "{:#010X}..{:#010X} {}",
+            span.range.start, span.range.end, span.path
+        )?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Print the file annotated with the owning structure and decoded value of every region. Long
+/// regions are shortened unless `--full` is given; colors are used when writing to a terminal.
+fn hexdump(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["range", "full", "no-color"])?;
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let raw = read_file(&path)?;
+    let ((registry, diagnostics), spans) = AssetRegistry::read_lenient_with_spans(&raw);
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+
+    let mut hexdump = HexDump::new(&raw, &spans, &registry);
+    if let Some(range) = args.option("range") {
+        let (start, end) = range
+            .split_once("..")
+            .ok_or_else(|| eyre!("expected `--range=<start>..<end>`, got `{}`", range))?;
+        hexdump.range = parse_offset(start)?..parse_offset(end)?;
+    }
+    if !args.flag("full") {
+        hexdump.max_lines = Some(8);
+    }
+    let stdout = std::io::stdout();
+    hexdump.color = !args.flag("no-color") && stdout.is_terminal();
+    hexdump.write(&mut std::io::BufWriter::new(stdout.lock()))?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Write a standalone HTML page with a tree of the registry next to a hex view of the file.
+/// Defaults to `<AssetRegistry.bin>.html` next to the input.
+fn html(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["output"])?;
+    let path = PathBuf::from(args.positional(0, "path to AssetRegistry.bin")?);
+    let raw = read_file(&path)?;
+    let output = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => {
+            let mut output = path.clone().into_os_string();
+            output.push(".html");
+            PathBuf::from(output)
+        }
+    };
+    let title = path
+        .file_name()
+        .map_or_else(|| path.to_string_lossy(), |n| n.to_string_lossy());
+    let mut out = std::io::BufWriter::new(fs::File::create(&output)?);
+    write_html(&mut out, &title, &raw)?;
+    out.flush()?;
+    info!("wrote {}", output.display());
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The `--output` path a command writes its registry to.
+fn output_path(args: &Args) -> EResult<PathBuf> {
+    args.option("output")
+        .map(PathBuf::from)
+        .ok_or_else(|| eyre!("missing `--output=<file>`\n{}", USAGE))
+}
+
+fn write_registry(registry: &AssetRegistry, output: &Path) -> EResult<()> {
+    let mut out = std::io::BufWriter::new(fs::File::create(output)?);
+    registry.write(&mut out)?;
+    out.flush()?;
+    info!("wrote {}", output.display());
+    Ok(())
+}
+
+/// Merge overlay registries into a base registry, in order, printing every entry that lost a
+/// conflict. `--conflicts` picks the winner: `overlay` (the default), `base` or `error`.
+fn merge(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["conflicts", "output"])?;
+    let policy = match args.option("conflicts").unwrap_or("overlay") {
+        "overlay" => ConflictPolicy::OverlayWins,
+        "base" => ConflictPolicy::BaseWins,
+        "error" => ConflictPolicy::Error,
+        other => return Err(eyre!("invalid `--conflicts={}`\n{}", other, USAGE)),
+    };
+    let output = output_path(args)?;
+    let base = read_registry(Path::new(
+        args.positional(0, "path to base AssetRegistry.bin")?,
+    ))?;
+    let overlays = args
+        .positionals(1, "path to overlay AssetRegistry.bin")?
+        .iter()
+        .map(|path| read_registry(Path::new(path)))
+        .collect::<EResult<Vec<_>>>()?;
+
+    let (merged, report) = AssetRegistry::merge(&base, &overlays, policy)?;
+    let mut stdout = std::io::stdout().lock();
+    for entry in &report.overridden {
+        writeln!(
+            stdout,
+            "{:?} {}: kept registry {}, discarded registry {}",
+            entry.kind, entry.key, entry.kept, entry.discarded
+        )?;
+    }
+    write_registry(&merged, &output)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Drop the names, store values and pairs nothing references and write the smaller registry.
+fn compact(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["output"])?;
+    let output = output_path(args)?;
+    let mut registry = read_registry(Path::new(args.positional(0, "path to AssetRegistry.bin")?))?;
+    let report = registry.compact()?;
+    writeln!(
+        std::io::stdout().lock(),
+        "removed {} name(s), {} value(s), {} pair(s); saved {} bytes",
+        report.names_removed,
+        report.values_removed,
+        report.pairs_removed,
+        report.bytes_saved()
+    )?;
+    write_registry(&registry, &output)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Rename a package, or with `--folder` move every package under a folder, printing each
+/// rename. With `--redirectors`, an `ObjectRedirector` is left at every old location.
+fn rename(args: &Args) -> EResult<ExitCode> {
+    args.expect_options(&["folder", "redirectors", "output"])?;
+    let from = args.positional(0, "package or folder to rename")?;
+    let to = args.positional(1, "new package or folder name")?;
+    let output = output_path(args)?;
+    let mut registry = read_registry(Path::new(args.positional(2, "path to AssetRegistry.bin")?))?;
+    let redirectors = match args.flag("redirectors") {
+        true => RedirectorPolicy::Create,
+        false => RedirectorPolicy::Skip,
+    };
+    let report = match args.flag("folder") {
+        true => registry.move_folder(from, to, redirectors)?,
+        false => registry.rename_package(from, to, redirectors)?,
+    };
+    let mut stdout = std::io::stdout().lock();
+    for (old, new) in &report.renamed {
+        writeln!(stdout, "{old} -> {new}")?;
+    }
+    writeln!(stdout, "{} redirector(s) created", report.redirectors)?;
+    write_registry(&registry, &output)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Apply the edit options in the order given and write the edited registry. Options can be
+/// repeated; `--set-tag` stores its value as a string.
+fn edit(args: &Args) -> EResult<ExitCode> {
+    const EDITS: [&str; 5] = [
+        "remove-package",
+        "remove-asset",
+        "set-class",
+        "set-tag",
+        "remove-tag",
+    ];
+    args.expect_options(&[&EDITS[..], &["output"]].concat())?;
+    let output = output_path(args)?;
+    let mut registry = read_registry(Path::new(args.positional(0, "path to AssetRegistry.bin")?))?;
+    let mut session = registry.edit();
+    for (edit, value) in args.options() {
+        if !EDITS.contains(&edit) {
+            continue;
+        }
+        let value = value.ok_or_else(|| eyre!("missing value for `--{}`\n{}", edit, USAGE))?;
+        let invalid = || eyre!("invalid `--{}={}`\n{}", edit, value, USAGE);
+        match edit {
+            "remove-package" => {
+                session.remove_package(value)?;
+            }
+            "remove-asset" => {
+                session.remove_asset(value)?;
+            }
+            "set-class" => {
+                let (object_path, class) = value.split_once('=').ok_or_else(invalid)?;
+                session.set_asset_class(object_path, class)?;
+            }
+            "set-tag" => {
+                let (target, tag_value) = value.split_once('=').ok_or_else(invalid)?;
+                let (object_path, key) = target.rsplit_once(':').ok_or_else(invalid)?;
+                session.set_tag(object_path, key, tag_value)?;
+            }
+            "remove-tag" => {
+                let (object_path, key) = value.rsplit_once(':').ok_or_else(invalid)?;
+                if !session.remove_tag(object_path, key)? {
+                    warn!("`{}` has no tag `{}`", object_path, key);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+    drop(session);
+    write_registry(&registry, &output)?;
+    Ok(ExitCode::SUCCESS)
+}
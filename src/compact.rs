@@ -0,0 +1,200 @@
+//! Garbage collection of the name table and the store.
+//!
+//! Edits and merges only ever append names, values and pairs, so unreferenced entries pile up.
+//! Compaction copies every section into fresh tables, which interns exactly the live names and
+//! values and renumbers all `FName::index`es, `FValueId`s and tag map handles.
+
+use color_eyre::eyre::Result as EResult;
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::assets::{AssetDataCollection, AssetPackageDataCollection};
+use crate::dependencies::{DependencySection, FDependsNode};
+use crate::merge::Merger;
+use crate::store_data::StoreData;
+use crate::write::SerializedSize;
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct CompactReport {
+    pub names_removed: usize,
+    pub values_removed: usize,
+    pub pairs_removed: usize,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+}
+
+impl CompactReport {
+    pub fn bytes_saved(&self) -> usize {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+fn value_count(store: &StoreData) -> usize {
+    store.text_data.len()
+        + store.numberless_names.len()
+        + store.names.len()
+        + store.numberless_export_paths.len()
+        + store.export_paths.len()
+        + store.ansi_strings.len()
+        + store.wide_strings.len()
+}
+
+fn pair_count(store: &StoreData) -> usize {
+    store.numberless_pairs.len() + store.pairs.len()
+}
+
+impl AssetRegistry {
+    /// Drop every name, store value and pair that nothing references. Entry order is preserved;
+    /// names and values are renumbered in order of first use.
+    #[instrument(name = "AssetRegistry_compact", skip_all)]
+    pub fn compact(&mut self) -> EResult<CompactReport> {
+        let bytes_before = self.serialized_size() as usize;
+
+        let sources = [&*self];
+        let mut merger = Merger::new(&sources, self.names.hash_version);
+        let assets = self
+            .assets
+            .assets
+            .iter()
+            .map(|a| merger.asset(0, a))
+            .collect::<EResult<Vec<_>>>()?;
+        let identity = (0..self.dependencies.nodes.len() as i32).collect::<Vec<_>>();
+        let nodes = self
+            .dependencies
+            .nodes
+            .iter()
+            .map(|n| {
+                Ok(FDependsNode {
+                    referencers: n.referencers.clone(),
+                    ..merger.node(0, n, &identity)?
+                })
+            })
+            .collect::<EResult<Vec<_>>>()?;
+        let packages = self
+            .package_data
+            .packages
+            .iter()
+            .map(|p| merger.package_data(0, p))
+            .collect::<EResult<Vec<_>>>()?;
+
+        let compacted = AssetRegistry {
+            names: merger.names.names,
+            store: merger.store.store,
+            assets: AssetDataCollection { assets },
+            dependencies: DependencySection { nodes },
+            package_data: AssetPackageDataCollection { packages },
+        };
+        // A tag map overlapping another one's pairs is copied separately, so the store may grow.
+        let (old, new) = (&self.store, &compacted.store);
+        let report = CompactReport {
+            names_removed: self.names.strings.len() - compacted.names.strings.len(),
+            values_removed: value_count(old).saturating_sub(value_count(new)),
+            pairs_removed: pair_count(old).saturating_sub(pair_count(new)),
+            bytes_before,
+            bytes_after: compacted.serialized_size() as usize,
+        };
+        debug!(?report);
+        *self = compacted;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{RegistryBuilder, TagValue};
+    use crate::dependencies::{EDependencyCategory, EDependencyProperty};
+    use crate::read::Readable;
+    use crate::store_data::StoreValue;
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/A.A", "Blueprint")
+            .tag("ParentClass", TagValue::Name("Actor".to_string()))
+            .tag("Color", "red");
+        builder
+            .add_asset("/Game/Gone/B.B", "Texture2D")
+            .tag("Size", "huge");
+        builder.add_dependency(
+            "/Game/A",
+            "/Game/Gone/B",
+            EDependencyCategory::Package,
+            EDependencyProperty::HARD,
+        );
+        builder.add_package_data("/Game/A", 1);
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut registry = fixture();
+        registry.remove_package("/Game/Gone/B").unwrap();
+        registry.set_tag("/Game/A.A", "Color", "blue").unwrap();
+
+        let report = registry.compact().unwrap();
+        // `/Game/Gone/B.B`, `/Game/Gone/B`, `/Game/Gone`, `B`, `Texture2D` and `Size`.
+        assert_eq!(report.names_removed, 6);
+        // `huge` and `red`.
+        assert_eq!(report.values_removed, 2);
+        // B's pair and A's pre-edit pairs.
+        assert_eq!(report.pairs_removed, 3);
+        assert!(report.bytes_saved() > 0);
+
+        let tags = registry.asset_tags("/Game/A.A").unwrap();
+        let StoreValue::NumberlessName(parent) = tags[0].1 else {
+            panic!("expected a name");
+        };
+        assert_eq!(registry.names.try_resolve(parent).unwrap(), "Actor");
+        assert_eq!(
+            tags[1],
+            (
+                "Color".to_string(),
+                StoreValue::AnsiString("blue".to_string())
+            )
+        );
+        assert_eq!(registry.store.ansi_strings, vec!["blue"]);
+        let nodes = &registry.dependencies.nodes;
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(
+            registry
+                .names
+                .try_resolve(nodes[0].identifier.package_name.unwrap())
+                .unwrap(),
+            "/Game/A"
+        );
+
+        let mut buf = vec![];
+        registry.write(&mut Cursor::new(&mut buf)).unwrap();
+        assert_eq!(buf.len(), report.bytes_after);
+        assert_eq!(
+            AssetRegistry::read(&mut Cursor::new(&buf)).unwrap(),
+            registry
+        );
+
+        // Nothing left to collect.
+        let report = registry.compact().unwrap();
+        assert_eq!(report.bytes_saved(), 0);
+        assert_eq!(
+            report.names_removed + report.values_removed + report.pairs_removed,
+            0
+        );
+    }
+
+    #[test]
+    fn test_shared_tag_maps_stay_shared() {
+        let mut registry = fixture();
+        let tags = registry.assets.assets[0].tags;
+        registry.assets.assets[1].tags = tags;
+        registry.compact().unwrap();
+        assert_eq!(
+            registry.assets.assets[0].tags,
+            registry.assets.assets[1].tags
+        );
+        assert_eq!(registry.store.numberless_pairs.len(), 2);
+    }
+}
@@ -0,0 +1,163 @@
+//! Which byte ranges of a registry file were consumed by which structure.
+//!
+//! Bytes that no structure claims are either trailing data or gaps in our understanding of the
+//! format (a skipped store, unparsed bytes at the end of the dependency section, ...).
+
+use std::ops::Range;
+
+use crate::asset_registry::AssetRegistry;
+use crate::lenient::{Diagnostic, LenientReader};
+
+/// A byte range consumed by a single structure.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CoveredRange {
+    pub range: Range<u64>,
+    /// Path of the structure, e.g. `assets.assets[3]`.
+    pub structure: String,
+}
+
+/// The consumed ranges of a file, in file order.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Coverage {
+    /// Length of the whole file.
+    pub len: u64,
+    pub ranges: Vec<CoveredRange>,
+}
+
+impl Coverage {
+    /// Number of bytes consumed by some structure.
+    pub fn covered_bytes(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|r| r.range.end - r.range.start)
+            .sum()
+    }
+
+    /// The byte ranges no structure consumed, including trailing bytes.
+    pub fn gaps(&self) -> Vec<Range<u64>> {
+        let mut gaps = vec![];
+        let mut position = 0;
+        for r in &self.ranges {
+            if r.range.start > position {
+                gaps.push(position..r.range.start);
+            }
+            position = position.max(r.range.end);
+        }
+        if self.len > position {
+            gaps.push(position..self.len);
+        }
+        gaps
+    }
+
+    /// The structure that consumed the byte at `offset`, if any.
+    pub fn structure_at(&self, offset: u64) -> Option<&str> {
+        let i = self.ranges.partition_point(|r| r.range.end <= offset);
+        self.ranges
+            .get(i)
+            .filter(|r| r.range.contains(&offset))
+            .map(|r| r.structure.as_str())
+    }
+}
+
+impl std::fmt::Display for Coverage {
+    /// One line per range, with gaps interleaved.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut gaps = self.gaps().into_iter().peekable();
+        let line = |f: &mut std::fmt::Formatter<'_>, range: &Range<u64>, what: &str| {
+            writeln!(
+                f,
+                "{:#010X}..{:#010X} {:>8} {}",
+                range.start,
+                range.end,
+                range.end - range.start,
+                what
+            )
+        };
+        for r in &self.ranges {
+            while let Some(gap) = gaps.next_if(|g| g.start < r.range.start) {
+                line(f, &gap, "<gap>")?;
+            }
+            line(f, &r.range, &r.structure)?;
+        }
+        for gap in gaps {
+            line(f, &gap, "<gap>")?;
+        }
+        Ok(())
+    }
+}
+
+impl AssetRegistry {
+    /// Parse `data` leniently and report which structure consumed which bytes, together with the
+    /// parse diagnostics.
+    pub fn coverage(data: &[u8]) -> (Coverage, Vec<Diagnostic>) {
+        let reader = LenientReader::read(data);
+        let coverage = Coverage {
+            len: data.len() as u64,
+            ranges: reader.coverage,
+        };
+        (coverage, reader.recovery.diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::RegistryBuilder;
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn bytes() -> Vec<u8> {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/A.A", "Blueprint")
+            .tag("Color", "red");
+        builder.add_asset("/Game/B.B", "Texture2D");
+        builder.add_package_data("/Game/A", 1);
+        let mut buf = vec![];
+        builder
+            .build()
+            .unwrap()
+            .write(&mut Cursor::new(&mut buf))
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_full_coverage() {
+        let buf = bytes();
+        let (coverage, diagnostics) = AssetRegistry::coverage(&buf);
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(coverage.gaps(), vec![]);
+        assert_eq!(coverage.covered_bytes(), buf.len() as u64);
+        assert_eq!(coverage.structure_at(0), Some("header"));
+        assert_eq!(coverage.structure_at(20), Some("names"));
+        assert_eq!(
+            coverage.structure_at(buf.len() as u64 - 1),
+            Some("package_data.packages[0]")
+        );
+        assert_eq!(coverage.structure_at(buf.len() as u64), None);
+        assert!(coverage
+            .ranges
+            .iter()
+            .any(|r| r.structure == "assets.assets[1]"));
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let mut buf = bytes();
+        let len = buf.len() as u64;
+        buf.extend_from_slice(&[0xAB; 5]);
+
+        let (coverage, diagnostics) = AssetRegistry::coverage(&buf);
+        assert_eq!(coverage.gaps(), vec![len..len + 5]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, crate::validate::Severity::Warning);
+        assert_eq!(diagnostics[0].offset, len);
+        assert_eq!(
+            diagnostics[0].message,
+            "5 trailing bytes after the package data"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::io::{Read, Write};
+
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use color_eyre::eyre::Result as EResult;
+#[cfg(feature = "trace-fields")]
+use tracing::*;
+
+use crate::names_batch::NamesBatch;
+use crate::read::Readable;
+use crate::spans;
+use crate::unreal_types::FName;
+use crate::write::{SerializedSize, Writable};
+
+/// Identifies a dependency node. Serialized as a `u8` bitmask of which fields are present,
+/// followed by the present fields in declaration order.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct FAssetIdentifier {
+    pub package_name: Option<FName>,
+    pub primary_asset_type: Option<FName>,
+    pub object_name: Option<FName>,
+    pub value_name: Option<FName>,
+}
+
+impl FAssetIdentifier {
+    pub fn package(package_name: FName) -> Self {
+        FAssetIdentifier {
+            package_name: Some(package_name),
+            ..Default::default()
+        }
+    }
+
+    pub fn resolve(&self, names: &NamesBatch) -> EResult<ResolvedIdentifier> {
+        let resolve = |n: Option<FName>| n.map(|n| names.try_resolve(n)).transpose();
+        Ok(ResolvedIdentifier {
+            package_name: resolve(self.package_name)?,
+            primary_asset_type: resolve(self.primary_asset_type)?,
+            object_name: resolve(self.object_name)?,
+            value_name: resolve(self.value_name)?,
+        })
+    }
+
+    fn fields(&self) -> [Option<FName>; 4] {
+        [
+            self.package_name,
+            self.primary_asset_type,
+            self.object_name,
+            self.value_name,
+        ]
+    }
+}
+
+/// An [`FAssetIdentifier`] with its names resolved to strings.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct ResolvedIdentifier {
+    pub package_name: Option<String>,
+    pub primary_asset_type: Option<String>,
+    pub object_name: Option<String>,
+    pub value_name: Option<String>,
+}
+
+impl ResolvedIdentifier {
+    pub fn package(package_name: &str) -> Self {
+        ResolvedIdentifier {
+            package_name: Some(package_name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Inverse of the [`Display`][std::fmt::Display] impl: `Type:Name` is a primary asset id,
+    /// anything else is `Package[.Object][::Value]`.
+    pub fn parse(s: &str) -> Self {
+        if !s.starts_with('/') {
+            if let Some((ty, name)) = s.split_once(':') {
+                return ResolvedIdentifier {
+                    primary_asset_type: Some(ty.to_string()),
+                    object_name: Some(name.to_string()),
+                    ..Default::default()
+                };
+            }
+        }
+
+        let (path, value_name) = match s.split_once("::") {
+            Some((path, value)) => (path, Some(value.to_string())),
+            None => (s, None),
+        };
+        let (package_name, object_name) = match path.split_once('.') {
+            Some((package, object)) => (package, Some(object.to_string())),
+            None => (path, None),
+        };
+        ResolvedIdentifier {
+            package_name: Some(package_name.to_string()),
+            primary_asset_type: None,
+            object_name,
+            value_name,
+        }
+    }
+}
+
+/// Formats like `FAssetIdentifier::ToString`.
+impl std::fmt::Display for ResolvedIdentifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ty) = &self.primary_asset_type {
+            return write!(
+                f,
+                "{}:{}",
+                ty,
+                self.object_name.as_deref().unwrap_or_default()
+            );
+        }
+        write!(f, "{}", self.package_name.as_deref().unwrap_or_default())?;
+        if let Some(object) = &self.object_name {
+            write!(f, ".{object}")?;
+        }
+        if let Some(value) = &self.value_name {
+            write!(f, "::{value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Writable<W> for FAssetIdentifier {
+    #[cfg_attr(
+        feature = "trace-fields",
+        instrument(name = "FAssetIdentifier_write", skip_all)
+    )]
+    fn write(&self, writer: &mut W) -> EResult<()> {
+        let fields = self.fields();
+        let field_bits = fields
+            .iter()
+            .enumerate()
+            .fold(0u8, |bits, (i, f)| bits | ((f.is_some() as u8) << i));
+        writer.write_u8(field_bits)?;
+        for name in fields.iter().flatten() {
+            name.write(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl SerializedSize for FAssetIdentifier {
+    fn serialized_size(&self) -> u64 {
+        1 + self
+            .fields()
+            .iter()
+            .flatten()
+            .map(|name| name.serialized_size())
+            .sum::<u64>()
+    }
+}
+
+impl<R: Read> Readable<R> for FAssetIdentifier {
+    #[cfg_attr(
+        feature = "trace-fields",
+        instrument(name = "FAssetIdentifier_read", skip_all)
+    )]
+    fn read(reader: &mut R) -> EResult<Self> {
+        let field_bits = spans::field("field_bits", || reader.read_u8())?;
+        let mut read_field = |bit: u8, name: &'static str| -> EResult<Option<FName>> {
+            match field_bits & (1 << bit) {
+                0 => Ok(None),
+                _ => Ok(Some(spans::field(name, || FName::read(reader))?)),
+            }
+        };
+        Ok(FAssetIdentifier {
+            package_name: read_field(0, "package_name")?,
+            primary_asset_type: read_field(1, "primary_asset_type")?,
+            object_name: read_field(2, "object_name")?,
+            value_name: read_field(3, "value_name")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let identifier = FAssetIdentifier {
+            package_name: Some(FName {
+                index: 123,
+                number: 0,
+            }),
+            primary_asset_type: None,
+            object_name: Some(FName {
+                index: 456,
+                number: 7,
+            }),
+            value_name: None,
+        };
+        let mut buf = vec![];
+        let mut writer = Cursor::new(&mut buf);
+        identifier.write(&mut writer).unwrap();
+        assert_eq!(buf[0], 0b101);
+        let mut reader = Cursor::new(&buf);
+        let read_identifier = FAssetIdentifier::read(&mut reader).unwrap();
+        assert_eq!(read_identifier, identifier);
+    }
+
+    #[test]
+    fn test_resolved_display_parse() {
+        for s in [
+            "/Game/Maps/Entry",
+            "/Game/Maps/Entry.Entry",
+            "/Game/Maps/Entry::Value",
+            "/Game/Maps/Entry.Entry::Value",
+            "Map:Entry",
+        ] {
+            assert_eq!(ResolvedIdentifier::parse(s).to_string(), s);
+        }
+        assert_eq!(
+            ResolvedIdentifier::parse("Map:Entry")
+                .primary_asset_type
+                .as_deref(),
+            Some("Map")
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Cycles in the dependency graph, found as its strongly connected components (Tarjan's
+//! algorithm). Hard package cycles force UE to load and cook their members together, so
+//! [`DependencyGraph::hard_cycles`] is what the `cycles` command checks.
+//!
+//! An [`AllowList`] records known cycles so that only new ones are reported as errors. It is a
+//! text file with one cycle per line, its members sorted and separated by spaces; `#` starts a
+//! comment.
+
+use std::collections::BTreeSet;
+
+use super::{DependencyEdge, DependencyFilter, DependencyGraph, EDependencyProperty};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Cycle {
+    /// The nodes of the strongly connected component, sorted by identifier.
+    pub members: Vec<usize>,
+    /// The edges between members, which are the ones closing the cycle, sorted by identifiers.
+    pub edges: Vec<DependencyEdge>,
+}
+
+impl Cycle {
+    /// The sorted member identifiers separated by spaces, as in an [`AllowList`].
+    pub fn key(&self, graph: &DependencyGraph) -> String {
+        self.members
+            .iter()
+            .map(|&m| graph.identifier(m).to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl<'a> DependencyGraph<'a> {
+    /// Every cycle through edges accepted by `filter`: the strongly connected components with
+    /// more than one node, and nodes depending on themselves. Sorted by [`Cycle::key`].
+    pub fn cycles(&self, filter: &DependencyFilter) -> Vec<Cycle> {
+        let mut cycles = vec![];
+        for component in self.strongly_connected_components(filter) {
+            let members = component.iter().copied().collect::<BTreeSet<_>>();
+            let mut edges = component
+                .iter()
+                .flat_map(|&node| self.dependencies(node, filter))
+                .filter(|edge| members.contains(&edge.to))
+                .collect::<Vec<_>>();
+            if component.len() > 1 || !edges.is_empty() {
+                let mut members = component;
+                edges.sort_by_cached_key(|edge| {
+                    let identifier = |node| self.identifier(node).to_string();
+                    (identifier(edge.from), identifier(edge.to))
+                });
+                members.sort_by_cached_key(|&m| self.identifier(m).to_string());
+                cycles.push(Cycle { members, edges });
+            }
+        }
+        cycles.sort_by_cached_key(|cycle| cycle.key(self));
+        cycles
+    }
+
+    /// The cycles through hard package dependencies.
+    pub fn hard_cycles(&self) -> Vec<Cycle> {
+        self.cycles(&DependencyFilter::package(
+            EDependencyProperty::HARD,
+            EDependencyProperty::NONE,
+        ))
+    }
+
+    /// Tarjan's algorithm, with an explicit stack so that long dependency chains can't overflow
+    /// the call stack.
+    fn strongly_connected_components(&self, filter: &DependencyFilter) -> Vec<Vec<usize>> {
+        const UNVISITED: usize = usize::MAX;
+        let mut index = vec![UNVISITED; self.len()];
+        let mut low_link = vec![0; self.len()];
+        let mut on_stack = vec![false; self.len()];
+        let mut stack = vec![];
+        let mut components = vec![];
+        let mut next_index = 0;
+
+        for start in 0..self.len() {
+            if index[start] != UNVISITED {
+                continue;
+            }
+            // Each frame is a node and the successors it has yet to visit.
+            let mut frames = vec![(start, self.successors(start, filter))];
+            index[start] = next_index;
+            low_link[start] = next_index;
+            next_index += 1;
+            stack.push(start);
+            on_stack[start] = true;
+
+            while let Some((node, successors)) = frames.last_mut() {
+                let node = *node;
+                if let Some(next) = successors.pop() {
+                    if index[next] == UNVISITED {
+                        index[next] = next_index;
+                        low_link[next] = next_index;
+                        next_index += 1;
+                        stack.push(next);
+                        on_stack[next] = true;
+                        frames.push((next, self.successors(next, filter)));
+                    } else if on_stack[next] {
+                        low_link[node] = low_link[node].min(index[next]);
+                    }
+                    continue;
+                }
+
+                frames.pop();
+                if let Some((parent, _)) = frames.last() {
+                    low_link[*parent] = low_link[*parent].min(low_link[node]);
+                }
+                if low_link[node] == index[node] {
+                    let mut component = vec![];
+                    loop {
+                        let member = stack.pop().unwrap();
+                        on_stack[member] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+
+    fn successors(&self, node: usize, filter: &DependencyFilter) -> Vec<usize> {
+        self.dependencies(node, filter)
+            .into_iter()
+            .map(|edge| edge.to)
+            .collect()
+    }
+}
+
+/// Known cycles, by [`Cycle::key`].
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct AllowList {
+    pub cycles: BTreeSet<String>,
+}
+
+impl AllowList {
+    pub fn parse(text: &str) -> AllowList {
+        let cycles = text
+            .lines()
+            .map(|line| line.split_once('#').map_or(line, |(cycle, _)| cycle))
+            .map(|line| {
+                // Normalize the order and spacing, so hand-edited lines still match.
+                let mut members = line.split_whitespace().collect::<Vec<_>>();
+                members.sort_unstable();
+                members.join(" ")
+            })
+            .filter(|key| !key.is_empty())
+            .collect();
+        AllowList { cycles }
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.cycles.contains(key)
+    }
+}
+
+impl std::fmt::Display for AllowList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for cycle in &self.cycles {
+            writeln!(f, "{cycle}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset_registry::AssetRegistry;
+    use crate::builder::RegistryBuilder;
+    use crate::dependencies::EDependencyCategory;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let hard = EDependencyProperty::HARD | EDependencyProperty::GAME;
+        let soft = EDependencyProperty::GAME;
+        let mut builder = RegistryBuilder::new();
+        for (from, to, properties) in [
+            // A hard three-package cycle with a soft shortcut back.
+            ("/Game/A", "/Game/B", hard),
+            ("/Game/B", "/Game/C", hard),
+            ("/Game/C", "/Game/A", hard),
+            ("/Game/B", "/Game/A", soft),
+            // Only a cycle when soft references count.
+            ("/Game/D", "/Game/E", hard),
+            ("/Game/E", "/Game/D", soft),
+            ("/Game/F", "/Game/F", hard),
+            ("/Game/C", "/Game/D", hard),
+        ] {
+            builder.add_dependency(from, to, EDependencyCategory::Package, properties);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_cycles() {
+        let registry = fixture();
+        let graph = registry.dependency_graph().unwrap();
+
+        let cycles = graph.hard_cycles();
+        let keys = cycles.iter().map(|c| c.key(&graph)).collect::<Vec<_>>();
+        assert_eq!(keys, ["/Game/A /Game/B /Game/C", "/Game/F"]);
+        let edges = cycles[0]
+            .edges
+            .iter()
+            .map(|e| format!("{} -> {}", graph.identifier(e.from), graph.identifier(e.to)))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(
+            edges,
+            BTreeSet::from([
+                "/Game/A -> /Game/B".to_string(),
+                "/Game/B -> /Game/C".to_string(),
+                "/Game/C -> /Game/A".to_string(),
+            ])
+        );
+
+        let all = graph.cycles(&DependencyFilter::default());
+        let keys = all.iter().map(|c| c.key(&graph)).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            ["/Game/A /Game/B /Game/C", "/Game/D /Game/E", "/Game/F"]
+        );
+        assert_eq!(all[0].edges.len(), 4);
+    }
+
+    #[test]
+    fn test_allow_list() {
+        let allow_list = AllowList::parse(
+            "# known cycles\n/Game/C  /Game/A /Game/B\n\n/Game/F # self reference\n",
+        );
+        assert!(allow_list.contains("/Game/A /Game/B /Game/C"));
+        assert!(allow_list.contains("/Game/F"));
+        assert!(!allow_list.contains("/Game/A /Game/B"));
+        assert_eq!(allow_list.to_string(), "/Game/A /Game/B /Game/C\n/Game/F\n");
+        assert_eq!(AllowList::parse(&allow_list.to_string()), allow_list);
+    }
+}
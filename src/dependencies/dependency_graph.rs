@@ -0,0 +1,507 @@
+//! Queries over the dependency graph: direct and transitive dependencies and referencers of a
+//! node, following only the edges a [`DependencyFilter`] accepts.
+//!
+//! Transitive queries walk the graph breadth-first and return, for every node reached, the
+//! shortest chain of edges leading to it, which answers questions like "why does this map pull in
+//! that texture?".
+
+use std::collections::{HashMap, VecDeque};
+
+use color_eyre::eyre::{eyre, Result as EResult};
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+
+use super::{EDependencyCategory, EDependencyProperty, ResolvedIdentifier};
+
+/// Which edges a query follows, like UE's `FDependencyQuery`. Property requirements only apply
+/// to the categories that have those properties: `HARD`, `GAME` and `BUILD` to package
+/// dependencies and `DIRECT` to manage dependencies.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DependencyFilter {
+    /// The categories to follow.
+    pub categories: Vec<EDependencyCategory>,
+    /// Properties an edge must have, e.g. `HARD` for hard references only.
+    pub required: EDependencyProperty,
+    /// Properties an edge must not have, e.g. `GAME` for editor-only references only.
+    pub excluded: EDependencyProperty,
+}
+
+impl Default for DependencyFilter {
+    /// Follow every edge.
+    fn default() -> Self {
+        DependencyFilter {
+            categories: vec![
+                EDependencyCategory::Package,
+                EDependencyCategory::Manage,
+                EDependencyCategory::SearchableName,
+            ],
+            required: EDependencyProperty::NONE,
+            excluded: EDependencyProperty::NONE,
+        }
+    }
+}
+
+impl DependencyFilter {
+    /// Only package dependencies with all of `required` and none of `excluded`.
+    pub fn package(required: EDependencyProperty, excluded: EDependencyProperty) -> Self {
+        DependencyFilter {
+            categories: vec![EDependencyCategory::Package],
+            required,
+            excluded,
+        }
+    }
+
+    pub fn accepts(&self, category: EDependencyCategory, properties: EDependencyProperty) -> bool {
+        let relevant = match category {
+            EDependencyCategory::Package => {
+                EDependencyProperty::HARD | EDependencyProperty::GAME | EDependencyProperty::BUILD
+            }
+            EDependencyCategory::Manage => EDependencyProperty::DIRECT,
+            EDependencyCategory::SearchableName => EDependencyProperty::NONE,
+        };
+        self.categories.contains(&category)
+            && properties.0 & self.required.0 & relevant.0 == self.required.0 & relevant.0
+            && properties.0 & self.excluded.0 & relevant.0 == 0
+    }
+}
+
+/// An edge of the graph, from a referencer to one of its dependencies, by node index.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct DependencyEdge {
+    pub from: usize,
+    pub to: usize,
+    pub category: EDependencyCategory,
+    pub properties: EDependencyProperty,
+}
+
+impl DependencyEdge {
+    /// The category and properties in words, e.g. `package hard game` or `manage direct`.
+    pub fn label(&self) -> String {
+        let p = self.properties;
+        match self.category {
+            EDependencyCategory::Package => {
+                let mut label = format!(
+                    "package {} {}",
+                    if p.contains(EDependencyProperty::HARD) {
+                        "hard"
+                    } else {
+                        "soft"
+                    },
+                    if p.contains(EDependencyProperty::GAME) {
+                        "game"
+                    } else {
+                        "editor-only"
+                    },
+                );
+                if p.contains(EDependencyProperty::BUILD) {
+                    label.push_str(" build");
+                }
+                label
+            }
+            EDependencyCategory::Manage => match p.contains(EDependencyProperty::DIRECT) {
+                true => "manage direct".to_string(),
+                false => "manage indirect".to_string(),
+            },
+            EDependencyCategory::SearchableName => "searchable name".to_string(),
+        }
+    }
+}
+
+/// A node reached by a transitive query.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DependencyHit {
+    pub node: usize,
+    /// The edges from the queried node to this one (towards the queried node for referencer
+    /// queries), in walking order. A single edge for direct hits.
+    pub path: Vec<DependencyEdge>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Dependencies,
+    Referencers,
+}
+
+/// The dependency graph with resolved identifiers and edges in both directions.
+#[derive(Debug, Clone)]
+pub struct DependencyGraph<'a> {
+    registry: &'a AssetRegistry,
+    identifiers: Vec<ResolvedIdentifier>,
+    nodes: HashMap<ResolvedIdentifier, usize>,
+    /// Edges pointing at each node, rebuilt from the dependencies so that they carry their
+    /// category and properties.
+    referencers: Vec<Vec<DependencyEdge>>,
+}
+
+impl AssetRegistry {
+    /// Resolve the dependency graph for queries. Fails on names that don't resolve.
+    #[instrument(name = "AssetRegistry_dependency_graph", skip_all)]
+    pub fn dependency_graph(&self) -> EResult<DependencyGraph<'_>> {
+        let identifiers = self
+            .dependencies
+            .nodes
+            .iter()
+            .map(|node| node.identifier.resolve(&self.names))
+            .collect::<EResult<Vec<_>>>()?;
+        let nodes = identifiers
+            .iter()
+            .enumerate()
+            .map(|(i, identifier)| (identifier.clone(), i))
+            .collect();
+        let mut graph = DependencyGraph {
+            registry: self,
+            identifiers,
+            nodes,
+            referencers: vec![vec![]; self.dependencies.nodes.len()],
+        };
+        for from in 0..self.dependencies.nodes.len() {
+            for edge in graph.edges_from(from) {
+                graph.referencers[edge.to].push(edge);
+            }
+        }
+        Ok(graph)
+    }
+}
+
+impl<'a> DependencyGraph<'a> {
+    pub fn registry(&self) -> &'a AssetRegistry {
+        self.registry
+    }
+
+    pub fn len(&self) -> usize {
+        self.identifiers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.identifiers.is_empty()
+    }
+
+    pub fn identifier(&self, node: usize) -> &ResolvedIdentifier {
+        &self.identifiers[node]
+    }
+
+    pub fn node(&self, identifier: &ResolvedIdentifier) -> Option<usize> {
+        self.nodes.get(identifier).copied()
+    }
+
+    /// The node of an identifier as formatted by [`ResolvedIdentifier`], e.g. `/Game/Maps/Arena`.
+    pub fn find(&self, identifier: &str) -> EResult<usize> {
+        self.node(&ResolvedIdentifier::parse(identifier))
+            .ok_or_else(|| eyre!("no dependency node `{}` in the registry", identifier))
+    }
+
+    /// Every outgoing edge of a node, skipping indices that don't point at a node.
+    fn edges_from(&self, from: usize) -> Vec<DependencyEdge> {
+        let node = &self.registry.dependencies.nodes[from];
+        let edge = |to: i32, category, properties| {
+            let to = usize::try_from(to).ok().filter(|&to| to < self.len())?;
+            Some(DependencyEdge {
+                from,
+                to,
+                category,
+                properties,
+            })
+        };
+        let package = node
+            .package_dependencies
+            .iter()
+            .map(|d| edge(d.node, EDependencyCategory::Package, d.properties));
+        let manage = node
+            .manage_dependencies
+            .iter()
+            .map(|d| edge(d.node, EDependencyCategory::Manage, d.properties));
+        let names = node.name_dependencies.iter().map(|&to| {
+            edge(
+                to,
+                EDependencyCategory::SearchableName,
+                EDependencyProperty::NONE,
+            )
+        });
+        package.chain(manage).chain(names).flatten().collect()
+    }
+
+    fn edges(
+        &self,
+        node: usize,
+        direction: Direction,
+        filter: &DependencyFilter,
+    ) -> Vec<DependencyEdge> {
+        let edges = match direction {
+            Direction::Dependencies => self.edges_from(node),
+            Direction::Referencers => self.referencers[node].clone(),
+        };
+        edges
+            .into_iter()
+            .filter(|e| filter.accepts(e.category, e.properties))
+            .collect()
+    }
+
+    /// The edges from `node` to its direct dependencies.
+    pub fn dependencies(&self, node: usize, filter: &DependencyFilter) -> Vec<DependencyEdge> {
+        self.edges(node, Direction::Dependencies, filter)
+    }
+
+    /// The edges from the direct referencers of `node` to it.
+    pub fn referencers(&self, node: usize, filter: &DependencyFilter) -> Vec<DependencyEdge> {
+        self.edges(node, Direction::Referencers, filter)
+    }
+
+    /// Every node that `node` depends on, directly or not, nearest first.
+    pub fn transitive_dependencies(
+        &self,
+        node: usize,
+        filter: &DependencyFilter,
+    ) -> Vec<DependencyHit> {
+        self.walk(node, Direction::Dependencies, filter)
+    }
+
+    /// Every node that depends on `node`, directly or not, nearest first.
+    pub fn transitive_referencers(
+        &self,
+        node: usize,
+        filter: &DependencyFilter,
+    ) -> Vec<DependencyHit> {
+        self.walk(node, Direction::Referencers, filter)
+    }
+
+    /// The shortest chain of edges through which `from` depends on `to`, if it does.
+    pub fn path(
+        &self,
+        from: usize,
+        to: usize,
+        filter: &DependencyFilter,
+    ) -> Option<Vec<DependencyEdge>> {
+        self.transitive_dependencies(from, filter)
+            .into_iter()
+            .find(|hit| hit.node == to)
+            .map(|hit| hit.path)
+    }
+
+    fn walk(
+        &self,
+        start: usize,
+        direction: Direction,
+        filter: &DependencyFilter,
+    ) -> Vec<DependencyHit> {
+        // The edge through which each node was first reached.
+        let mut reached_by = HashMap::<usize, Option<DependencyEdge>>::new();
+        reached_by.insert(start, None);
+        let mut queue = VecDeque::from([start]);
+        let mut order = vec![];
+        while let Some(node) = queue.pop_front() {
+            for edge in self.edges(node, direction, filter) {
+                let next = match direction {
+                    Direction::Dependencies => edge.to,
+                    Direction::Referencers => edge.from,
+                };
+                if reached_by.contains_key(&next) {
+                    continue;
+                }
+                reached_by.insert(next, Some(edge));
+                order.push(next);
+                queue.push_back(next);
+            }
+        }
+
+        order
+            .into_iter()
+            .map(|node| {
+                let mut path = vec![];
+                let mut current = node;
+                while let Some(Some(edge)) = reached_by.get(&current) {
+                    path.push(*edge);
+                    current = match direction {
+                        Direction::Dependencies => edge.from,
+                        Direction::Referencers => edge.to,
+                    };
+                }
+                path.reverse();
+                DependencyHit { node, path }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::RegistryBuilder;
+
+    use pretty_assertions::assert_eq;
+
+    const HARD_GAME: EDependencyProperty =
+        EDependencyProperty(EDependencyProperty::HARD.0 | EDependencyProperty::GAME.0);
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        for (from, to, category, properties) in [
+            (
+                "/Game/Maps/Arena",
+                "/Game/Weapons/BP_Rifle",
+                EDependencyCategory::Package,
+                HARD_GAME,
+            ),
+            (
+                "/Game/Weapons/BP_Rifle",
+                "/Game/Weapons/T_Rifle",
+                EDependencyCategory::Package,
+                HARD_GAME,
+            ),
+            (
+                "/Game/Maps/Arena",
+                "/Game/Editor/T_Preview",
+                EDependencyCategory::Package,
+                EDependencyProperty::HARD,
+            ),
+            (
+                "/Game/Weapons/BP_Rifle",
+                "/Game/Weapons/BP_Scope",
+                EDependencyCategory::Package,
+                EDependencyProperty::GAME,
+            ),
+            (
+                "Map:Arena",
+                "/Game/Maps/Arena",
+                EDependencyCategory::Manage,
+                EDependencyProperty::DIRECT,
+            ),
+        ] {
+            builder.add_dependency(from, to, category, properties);
+        }
+        builder.build().unwrap()
+    }
+
+    fn names(graph: &DependencyGraph, hits: &[DependencyHit]) -> Vec<(String, usize)> {
+        hits.iter()
+            .map(|hit| (graph.identifier(hit.node).to_string(), hit.path.len()))
+            .collect()
+    }
+
+    #[test]
+    fn test_queries() {
+        let registry = fixture();
+        let graph = registry.dependency_graph().unwrap();
+        let arena = graph.find("/Game/Maps/Arena").unwrap();
+        let texture = graph.find("/Game/Weapons/T_Rifle").unwrap();
+        let all = DependencyFilter::default();
+
+        assert_eq!(
+            names(&graph, &graph.transitive_dependencies(arena, &all)),
+            [
+                ("/Game/Weapons/BP_Rifle".to_string(), 1),
+                ("/Game/Editor/T_Preview".to_string(), 1),
+                ("/Game/Weapons/T_Rifle".to_string(), 2),
+                ("/Game/Weapons/BP_Scope".to_string(), 2),
+            ]
+        );
+        let hard_game = DependencyFilter::package(HARD_GAME, EDependencyProperty::NONE);
+        assert_eq!(
+            names(&graph, &graph.transitive_dependencies(arena, &hard_game)),
+            [
+                ("/Game/Weapons/BP_Rifle".to_string(), 1),
+                ("/Game/Weapons/T_Rifle".to_string(), 2),
+            ]
+        );
+        let editor_only =
+            DependencyFilter::package(EDependencyProperty::NONE, EDependencyProperty::GAME);
+        assert_eq!(
+            names(&graph, &graph.transitive_dependencies(arena, &editor_only)),
+            [("/Game/Editor/T_Preview".to_string(), 1)]
+        );
+
+        let path = graph.path(arena, texture, &hard_game).unwrap();
+        let path = path
+            .iter()
+            .map(|e| {
+                (
+                    graph.identifier(e.from).to_string(),
+                    graph.identifier(e.to).to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            path,
+            [
+                (
+                    "/Game/Maps/Arena".to_string(),
+                    "/Game/Weapons/BP_Rifle".to_string()
+                ),
+                (
+                    "/Game/Weapons/BP_Rifle".to_string(),
+                    "/Game/Weapons/T_Rifle".to_string()
+                ),
+            ]
+        );
+        assert_eq!(graph.path(texture, arena, &all), None);
+
+        assert_eq!(
+            names(&graph, &graph.transitive_referencers(texture, &all)),
+            [
+                ("/Game/Weapons/BP_Rifle".to_string(), 1),
+                ("/Game/Maps/Arena".to_string(), 2),
+                ("Map:Arena".to_string(), 3),
+            ]
+        );
+        let package_only =
+            DependencyFilter::package(EDependencyProperty::NONE, EDependencyProperty::NONE);
+        assert_eq!(
+            names(
+                &graph,
+                &graph.transitive_referencers(texture, &package_only)
+            )
+            .len(),
+            2
+        );
+        let referencers = graph.referencers(arena, &all);
+        assert_eq!(referencers.len(), 1);
+        assert_eq!(referencers[0].category, EDependencyCategory::Manage);
+        assert_eq!(graph.dependencies(texture, &all), []);
+
+        assert_eq!(
+            graph.find("/Game/Missing").unwrap_err().to_string(),
+            "no dependency node `/Game/Missing` in the registry"
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let hard = DependencyFilter {
+            required: EDependencyProperty::HARD | EDependencyProperty::DIRECT,
+            ..Default::default()
+        };
+        assert!(hard.accepts(EDependencyCategory::Package, EDependencyProperty::HARD));
+        assert!(!hard.accepts(EDependencyCategory::Package, EDependencyProperty::GAME));
+        assert!(hard.accepts(EDependencyCategory::Manage, EDependencyProperty::DIRECT));
+        assert!(!hard.accepts(EDependencyCategory::Manage, EDependencyProperty::NONE));
+        assert!(hard.accepts(
+            EDependencyCategory::SearchableName,
+            EDependencyProperty::NONE
+        ));
+        let packages =
+            DependencyFilter::package(EDependencyProperty::NONE, EDependencyProperty::NONE);
+        assert!(!packages.accepts(EDependencyCategory::Manage, EDependencyProperty::DIRECT));
+        let edge = |category, properties| DependencyEdge {
+            from: 0,
+            to: 1,
+            category,
+            properties,
+        };
+        assert_eq!(
+            edge(
+                EDependencyCategory::Package,
+                HARD_GAME | EDependencyProperty::BUILD
+            )
+            .label(),
+            "package hard game build"
+        );
+        assert_eq!(
+            edge(EDependencyCategory::Package, EDependencyProperty::NONE).label(),
+            "package soft editor-only"
+        );
+        assert_eq!(
+            edge(EDependencyCategory::Manage, EDependencyProperty::DIRECT).label(),
+            "manage direct"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::io::{Read, Seek, Write};
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use color_eyre::eyre::{eyre, Result as EResult};
+use tracing::*;
+
+use crate::read::{read_array, Readable};
+use crate::spans;
+use crate::write::{count_i32, write_array, PatchWriter, SerializedSize, Writable};
+
+use super::{FDependency, FDependsNode};
+
+/// The dependency section is prefixed by its size in bytes (excluding the size itself) so that
+/// readers not interested in dependencies can skip over it.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DependencySection {
+    pub nodes: Vec<FDependsNode>,
+}
+
+impl DependencySection {
+    /// Recompute every node's referencers as the reverse edges of all dependency categories.
+    pub fn rebuild_referencers(&mut self) {
+        let mut referencers = vec![vec![]; self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            let targets = node
+                .package_dependencies
+                .iter()
+                .chain(&node.manage_dependencies)
+                .map(|d| d.node)
+                .chain(node.name_dependencies.iter().copied());
+            for target in targets {
+                if let Some(referencers) = referencers.get_mut(target as usize) {
+                    referencers.push(i as i32);
+                }
+            }
+        }
+        for (node, mut referencers) in self.nodes.iter_mut().zip(referencers) {
+            referencers.sort_unstable();
+            referencers.dedup();
+            node.referencers = referencers;
+        }
+    }
+
+    /// Remove every node matching `predicate`, dropping the edges pointing at removed nodes and
+    /// renumbering the remaining ones. Returns the number of removed nodes.
+    pub fn remove_nodes(&mut self, mut predicate: impl FnMut(&FDependsNode) -> bool) -> usize {
+        let mut new_indices = Vec::with_capacity(self.nodes.len());
+        let mut next = 0;
+        for node in &self.nodes {
+            match predicate(node) {
+                true => new_indices.push(None),
+                false => {
+                    new_indices.push(Some(next));
+                    next += 1;
+                }
+            }
+        }
+        let removed = self.nodes.len() - next as usize;
+        if removed == 0 {
+            return 0;
+        }
+
+        let remap = |i: i32| {
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| new_indices.get(i).copied().flatten())
+        };
+        let remap_dependencies = |dependencies: &mut Vec<FDependency>| {
+            dependencies.retain_mut(|d| match remap(d.node) {
+                Some(node) => {
+                    d.node = node;
+                    true
+                }
+                None => false,
+            })
+        };
+        let remap_indices = |indices: &mut Vec<i32>| {
+            *indices = indices.iter().filter_map(|&i| remap(i)).collect();
+        };
+
+        let mut i = 0;
+        self.nodes.retain(|_| {
+            i += 1;
+            new_indices[i - 1].is_some()
+        });
+        for node in &mut self.nodes {
+            remap_dependencies(&mut node.package_dependencies);
+            remap_indices(&mut node.name_dependencies);
+            remap_dependencies(&mut node.manage_dependencies);
+            remap_indices(&mut node.referencers);
+        }
+        removed
+    }
+}
+
+impl<W: Write + Seek> Writable<W> for DependencySection {
+    #[instrument(name = "DependencySection_write", skip_all)]
+    fn write(&self, writer: &mut W) -> EResult<()> {
+        let mut writer = PatchWriter::new(writer)?;
+        let size = writer.reserve::<i64>()?;
+        let start = writer.position();
+        writer.write_i32::<LE>(count_i32("dependency nodes", self.nodes.len())?)?;
+        write_array(&mut writer, &self.nodes, |w, n| n.write(w))?;
+
+        let end = writer.position();
+        writer.patch(size, (end - start) as i64)?;
+        writer.finish()
+    }
+}
+
+impl SerializedSize for DependencySection {
+    fn serialized_size(&self) -> u64 {
+        8 + 4 + self.nodes.serialized_size()
+    }
+}
+
+impl<R: Read> Readable<R> for DependencySection {
+    #[instrument(name = "DependencySection_read", skip_all)]
+    fn read(reader: &mut R) -> EResult<Self> {
+        let section_size = spans::field("size", || reader.read_i64::<LE>())?;
+        debug!(section_size);
+        let count = spans::field("count", || reader.read_i32::<LE>())?;
+        if count < 0 {
+            return Err(eyre!("negative dependency node count {}", count));
+        }
+        let nodes = spans::field("nodes", || {
+            read_array(count as u32, reader, FDependsNode::read)
+        })?;
+        Ok(DependencySection { nodes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dependencies::{EDependencyProperty, FAssetIdentifier};
+    use crate::unreal_types::FName;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let section = DependencySection {
+            nodes: vec![
+                FDependsNode {
+                    identifier: FAssetIdentifier::package(FName {
+                        index: 1,
+                        number: 0,
+                    }),
+                    package_dependencies: vec![FDependency {
+                        node: 1,
+                        properties: EDependencyProperty::HARD | EDependencyProperty::GAME,
+                    }],
+                    ..Default::default()
+                },
+                FDependsNode {
+                    identifier: FAssetIdentifier::package(FName {
+                        index: 2,
+                        number: 0,
+                    }),
+                    referencers: vec![0],
+                    ..Default::default()
+                },
+            ],
+        };
+        let mut buf = vec![];
+        let mut writer = Cursor::new(&mut buf);
+        section.write(&mut writer).unwrap();
+        assert_eq!(
+            i64::from_le_bytes(buf[..8].try_into().unwrap()),
+            buf.len() as i64 - 8
+        );
+        let mut reader = Cursor::new(&buf);
+        let read_section = DependencySection::read(&mut reader).unwrap();
+        assert_eq!(read_section, section);
+    }
+}
@@ -0,0 +1,268 @@
+use std::io::{Read, Write};
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use color_eyre::eyre::{eyre, Result as EResult};
+#[cfg(feature = "trace-fields")]
+use tracing::*;
+
+use crate::read::{read_array, Readable};
+use crate::spans;
+use crate::write::{count_i32, write_array, SerializedSize, Writable};
+
+use super::FAssetIdentifier;
+
+/// `UE::AssetRegistry::EDependencyProperty`.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct EDependencyProperty(pub u8);
+
+impl EDependencyProperty {
+    pub const NONE: EDependencyProperty = EDependencyProperty(0);
+    /// Package dependency: hard (as opposed to soft) reference.
+    pub const HARD: EDependencyProperty = EDependencyProperty(1 << 0);
+    /// Package dependency: used in game (as opposed to editor-only).
+    pub const GAME: EDependencyProperty = EDependencyProperty(1 << 1);
+    /// Package dependency: needed for building (cooking) the referencer.
+    pub const BUILD: EDependencyProperty = EDependencyProperty(1 << 2);
+    /// Manage dependency: direct (as opposed to indirect) management.
+    pub const DIRECT: EDependencyProperty = EDependencyProperty(1 << 3);
+
+    pub fn contains(self, other: EDependencyProperty) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for EDependencyProperty {
+    type Output = EDependencyProperty;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        EDependencyProperty(self.0 | rhs.0)
+    }
+}
+
+/// `UE::AssetRegistry::EDependencyCategory`: which list of a node a dependency lives in.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum EDependencyCategory {
+    Package,
+    Manage,
+    SearchableName,
+}
+
+/// A dependency edge: an index into [`DependencySection::nodes`][super::DependencySection] plus
+/// its properties.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct FDependency {
+    pub node: i32,
+    pub properties: EDependencyProperty,
+}
+
+/// Number of flag bits serialized per package dependency (`HARD | GAME | BUILD`).
+const PACKAGE_FLAG_SET_WIDTH: u32 = 3;
+/// Number of flag bits serialized per manage dependency (`DIRECT`).
+const MANAGE_FLAG_SET_WIDTH: u32 = 1;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FDependsNode {
+    pub identifier: FAssetIdentifier,
+    pub package_dependencies: Vec<FDependency>,
+    pub name_dependencies: Vec<i32>,
+    pub manage_dependencies: Vec<FDependency>,
+    pub referencers: Vec<i32>,
+}
+
+fn package_flags_to_bits(properties: EDependencyProperty) -> u32 {
+    (properties.0 & 0b111) as u32
+}
+
+fn package_bits_to_flags(bits: u32) -> EDependencyProperty {
+    EDependencyProperty(bits as u8)
+}
+
+fn manage_flags_to_bits(properties: EDependencyProperty) -> u32 {
+    properties.contains(EDependencyProperty::DIRECT) as u32
+}
+
+fn manage_bits_to_flags(bits: u32) -> EDependencyProperty {
+    match bits {
+        0 => EDependencyProperty::NONE,
+        _ => EDependencyProperty::DIRECT,
+    }
+}
+
+fn write_indices<W: Write>(writer: &mut W, indices: &[i32]) -> EResult<()> {
+    writer.write_i32::<LE>(count_i32("dependencies", indices.len())?)?;
+    write_array(writer, indices, |w, i| w.write_i32::<LE>(*i))?;
+    Ok(())
+}
+
+fn indices_size(indices: &[i32]) -> u64 {
+    4 + 4 * indices.len() as u64
+}
+
+fn read_indices<R: Read>(reader: &mut R) -> EResult<Vec<i32>> {
+    let len = spans::field("count", || reader.read_i32::<LE>())?;
+    if len < 0 {
+        return Err(eyre!("negative dependency count {}", len));
+    }
+    Ok(spans::field("indices", || {
+        read_array(len as u32, reader, |r| r.read_i32::<LE>())
+    })?)
+}
+
+/// Dependencies with flags are serialized as the node indices followed by a `TBitArray` of
+/// `width` bits per dependency, packed LSB-first into `u32` words.
+fn write_dependencies<W: Write>(
+    writer: &mut W,
+    dependencies: &[FDependency],
+    width: u32,
+    to_bits: fn(EDependencyProperty) -> u32,
+) -> EResult<()> {
+    let indices = dependencies.iter().map(|d| d.node).collect::<Vec<_>>();
+    write_indices(writer, &indices)?;
+
+    let width = width as usize;
+    let mut words = vec![0u32; (dependencies.len() * width).div_ceil(32)];
+    for (i, dependency) in dependencies.iter().enumerate() {
+        let bits = to_bits(dependency.properties);
+        for b in 0..width {
+            if bits & (1 << b) != 0 {
+                let bit = i * width + b;
+                words[bit / 32] |= 1 << (bit % 32);
+            }
+        }
+    }
+    write_array(writer, &words, |w, word| w.write_u32::<LE>(*word))?;
+    Ok(())
+}
+
+fn dependencies_size(dependencies: &[FDependency], width: u32) -> u64 {
+    let words = (dependencies.len() as u64 * width as u64).div_ceil(32);
+    4 + 4 * dependencies.len() as u64 + 4 * words
+}
+
+fn read_dependencies<R: Read>(
+    reader: &mut R,
+    width: u32,
+    from_bits: fn(u32) -> EDependencyProperty,
+) -> EResult<Vec<FDependency>> {
+    let indices = read_indices(reader)?;
+    let n_bits = indices.len() as u32 * width;
+    let words = spans::field("properties", || {
+        read_array(n_bits.div_ceil(32), reader, |r| r.read_u32::<LE>())
+    })?;
+    let dependencies = indices
+        .into_iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let bits = (0..width).fold(0u32, |bits, b| {
+                let bit = i as u32 * width + b;
+                bits | (((words[(bit / 32) as usize] >> (bit % 32)) & 1) << b)
+            });
+            FDependency {
+                node,
+                properties: from_bits(bits),
+            }
+        })
+        .collect();
+    Ok(dependencies)
+}
+
+impl<W: Write> Writable<W> for FDependsNode {
+    #[cfg_attr(
+        feature = "trace-fields",
+        instrument(name = "FDependsNode_write", skip_all)
+    )]
+    fn write(&self, writer: &mut W) -> EResult<()> {
+        self.identifier.write(writer)?;
+        write_dependencies(
+            writer,
+            &self.package_dependencies,
+            PACKAGE_FLAG_SET_WIDTH,
+            package_flags_to_bits,
+        )?;
+        write_indices(writer, &self.name_dependencies)?;
+        write_dependencies(
+            writer,
+            &self.manage_dependencies,
+            MANAGE_FLAG_SET_WIDTH,
+            manage_flags_to_bits,
+        )?;
+        write_indices(writer, &self.referencers)?;
+        Ok(())
+    }
+}
+
+impl SerializedSize for FDependsNode {
+    fn serialized_size(&self) -> u64 {
+        self.identifier.serialized_size()
+            + dependencies_size(&self.package_dependencies, PACKAGE_FLAG_SET_WIDTH)
+            + indices_size(&self.name_dependencies)
+            + dependencies_size(&self.manage_dependencies, MANAGE_FLAG_SET_WIDTH)
+            + indices_size(&self.referencers)
+    }
+}
+
+impl<R: Read> Readable<R> for FDependsNode {
+    #[cfg_attr(
+        feature = "trace-fields",
+        instrument(name = "FDependsNode_read", skip_all)
+    )]
+    fn read(reader: &mut R) -> EResult<Self> {
+        let identifier = spans::field("identifier", || FAssetIdentifier::read(reader))?;
+        let package_dependencies = spans::field("package_dependencies", || {
+            read_dependencies(reader, PACKAGE_FLAG_SET_WIDTH, package_bits_to_flags)
+        })?;
+        let name_dependencies = spans::field("name_dependencies", || read_indices(reader))?;
+        let manage_dependencies = spans::field("manage_dependencies", || {
+            read_dependencies(reader, MANAGE_FLAG_SET_WIDTH, manage_bits_to_flags)
+        })?;
+        let referencers = spans::field("referencers", || read_indices(reader))?;
+        Ok(FDependsNode {
+            identifier,
+            package_dependencies,
+            name_dependencies,
+            manage_dependencies,
+            referencers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unreal_types::FName;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let node = FDependsNode {
+            identifier: FAssetIdentifier::package(FName {
+                index: 12,
+                number: 0,
+            }),
+            package_dependencies: (0..12)
+                .map(|i| FDependency {
+                    node: i,
+                    properties: EDependencyProperty(i as u8 % 8),
+                })
+                .collect(),
+            name_dependencies: vec![3],
+            manage_dependencies: vec![
+                FDependency {
+                    node: 1,
+                    properties: EDependencyProperty::DIRECT,
+                },
+                FDependency {
+                    node: 2,
+                    properties: EDependencyProperty::NONE,
+                },
+            ],
+            referencers: vec![4, 5],
+        };
+        let mut buf = vec![];
+        let mut writer = Cursor::new(&mut buf);
+        node.write(&mut writer).unwrap();
+        let mut reader = Cursor::new(&buf);
+        let read_node = FDependsNode::read(&mut reader).unwrap();
+        assert_eq!(read_node, node);
+    }
+}
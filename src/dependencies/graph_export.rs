@@ -0,0 +1,408 @@
+//! Export of the dependency graph, or of the part reachable from some roots, for Graphviz (DOT),
+//! Gephi or yEd (GraphML) and graph databases or spreadsheets (node and edge CSV).
+//!
+//! Nodes are keyed by their [`ResolvedIdentifier`] string and carry the
+//! class of the package's first asset and the package's disk size when the registry has them.
+//! Edges carry their category (`package`, `manage` or `searchable_name`) and flags (`hard`,
+//! `game`, `build`, `direct`, joined by `|`).
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt::Write as _;
+
+use color_eyre::eyre::{eyre, Result as EResult};
+use tracing::*;
+
+use super::{
+    DependencyEdge, DependencyFilter, DependencyGraph, EDependencyCategory, EDependencyProperty,
+    ResolvedIdentifier,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExportNode {
+    pub identifier: String,
+    /// Class of the first asset of the node's package.
+    pub class: Option<String>,
+    /// Disk size of the node's package, from the package data.
+    pub disk_size: Option<i64>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ExportEdge {
+    /// Index into [`GraphExport::nodes`].
+    pub source: usize,
+    /// Index into [`GraphExport::nodes`].
+    pub target: usize,
+    pub category: EDependencyCategory,
+    pub properties: EDependencyProperty,
+}
+
+impl ExportEdge {
+    pub fn category_name(&self) -> &'static str {
+        match self.category {
+            EDependencyCategory::Package => "package",
+            EDependencyCategory::Manage => "manage",
+            EDependencyCategory::SearchableName => "searchable_name",
+        }
+    }
+
+    /// The set properties, e.g. `hard|game`; empty if none are.
+    pub fn flags(&self) -> String {
+        [
+            (EDependencyProperty::HARD, "hard"),
+            (EDependencyProperty::GAME, "game"),
+            (EDependencyProperty::BUILD, "build"),
+            (EDependencyProperty::DIRECT, "direct"),
+        ]
+        .into_iter()
+        .filter(|(property, _)| self.properties.contains(*property))
+        .map(|(_, name)| name)
+        .collect::<Vec<_>>()
+        .join("|")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    /// A node file and an edge file.
+    Csv,
+}
+
+impl std::str::FromStr for GraphFormat {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> EResult<Self> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            "csv" => Ok(GraphFormat::Csv),
+            _ => Err(eyre!(
+                "unknown graph format `{}`, expected dot, graphml or csv",
+                s
+            )),
+        }
+    }
+}
+
+/// A self-contained copy of (part of) the graph, ready to be formatted.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct GraphExport {
+    pub nodes: Vec<ExportNode>,
+    pub edges: Vec<ExportEdge>,
+}
+
+impl<'a> DependencyGraph<'a> {
+    /// The nodes reachable from `roots` within `max_depth` edges, with the edges between them;
+    /// the whole graph if `roots` is empty. Only edges accepted by `filter` are followed and kept.
+    #[instrument(name = "DependencyGraph_export", skip_all)]
+    pub fn export(
+        &self,
+        roots: &[usize],
+        max_depth: usize,
+        filter: &DependencyFilter,
+    ) -> EResult<GraphExport> {
+        let included = match roots {
+            [] => (0..self.len()).collect::<BTreeSet<_>>(),
+            _ => {
+                // Breadth-first from all roots at once, so every node is first reached at its
+                // distance from the nearest root and nothing beyond `max_depth` is expanded.
+                let mut included = roots.iter().copied().collect::<BTreeSet<_>>();
+                let mut queue = included
+                    .iter()
+                    .map(|&root| (root, 0))
+                    .collect::<VecDeque<_>>();
+                while let Some((node, depth)) = queue.pop_front() {
+                    if depth == max_depth {
+                        continue;
+                    }
+                    for edge in self.dependencies(node, filter) {
+                        if included.insert(edge.to) {
+                            queue.push_back((edge.to, depth + 1));
+                        }
+                    }
+                }
+                included
+            }
+        };
+
+        let registry = self.registry();
+        let names = &registry.names;
+        let mut classes = HashMap::new();
+        for asset in &registry.assets.assets {
+            let class = names.try_resolve(asset.asset_class)?;
+            classes
+                .entry(names.try_resolve(asset.package_name)?)
+                .or_insert(class);
+        }
+        let mut disk_sizes = HashMap::new();
+        for package in &registry.package_data.packages {
+            disk_sizes.insert(names.try_resolve(package.package_name)?, package.disk_size);
+        }
+
+        let mut export = GraphExport::default();
+        let mut positions = HashMap::new();
+        for &node in &included {
+            let identifier = self.identifier(node);
+            // Only package nodes (not objects or values in them) stand for the package.
+            let package = match identifier {
+                ResolvedIdentifier {
+                    package_name: Some(package),
+                    primary_asset_type: None,
+                    object_name: None,
+                    value_name: None,
+                } => Some(package),
+                _ => None,
+            };
+            positions.insert(node, export.nodes.len());
+            export.nodes.push(ExportNode {
+                identifier: identifier.to_string(),
+                class: package.and_then(|p| classes.get(p).cloned()),
+                disk_size: package.and_then(|p| disk_sizes.get(p).copied()),
+            });
+        }
+        for &node in &included {
+            for DependencyEdge {
+                from,
+                to,
+                category,
+                properties,
+            } in self.dependencies(node, filter)
+            {
+                if let Some(&target) = positions.get(&to) {
+                    export.edges.push(ExportEdge {
+                        source: positions[&from],
+                        target,
+                        category,
+                        properties,
+                    });
+                }
+            }
+        }
+        debug!(nodes = export.nodes.len(), edges = export.edges.len());
+        Ok(export)
+    }
+}
+
+impl GraphExport {
+    /// Graphviz DOT, e.g. for `dot -Tsvg`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph dependencies {\n".to_string();
+        for node in &self.nodes {
+            write!(dot, "  {}", dot_string(&node.identifier)).unwrap();
+            let mut attributes = vec![];
+            if let Some(class) = &node.class {
+                attributes.push(format!("class={}", dot_string(class)));
+            }
+            if let Some(disk_size) = node.disk_size {
+                attributes.push(format!("disk_size={disk_size}"));
+            }
+            match attributes.is_empty() {
+                true => dot.push_str(";\n"),
+                false => writeln!(dot, " [{}];", attributes.join(", ")).unwrap(),
+            }
+        }
+        for edge in &self.edges {
+            writeln!(
+                dot,
+                "  {} -> {} [category={}, flags={}];",
+                dot_string(&self.nodes[edge.source].identifier),
+                dot_string(&self.nodes[edge.target].identifier),
+                dot_string(edge.category_name()),
+                dot_string(&edge.flags())
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// GraphML with `class` and `disk_size` node keys and `category` and `flags` edge keys.
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        for (id, domain, ty) in [
+            ("class", "node", "string"),
+            ("disk_size", "node", "long"),
+            ("category", "edge", "string"),
+            ("flags", "edge", "string"),
+        ] {
+            writeln!(
+                xml,
+                "  <key id=\"{id}\" for=\"{domain}\" attr.name=\"{id}\" attr.type=\"{ty}\"/>"
+            )
+            .unwrap();
+        }
+        xml.push_str("  <graph id=\"dependencies\" edgedefault=\"directed\">\n");
+        for node in &self.nodes {
+            write!(xml, "    <node id=\"{}\">", xml_escape(&node.identifier)).unwrap();
+            if let Some(class) = &node.class {
+                write!(xml, "<data key=\"class\">{}</data>", xml_escape(class)).unwrap();
+            }
+            if let Some(disk_size) = node.disk_size {
+                write!(xml, "<data key=\"disk_size\">{disk_size}</data>").unwrap();
+            }
+            xml.push_str("</node>\n");
+        }
+        for edge in &self.edges {
+            writeln!(
+                xml,
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"category\">{}</data><data key=\"flags\">{}</data></edge>",
+                xml_escape(&self.nodes[edge.source].identifier),
+                xml_escape(&self.nodes[edge.target].identifier),
+                edge.category_name(),
+                edge.flags()
+            )
+            .unwrap();
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    /// One `id,class,disk_size` row per node, with a header.
+    pub fn to_nodes_csv(&self) -> String {
+        let mut csv = "id,class,disk_size\n".to_string();
+        for node in &self.nodes {
+            writeln!(
+                csv,
+                "{},{},{}",
+                csv_field(&node.identifier),
+                csv_field(node.class.as_deref().unwrap_or_default()),
+                node.disk_size.map(|s| s.to_string()).unwrap_or_default()
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    /// One `source,target,category,flags` row per edge, with a header.
+    pub fn to_edges_csv(&self) -> String {
+        let mut csv = "source,target,category,flags\n".to_string();
+        for edge in &self.edges {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                csv_field(&self.nodes[edge.source].identifier),
+                csv_field(&self.nodes[edge.target].identifier),
+                edge.category_name(),
+                edge.flags()
+            )
+            .unwrap();
+        }
+        csv
+    }
+}
+
+fn dot_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Quote a field if it contains a separator, quote or line break (RFC 4180).
+fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset_registry::AssetRegistry;
+    use crate::builder::RegistryBuilder;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let hard_game = EDependencyProperty::HARD | EDependencyProperty::GAME;
+        let mut builder = RegistryBuilder::new();
+        builder.add_asset("/Game/Maps/Arena.Arena", "World");
+        builder.add_asset("/Game/Weapons/BP_Rifle.BP_Rifle", "Blueprint");
+        builder.add_package_data("/Game/Maps/Arena", 300);
+        builder.add_package_data("/Game/Weapons/BP_Rifle", 20);
+        for (from, to, properties) in [
+            ("/Game/Maps/Arena", "/Game/Weapons/BP_Rifle", hard_game),
+            ("/Game/Weapons/BP_Rifle", "/Game/Weapons/T_Rifle", hard_game),
+            (
+                "/Game/Weapons/T_Rifle",
+                "/Game/Weapons/M_Rifle",
+                EDependencyProperty::GAME,
+            ),
+        ] {
+            builder.add_dependency(from, to, EDependencyCategory::Package, properties);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_export() {
+        let registry = fixture();
+        let graph = registry.dependency_graph().unwrap();
+        let all = DependencyFilter::default();
+
+        let full = graph.export(&[], usize::MAX, &all).unwrap();
+        assert_eq!(full.nodes.len(), 4);
+        assert_eq!(full.edges.len(), 3);
+
+        let arena = graph.find("/Game/Maps/Arena").unwrap();
+        let only_root = graph.export(&[arena], 0, &all).unwrap();
+        assert_eq!(only_root.nodes.len(), 1);
+        assert_eq!(only_root.edges.len(), 0);
+        let export = graph.export(&[arena], 1, &all).unwrap();
+        assert_eq!(
+            export.to_dot(),
+            r#"digraph dependencies {
+  "/Game/Maps/Arena" [class="World", disk_size=300];
+  "/Game/Weapons/BP_Rifle" [class="Blueprint", disk_size=20];
+  "/Game/Maps/Arena" -> "/Game/Weapons/BP_Rifle" [category="package", flags="hard|game"];
+}
+"#
+        );
+        assert_eq!(
+            export.to_nodes_csv(),
+            "id,class,disk_size\n/Game/Maps/Arena,World,300\n/Game/Weapons/BP_Rifle,Blueprint,20\n"
+        );
+        assert_eq!(
+            export.to_edges_csv(),
+            "source,target,category,flags\n/Game/Maps/Arena,/Game/Weapons/BP_Rifle,package,hard|game\n"
+        );
+
+        let hard = DependencyFilter::package(EDependencyProperty::HARD, EDependencyProperty::NONE);
+        let export = graph.export(&[arena], usize::MAX, &hard).unwrap();
+        let graphml = export.to_graphml();
+        assert!(graphml.contains("<node id=\"/Game/Weapons/T_Rifle\"></node>\n"));
+        assert!(graphml.contains(
+            "<edge source=\"/Game/Weapons/BP_Rifle\" target=\"/Game/Weapons/T_Rifle\"><data key=\"category\">package</data><data key=\"flags\">hard|game</data></edge>"
+        ));
+        assert!(!graphml.contains("M_Rifle"));
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(dot_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(xml_escape("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+        assert_eq!(csv_field("/Game/A"), "/Game/A");
+        assert_eq!(
+            "graphml".parse::<GraphFormat>().unwrap(),
+            GraphFormat::GraphMl
+        );
+        assert!("svg".parse::<GraphFormat>().is_err());
+    }
+}
@@ -0,0 +1,16 @@
+//! The dependency section of the asset registry: a graph of `FDependsNode`s referring to each
+//! other by index.
+
+mod asset_identifier;
+mod cycles;
+mod dependency_graph;
+mod dependency_section;
+mod depends_node;
+mod graph_export;
+
+pub use asset_identifier::*;
+pub use cycles::*;
+pub use dependency_graph::*;
+pub use dependency_section::*;
+pub use depends_node::*;
+pub use graph_export::*;
@@ -0,0 +1,489 @@
+//! Editing a loaded registry in place.
+//!
+//! Edits never rewrite name table entries, store values or pairs that something else might point
+//! at: new names and values are appended (reusing existing ones when equal) and an edited tag map
+//! is appended as a fresh run of pairs. Whatever becomes unreferenced stays in the tables until
+//! the registry is compacted.
+//!
+//! Interning needs an index of the name table and store. The methods on [`AssetRegistry`] build it
+//! for every edit; a [`RegistryEdit`] session from [`AssetRegistry::edit`] builds it once.
+
+use color_eyre::eyre::{eyre, Report, Result as EResult};
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::assets::AssetData;
+use crate::builder::{build_asset, build_value, push_tags, AssetBuilder, TagValue};
+use crate::names_batch::{NamesBatch, NamesBuilder};
+use crate::store_data::{StoreBuilder, StoreData, StoreValue};
+use crate::unreal_types::*;
+
+/// What [`AssetRegistry::remove_package`] removed.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct RemovedPackage {
+    pub assets: usize,
+    pub dependency_nodes: usize,
+    pub package_data: bool,
+}
+
+impl AssetRegistry {
+    /// Start a batch of edits. Each single-edit method on the registry indexes the whole name
+    /// table and store, so prefer a session when making more than a few edits.
+    pub fn edit(&mut self) -> RegistryEdit<'_> {
+        let names = NamesBuilder::from_names(std::mem::take(&mut self.names));
+        let store = StoreBuilder::from_store(std::mem::take(&mut self.store));
+        RegistryEdit {
+            registry: self,
+            names,
+            store,
+        }
+    }
+
+    /// Index of the asset with the given object path.
+    pub fn find_asset(&self, object_path: &str) -> Option<usize> {
+        find_asset(&self.names, &self.assets.assets, object_path)
+    }
+
+    /// Resolved tags of an asset, in stored order.
+    pub fn asset_tags(&self, object_path: &str) -> EResult<Vec<(String, StoreValue)>> {
+        let i = self
+            .find_asset(object_path)
+            .ok_or_else(|| no_asset(object_path))?;
+        tags_of(&self.names, &self.store, &self.assets.assets[i])
+    }
+
+    /// Append a new asset. Fails if an asset with the same object path already exists.
+    pub fn add_asset(&mut self, asset: &AssetBuilder) -> EResult<()> {
+        self.edit().add_asset(asset)
+    }
+
+    /// Remove a single asset, leaving the rest of its package alone. Removing the last asset of
+    /// a package also removes its package data, but keeps its dependency nodes.
+    pub fn remove_asset(&mut self, object_path: &str) -> EResult<AssetData> {
+        self.edit().remove_asset(object_path)
+    }
+
+    /// Remove a package: all of its assets, its package data and every dependency node
+    /// identifying the package or something inside it.
+    pub fn remove_package(&mut self, package_name: &str) -> EResult<RemovedPackage> {
+        self.edit().remove_package(package_name)
+    }
+
+    /// Change the class of an asset.
+    pub fn set_asset_class(&mut self, object_path: &str, class: &str) -> EResult<()> {
+        self.edit().set_asset_class(object_path, class)
+    }
+
+    /// Set a tag of an asset, replacing any previous value of the same key.
+    pub fn set_tag(
+        &mut self,
+        object_path: &str,
+        key: &str,
+        value: impl Into<TagValue>,
+    ) -> EResult<()> {
+        self.edit().set_tag(object_path, key, value)
+    }
+
+    /// Remove a tag of an asset. Returns whether the asset had the tag.
+    pub fn remove_tag(&mut self, object_path: &str, key: &str) -> EResult<bool> {
+        self.edit().remove_tag(object_path, key)
+    }
+}
+
+/// A batch of edits started by [`AssetRegistry::edit`]. The name table and store are indexed
+/// once for the whole batch and handed back to the registry when the session is dropped.
+pub struct RegistryEdit<'a> {
+    registry: &'a mut AssetRegistry,
+    names: NamesBuilder,
+    store: StoreBuilder,
+}
+
+impl Drop for RegistryEdit<'_> {
+    fn drop(&mut self) {
+        self.registry.names = std::mem::take(&mut self.names.names);
+        self.registry.store = std::mem::take(&mut self.store.store);
+    }
+}
+
+fn find_asset(names: &NamesBatch, assets: &[AssetData], object_path: &str) -> Option<usize> {
+    assets
+        .iter()
+        .position(|a| names.resolve(a.object_path).as_deref() == Some(object_path))
+}
+
+fn no_asset(object_path: &str) -> Report {
+    eyre!("no asset `{}` in the registry", object_path)
+}
+
+fn tags_of(
+    names: &NamesBatch,
+    store: &StoreData,
+    asset: &AssetData,
+) -> EResult<Vec<(String, StoreValue)>> {
+    store
+        .pairs_for(FPartialMapHandle::from_int(asset.tags))?
+        .iter()
+        .map(|pair| {
+            let value = store.value(FValueId::from_int(pair.value)?)?;
+            Ok((names.try_resolve(pair.key)?, value))
+        })
+        .collect()
+}
+
+impl RegistryEdit<'_> {
+    /// Index of the asset with the given object path.
+    pub fn find_asset(&self, object_path: &str) -> Option<usize> {
+        find_asset(&self.names.names, &self.registry.assets.assets, object_path)
+    }
+
+    fn asset_index(&self, object_path: &str) -> EResult<usize> {
+        self.find_asset(object_path)
+            .ok_or_else(|| no_asset(object_path))
+    }
+
+    /// Resolved tags of an asset, in stored order.
+    pub fn asset_tags(&self, object_path: &str) -> EResult<Vec<(String, StoreValue)>> {
+        let asset = &self.registry.assets.assets[self.asset_index(object_path)?];
+        tags_of(&self.names.names, &self.store.store, asset)
+    }
+
+    /// Append a new asset. Fails if an asset with the same object path already exists.
+    #[instrument(
+        name = "RegistryEdit_add_asset",
+        skip_all,
+        fields(object_path = asset.object_path())
+    )]
+    pub fn add_asset(&mut self, asset: &AssetBuilder) -> EResult<()> {
+        if self.find_asset(asset.object_path()).is_some() {
+            return Err(eyre!("duplicate asset `{}`", asset.object_path()));
+        }
+        let asset = build_asset(&mut self.names, &mut self.store, asset)?;
+        self.registry.assets.assets.push(asset);
+        Ok(())
+    }
+
+    /// Remove a single asset, leaving the rest of its package alone. Removing the last asset of
+    /// a package also removes its package data, which would be left describing nothing, but
+    /// keeps its dependency nodes since other packages may still reference it.
+    #[instrument(name = "RegistryEdit_remove_asset", skip(self))]
+    pub fn remove_asset(&mut self, object_path: &str) -> EResult<AssetData> {
+        let i = self.asset_index(object_path)?;
+        let registry = &mut *self.registry;
+        let asset = registry.assets.assets.remove(i);
+
+        let names = &self.names.names;
+        let package = names.try_resolve(asset.package_name)?;
+        let is_package = |name: FName| names.resolve(name).as_deref() == Some(package.as_str());
+        if !registry
+            .assets
+            .assets
+            .iter()
+            .any(|a| is_package(a.package_name))
+        {
+            registry
+                .package_data
+                .packages
+                .retain(|p| !is_package(p.package_name));
+        }
+        Ok(asset)
+    }
+
+    /// Remove a package: all of its assets, its package data and every dependency node
+    /// identifying the package or something inside it. Edges pointing at removed nodes are
+    /// dropped and the remaining nodes renumbered.
+    #[instrument(name = "RegistryEdit_remove_package", skip(self))]
+    pub fn remove_package(&mut self, package_name: &str) -> EResult<RemovedPackage> {
+        let names = &self.names.names;
+        let is_package = |name: FName| names.resolve(name).as_deref() == Some(package_name);
+        let registry = &mut *self.registry;
+
+        let mut removed = RemovedPackage::default();
+        let assets = registry.assets.assets.len();
+        registry
+            .assets
+            .assets
+            .retain(|a| !is_package(a.package_name));
+        removed.assets = assets - registry.assets.assets.len();
+
+        removed.dependency_nodes = registry
+            .dependencies
+            .remove_nodes(|n| n.identifier.package_name.is_some_and(is_package));
+
+        let packages = registry.package_data.packages.len();
+        registry
+            .package_data
+            .packages
+            .retain(|p| !is_package(p.package_name));
+        removed.package_data = packages != registry.package_data.packages.len();
+
+        debug!(?removed);
+        if removed == RemovedPackage::default() {
+            return Err(eyre!("no package `{}` in the registry", package_name));
+        }
+        Ok(removed)
+    }
+
+    /// Change the class of an asset.
+    #[instrument(name = "RegistryEdit_set_asset_class", skip(self))]
+    pub fn set_asset_class(&mut self, object_path: &str, class: &str) -> EResult<()> {
+        let i = self.asset_index(object_path)?;
+        self.registry.assets.assets[i].asset_class = self.names.intern(class)?;
+        Ok(())
+    }
+
+    /// Set a tag of an asset, replacing any previous value of the same key.
+    #[instrument(name = "RegistryEdit_set_tag", skip(self, value))]
+    pub fn set_tag(
+        &mut self,
+        object_path: &str,
+        key: &str,
+        value: impl Into<TagValue>,
+    ) -> EResult<()> {
+        let value = value.into();
+        self.edit_tag_map(object_path, |names, store, map| {
+            let key = names.intern(key)?;
+            let value = build_value(names, store, &value)?;
+            match map.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => *v = value,
+                None => map.push((key, value)),
+            }
+            Ok(())
+        })
+    }
+
+    /// Remove a tag of an asset. Returns whether the asset had the tag.
+    #[instrument(name = "RegistryEdit_remove_tag", skip(self))]
+    pub fn remove_tag(&mut self, object_path: &str, key: &str) -> EResult<bool> {
+        if !self.asset_tags(object_path)?.iter().any(|(k, _)| k == key) {
+            return Ok(false);
+        }
+        self.edit_tag_map(object_path, |names, _, map| {
+            map.retain(|(k, _)| names.names.resolve(*k).as_deref() != Some(key));
+            Ok(())
+        })?;
+        Ok(true)
+    }
+
+    /// Copy an asset's tag map, let `edit` change it and point the asset at the edited copy.
+    /// Handles may be shared between assets, so the original pairs are left untouched.
+    fn edit_tag_map(
+        &mut self,
+        object_path: &str,
+        edit: impl FnOnce(
+            &mut NamesBuilder,
+            &mut StoreBuilder,
+            &mut Vec<(FName, FValueId)>,
+        ) -> EResult<()>,
+    ) -> EResult<()> {
+        let i = self.asset_index(object_path)?;
+        let asset = &mut self.registry.assets.assets[i];
+        let mut map = self
+            .store
+            .store
+            .pairs_for(FPartialMapHandle::from_int(asset.tags))?
+            .iter()
+            .map(|pair| Ok((pair.key, FValueId::from_int(pair.value)?)))
+            .collect::<EResult<Vec<_>>>()?;
+        edit(&mut self.names, &mut self.store, &mut map)?;
+        asset.tags = push_tags(&mut self.store, object_path, &map)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::RegistryBuilder;
+    use crate::dependencies::{EDependencyCategory, EDependencyProperty};
+    use crate::read::Readable;
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/A.A", "Blueprint")
+            .tag("ParentClass", TagValue::Name("Actor".to_string()))
+            .tag("Color", "red");
+        builder
+            .add_asset("/Game/B.B", "Texture2D")
+            .tag("Color", "red");
+        builder.add_asset("/Game/C.C", "World");
+        for (from, to) in [
+            ("/Game/A", "/Game/B"),
+            ("/Game/C", "/Game/B"),
+            ("/Game/C", "/Game/A"),
+        ] {
+            builder.add_dependency(
+                from,
+                to,
+                EDependencyCategory::Package,
+                EDependencyProperty::HARD,
+            );
+        }
+        builder.add_package_data("/Game/A", 1);
+        builder.add_package_data("/Game/B", 2);
+        builder.build().unwrap()
+    }
+
+    fn roundtrip(registry: &AssetRegistry) -> AssetRegistry {
+        let mut buf = vec![];
+        registry.write(&mut Cursor::new(&mut buf)).unwrap();
+        AssetRegistry::read(&mut Cursor::new(&buf)).unwrap()
+    }
+
+    fn red() -> StoreValue {
+        StoreValue::AnsiString("red".to_string())
+    }
+
+    #[test]
+    fn test_add_asset() {
+        let mut registry = fixture();
+        let names = registry.names.strings.len();
+        let mut asset = AssetBuilder::new("/Game/D.D", "Blueprint");
+        asset.tag("Color", "red");
+        registry.add_asset(&asset).unwrap();
+
+        // Only `/Game/D.D`, `/Game/D` and `D` are new names; `red` and the rest are reused.
+        assert_eq!(registry.names.strings.len(), names + 3);
+        assert_eq!(registry.store.ansi_strings, vec!["red"]);
+        assert_eq!(
+            registry.asset_tags("/Game/D.D").unwrap(),
+            vec![("Color".to_string(), red())]
+        );
+        assert!(registry.add_asset(&asset).is_err());
+        assert_eq!(roundtrip(&registry), registry);
+    }
+
+    #[test]
+    fn test_edit_tags() {
+        let mut registry = fixture();
+        registry.set_tag("/Game/B.B", "Color", "blue").unwrap();
+        registry
+            .set_tag("/Game/B.B", "Size", TagValue::Text("big".to_string()))
+            .unwrap();
+        assert_eq!(
+            registry.asset_tags("/Game/B.B").unwrap(),
+            vec![
+                (
+                    "Color".to_string(),
+                    StoreValue::AnsiString("blue".to_string())
+                ),
+                (
+                    "Size".to_string(),
+                    StoreValue::LocalizedText(FText::from("big"))
+                ),
+            ]
+        );
+        // Other assets with the same value are unaffected.
+        assert_eq!(
+            registry.asset_tags("/Game/A.A").unwrap()[1],
+            ("Color".to_string(), red())
+        );
+
+        assert!(registry.remove_tag("/Game/A.A", "ParentClass").unwrap());
+        assert!(!registry.remove_tag("/Game/A.A", "ParentClass").unwrap());
+        assert_eq!(
+            registry.asset_tags("/Game/A.A").unwrap(),
+            vec![("Color".to_string(), red())]
+        );
+
+        registry
+            .set_asset_class("/Game/A.A", "WidgetBlueprint")
+            .unwrap();
+        let asset = &registry.assets.assets[registry.find_asset("/Game/A.A").unwrap()];
+        assert_eq!(
+            registry.names.try_resolve(asset.asset_class).unwrap(),
+            "WidgetBlueprint"
+        );
+
+        assert!(registry
+            .set_tag("/Game/Missing.Missing", "Color", "red")
+            .is_err());
+        assert_eq!(roundtrip(&registry), registry);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut registry = fixture();
+        registry.remove_asset("/Game/C.C").unwrap();
+        assert!(registry.remove_asset("/Game/C.C").is_err());
+        assert_eq!(registry.dependencies.nodes.len(), 3);
+
+        let removed = registry.remove_package("/Game/A").unwrap();
+        assert_eq!(
+            removed,
+            RemovedPackage {
+                assets: 1,
+                dependency_nodes: 1,
+                package_data: true,
+            }
+        );
+        assert!(registry.remove_package("/Game/A").is_err());
+
+        let nodes = &registry.dependencies.nodes;
+        let packages = nodes
+            .iter()
+            .map(|n| n.identifier.resolve(&registry.names).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(packages, vec!["/Game/B", "/Game/C"]);
+        // C -> A is gone, C -> B is renumbered.
+        assert_eq!(nodes[1].package_dependencies.len(), 1);
+        assert_eq!(nodes[1].package_dependencies[0].node, 0);
+        assert_eq!(nodes[0].referencers, vec![1]);
+        assert_eq!(registry.package_data.packages.len(), 1);
+        assert_eq!(roundtrip(&registry), registry);
+    }
+
+    #[test]
+    fn test_remove_last_asset() {
+        let mut registry = fixture();
+        let mut asset = AssetBuilder::new("/Game/B.B_Extra", "Texture2D");
+        asset.tag("Color", "red");
+        registry.add_asset(&asset).unwrap();
+
+        registry.remove_asset("/Game/B.B").unwrap();
+        assert_eq!(registry.package_data.packages.len(), 2);
+        registry.remove_asset("/Game/B.B_Extra").unwrap();
+        let packages = registry
+            .package_data
+            .packages
+            .iter()
+            .map(|p| registry.names.try_resolve(p.package_name).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(packages, vec!["/Game/A"]);
+        // The node of `/Game/B` stays, `/Game/A` and `/Game/C` still depend on it.
+        assert_eq!(registry.dependencies.nodes.len(), 3);
+        assert_eq!(registry.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_edit_session() {
+        let mut registry = fixture();
+        let names = registry.names.strings.len();
+        let mut edit = registry.edit();
+        edit.add_asset(&AssetBuilder::new("/Game/D.D", "Blueprint"))
+            .unwrap();
+        edit.set_tag("/Game/D.D", "Color", "red").unwrap();
+        edit.set_asset_class("/Game/D.D", "World").unwrap();
+        assert!(edit.remove_tag("/Game/D.D", "Color").unwrap());
+        assert!(edit
+            .set_tag("/Game/Missing.Missing", "Color", "red")
+            .is_err());
+        edit.remove_package("/Game/A").unwrap();
+        assert_eq!(edit.asset_tags("/Game/D.D").unwrap(), vec![]);
+        drop(edit);
+
+        // The tables are handed back, with only `/Game/D.D`, `/Game/D` and `D` added.
+        assert_eq!(registry.names.strings.len(), names + 3);
+        assert_eq!(registry.find_asset("/Game/A.A"), None);
+        let asset = &registry.assets.assets[registry.find_asset("/Game/D.D").unwrap()];
+        assert_eq!(
+            registry.names.try_resolve(asset.asset_class).unwrap(),
+            "World"
+        );
+        assert_eq!(roundtrip(&registry), registry);
+    }
+}
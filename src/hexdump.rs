@@ -0,0 +1,401 @@
+//! Hex dump of a registry file, annotated with the span map: every region is labeled with the
+//! structure and field that owns it and its decoded value, and unconsumed bytes are flagged.
+
+use std::io::{Cursor, Write};
+use std::ops::Range;
+
+use crate::asset_registry::AssetRegistry;
+use crate::asset_registry_version::AssetRegistryVersion;
+use crate::read::Readable;
+use crate::serialized_name_header::SerializedNameHeader;
+use crate::spans::{Span, SpanMap};
+use crate::unreal_types::{FName, FPartialMapHandle, FString, FValueId};
+
+const BYTES_PER_LINE: usize = 16;
+
+const RED: &str = "\x1b[31m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// A run of bytes with a single owner.
+enum Region<'s> {
+    /// A leaf field.
+    Field(&'s Span),
+    /// Bytes of a structure that none of its fields claim.
+    Unclaimed(&'s Span),
+    /// Bytes no structure consumed.
+    Gap,
+}
+
+pub struct HexDump<'a> {
+    data: &'a [u8],
+    spans: &'a SpanMap,
+    registry: &'a AssetRegistry,
+    /// Only dump the bytes in this range.
+    pub range: Range<u64>,
+    /// Elide the middle of regions longer than this many lines.
+    pub max_lines: Option<usize>,
+    /// Highlight with ANSI escapes.
+    pub color: bool,
+}
+
+impl<'a> HexDump<'a> {
+    /// `spans` and `registry` are the result of parsing `data`.
+    pub fn new(data: &'a [u8], spans: &'a SpanMap, registry: &'a AssetRegistry) -> Self {
+        HexDump {
+            data,
+            spans,
+            registry,
+            range: 0..data.len() as u64,
+            max_lines: None,
+            color: false,
+        }
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let spans = &self.spans.spans;
+        let mut position = 0;
+        // The structures enclosing `position`, outermost first.
+        let mut open = vec![];
+        for (i, span) in spans.iter().enumerate() {
+            let start = span.range.start.max(position);
+            if start >= span.range.end {
+                continue;
+            }
+            self.fill(out, &mut open, position, start)?;
+            position = start;
+
+            let is_leaf = spans
+                .get(i + 1)
+                .is_none_or(|next| next.range.start >= span.range.end);
+            if is_leaf {
+                self.region(out, start..span.range.end, Region::Field(span))?;
+                position = span.range.end;
+            } else if is_heading(&span.path) && self.range.contains(&start) {
+                let (bold, reset) = self.style(BOLD);
+                writeln!(
+                    out,
+                    "{bold}-- {} ({} bytes){reset}",
+                    span.path,
+                    span.range.end - span.range.start
+                )?;
+            }
+            if !is_leaf {
+                open.push(span);
+            }
+        }
+        self.fill(out, &mut open, position, self.data.len() as u64)
+    }
+
+    /// Dump the bytes between leaf fields, attributing them to the innermost enclosing span.
+    /// `open` holds the structures that started before `from`; no span starts within `from..to`.
+    fn fill(
+        &self,
+        out: &mut impl Write,
+        open: &mut Vec<&Span>,
+        mut from: u64,
+        to: u64,
+    ) -> std::io::Result<()> {
+        while from < to {
+            while open.last().is_some_and(|span| span.range.end <= from) {
+                open.pop();
+            }
+            match open.last() {
+                Some(span) => {
+                    let end = span.range.end.min(to);
+                    self.region(out, from..end, Region::Unclaimed(span))?;
+                    from = end;
+                }
+                None => {
+                    self.region(out, from..to, Region::Gap)?;
+                    from = to;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn style(&self, style: &'static str) -> (&'static str, &'static str) {
+        match self.color {
+            true => (style, RESET),
+            false => ("", ""),
+        }
+    }
+
+    fn region(
+        &self,
+        out: &mut impl Write,
+        range: Range<u64>,
+        region: Region,
+    ) -> std::io::Result<()> {
+        let start = range.start.max(self.range.start);
+        let end = range.end.min(self.range.end);
+        if start >= end {
+            return Ok(());
+        }
+
+        let bytes = &self.data[range.start as usize..range.end as usize];
+        let (label, (style, reset), marker) = match region {
+            Region::Field(span) => (
+                match self.describe(&span.path, bytes) {
+                    Some(value) => format!("{} = {}", span.path, value),
+                    None => span.path.clone(),
+                },
+                self.style(""),
+                ' ',
+            ),
+            Region::Unclaimed(span) => (format!("{} (unparsed)", span.path), self.style(RED), '!'),
+            Region::Gap => ("<unconsumed>".to_string(), self.style(RED), '!'),
+        };
+
+        let lines = (start..end)
+            .step_by(BYTES_PER_LINE)
+            .map(|line| line..(line + BYTES_PER_LINE as u64).min(end))
+            .collect::<Vec<_>>();
+        let elide = match self.max_lines {
+            Some(max) if lines.len() > max => Some(max.saturating_sub(1).max(1)),
+            _ => None,
+        };
+        for (i, line) in lines.iter().enumerate() {
+            if let Some(keep) = elide {
+                if i == keep {
+                    let elided = lines[keep..lines.len() - 1]
+                        .iter()
+                        .map(|l| l.end - l.start)
+                        .sum::<u64>();
+                    writeln!(out, "{style}{marker}         ... {elided} bytes ...{reset}")?;
+                }
+                if i >= keep && i != lines.len() - 1 {
+                    continue;
+                }
+            }
+            let chunk = &self.data[line.start as usize..line.end as usize];
+            let hex = chunk
+                .iter()
+                .map(|b| format!("{b:02X}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|b| match b.is_ascii_graphic() || *b == b' ' {
+                    true => *b as char,
+                    false => '.',
+                })
+                .collect::<String>();
+            let label = match i {
+                0 => label.as_str(),
+                _ => "",
+            };
+            let line = format!(
+                "{marker}{:08X}  {:<hex_width$}  |{:<BYTES_PER_LINE$}|  {}",
+                line.start,
+                hex,
+                ascii,
+                label,
+                hex_width = BYTES_PER_LINE * 3 - 1,
+            );
+            writeln!(out, "{style}{}{reset}", line.trim_end())?;
+        }
+        Ok(())
+    }
+
+    /// The decoded value of the field at `path`, if there is a more useful rendering than the
+    /// bytes themselves.
+    fn describe(&self, path: &str, bytes: &[u8]) -> Option<String> {
+        let registry = self.registry;
+        let index = |prefix: &str| -> Option<usize> {
+            path.strip_prefix(prefix)?.strip_suffix(']')?.parse().ok()
+        };
+        let int = || -> Option<u64> {
+            match bytes.len() {
+                1 | 2 | 4 | 8 => {
+                    let mut buf = [0u8; 8];
+                    buf[..bytes.len()].copy_from_slice(bytes);
+                    Some(u64::from_le_bytes(buf))
+                }
+                _ => None,
+            }
+        };
+        let hex = || bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+
+        if let Some(i) = index("names.strings[") {
+            return registry.names.strings.get(i).map(|s| format!("{s:?}"));
+        }
+        if index("names.headers[").is_some() {
+            let header = SerializedNameHeader::read(&mut Cursor::new(bytes)).ok()?;
+            return Some(format!("utf16 = {}, len = {}", header.is_utf16, header.len));
+        }
+        if index("names.hashes[").is_some() {
+            return Some(format!("{:#018x}", int()?));
+        }
+        if let Some(i) = index("store.ansi_strings[") {
+            return registry.store.ansi_strings.get(i).map(|s| format!("{s:?}"));
+        }
+        if let Some(i) = index("store.wide_strings[") {
+            return registry.store.wide_strings.get(i).map(|s| format!("{s:?}"));
+        }
+        if let Some(i) = index("store.text_data[") {
+            let text = registry.store.text_data.get(i)?.try_into_string().ok()?;
+            return Some(format!("{text:?}"));
+        }
+        if path == "header.version" {
+            let version = AssetRegistryVersion::read(&mut Cursor::new(bytes)).ok()?;
+            return Some(format!("{version:?}"));
+        }
+        if path.ends_with("magic") || path.ends_with(".field_bits") {
+            return Some(format!("{:#x}", int()?));
+        }
+        if path.ends_with("guid") || path.ends_with("cooked_hash") {
+            return Some(hex());
+        }
+        if path.ends_with(".sub_path_string") {
+            let s = FString::read(&mut Cursor::new(bytes)).ok()?;
+            return Some(format!("{:?}", s.as_str()));
+        }
+        if path.ends_with(".index") {
+            // Only `FName`s have an `index` field.
+            let index = int()? as u32 & !FName::HAS_NUMBER;
+            let name = registry.names.get(FName { index, number: 0 })?;
+            return Some(format!("{index} {name:?}"));
+        }
+        if path.starts_with("assets.assets[") && path.ends_with("].tags") {
+            let handle = FPartialMapHandle::from_int(int()?);
+            return Some(format!(
+                "{} pairs from {}{}",
+                handle.num,
+                handle.pair_begin,
+                match handle.has_numberless_keys {
+                    true => " (numberless)",
+                    false => "",
+                }
+            ));
+        }
+        if path.starts_with("store.") && path.ends_with("].value") {
+            let id = FValueId::from_int(int()? as u32).ok()?;
+            return Some(format!("{:?}[{}]", id.ty, id.index));
+        }
+        if path.ends_with("disk_size") || path.ends_with("size") {
+            return Some((int()? as i64).to_string());
+        }
+        int().map(|i| i.to_string())
+    }
+}
+
+/// Structures that get a heading line: sections and array elements.
+fn is_heading(path: &str) -> bool {
+    !path.contains(['.', '[']) || path.ends_with(']')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::RegistryBuilder;
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn dump(data: &[u8], configure: impl FnOnce(&mut HexDump)) -> String {
+        let ((registry, _), spans) = AssetRegistry::read_lenient_with_spans(data);
+        let mut hexdump = HexDump::new(data, &spans, &registry);
+        configure(&mut hexdump);
+        let mut out = vec![];
+        hexdump.write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn bytes() -> Vec<u8> {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/A.A", "Blueprint")
+            .tag("Color", "red");
+        let mut buf = vec![];
+        builder
+            .build()
+            .unwrap()
+            .write(&mut Cursor::new(&mut buf))
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_hexdump() {
+        let mut buf = bytes();
+        let len = buf.len();
+        buf.extend_from_slice(b"??");
+        let out = dump(&buf, |_| {});
+        let lines = out.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "-- header (20 bytes)");
+        assert_eq!(
+            lines[1],
+            " 00000000  E7 9E 7F 71 3A 49 B0 E9 32 91 B3 88 07 81 38 1B  |...q:I..2.....8.|  \
+             header.guid = e79e7f713a49b0e93291b3880781381b"
+        );
+        assert_eq!(
+            lines[2],
+            format!(
+                " 00000010  {:<47}  |{:<16}|  header.version = FixedTags",
+                "08 00 00 00", "...."
+            )
+        );
+        assert_eq!(lines[3], "-- names (112 bytes)");
+        for line in [
+            " 00000073  42 6C 75 65 70 72 69 6E 74                       |Blueprint       |  \
+             names.strings[3] = \"Blueprint\"",
+            " 000000BC  72 65 64 00                                      |red.            |  \
+             store.ansi_strings[0] = \"red\"",
+            " 000000C4  00 00 00 00                                      |....            |  \
+             store.numberless_pairs[0].value = AnsiString[0]",
+            "-- assets.assets[0] (40 bytes)",
+            " 000000D8  03 00 00 00                                      |....            |  \
+             assets.assets[0].asset_class.index = 3 \"Blueprint\"",
+            " 000000E4  00 00 00 00 01 00 00 80                          |........        |  \
+             assets.assets[0].tags = 1 pairs from 0 (numberless)",
+            " 000000F0  00 00 00 00                                      |....            |  \
+             assets.assets[0].chunk_count = 0",
+        ] {
+            assert!(lines.contains(&line), "missing {line:?}");
+        }
+        assert_eq!(
+            lines.last().unwrap(),
+            &format!(
+                "!{:08X}  {:<47}  |{:<16}|  <unconsumed>",
+                len, "3F 3F", "??"
+            )
+        );
+    }
+
+    #[test]
+    fn test_range_and_elision() {
+        let buf = bytes();
+        let out = dump(&buf, |h| h.range = 0x10..0x18);
+        assert_eq!(
+            out.lines().collect::<Vec<_>>(),
+            vec![
+                " 00000010  08 00 00 00                                      |....            |  \
+                 header.version = FixedTags",
+                "-- names (112 bytes)",
+                " 00000014  06 00 00 00                                      |....            |  \
+                 names.count = 6",
+            ]
+        );
+
+        let mut builder = RegistryBuilder::new();
+        builder.add_asset("/Game/A.A", &"X".repeat(64));
+        let mut buf = vec![];
+        builder
+            .build()
+            .unwrap()
+            .write(&mut Cursor::new(&mut buf))
+            .unwrap();
+        let out = dump(&buf, |h| h.max_lines = Some(2));
+        let lines = out.lines().collect::<Vec<_>>();
+        let i = lines
+            .iter()
+            .position(|l| l.ends_with(&format!("= \"{}\"", "X".repeat(64))))
+            .unwrap();
+        assert_eq!(lines[i + 1], "          ... 32 bytes ...");
+        assert!(lines[i + 2].ends_with("|XXXXXXXXXXXXXXXX|"));
+    }
+}
@@ -0,0 +1,481 @@
+//! Export of a registry as a single static HTML page: a collapsible tree of the parsed registry
+//! next to a hex view of the file. Selecting a tree node highlights its bytes and clicking a byte
+//! selects the node that owns it.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::ops::Range;
+
+use crate::asset_registry::AssetRegistry;
+use crate::dependencies::{EDependencyProperty, FDependency};
+use crate::lenient::Diagnostic;
+use crate::spans::{write_json_string, SpanMap};
+use crate::unreal_types::{FName, FPartialMapHandle, FValueId};
+
+const VIEWER: &str = include_str!("viewer.html");
+
+/// A node of the tree pane.
+struct Node {
+    label: String,
+    /// The span whose bytes the node stands for.
+    path: Option<String>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(label: impl Into<String>, path: Option<String>) -> Self {
+        Node {
+            label: label.into(),
+            path,
+            children: vec![],
+        }
+    }
+
+    fn write_json(&self, json: &mut String, ranges: &HashMap<&str, Range<u64>>) {
+        json.push_str("{\"l\":");
+        write_json_string(json, &self.label);
+        if let Some(range) = self.path.as_deref().and_then(|p| ranges.get(p)) {
+            json.push_str(",\"p\":");
+            write_json_string(json, self.path.as_deref().unwrap());
+            write!(json, ",\"r\":[{},{}]", range.start, range.end).unwrap();
+        }
+        if !self.children.is_empty() {
+            json.push_str(",\"c\":[");
+            for (i, child) in self.children.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                child.write_json(json, ranges);
+            }
+            json.push(']');
+        }
+        json.push('}');
+    }
+}
+
+fn properties(properties: EDependencyProperty) -> String {
+    let flags = [
+        (EDependencyProperty::HARD, "hard"),
+        (EDependencyProperty::GAME, "game"),
+        (EDependencyProperty::BUILD, "build"),
+        (EDependencyProperty::DIRECT, "direct"),
+    ];
+    flags
+        .iter()
+        .filter(|(flag, _)| properties.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Builds the tree of a (possibly partially parsed) registry, tolerating dangling references.
+struct TreeBuilder<'a> {
+    registry: &'a AssetRegistry,
+}
+
+impl TreeBuilder<'_> {
+    fn name(&self, name: FName) -> String {
+        self.registry
+            .names
+            .resolve(name)
+            .unwrap_or_else(|| format!("<name {}>", name.index))
+    }
+
+    fn node_label(&self, node: i32) -> String {
+        let names = &self.registry.names;
+        self.registry
+            .dependencies
+            .nodes
+            .get(node as usize)
+            .and_then(|n| n.identifier.resolve(names).ok())
+            .map_or_else(|| format!("<node {node}>"), |id| id.to_string())
+    }
+
+    fn tree(&self) -> Vec<Node> {
+        vec![
+            self.assets(),
+            self.dependencies(),
+            self.package_data(),
+            self.names(),
+        ]
+    }
+
+    fn assets(&self) -> Node {
+        let registry = self.registry;
+        let assets = &registry.assets.assets;
+        let mut root = Node::new(
+            format!("Assets ({})", assets.len()),
+            Some("assets".to_string()),
+        );
+        for (i, asset) in assets.iter().enumerate() {
+            let path = format!("assets.assets[{i}]");
+            let mut node = Node::new(
+                format!(
+                    "{} ({})",
+                    self.name(asset.object_path),
+                    self.name(asset.asset_class)
+                ),
+                Some(path.clone()),
+            );
+            for (label, field, name) in [
+                ("class", "asset_class", asset.asset_class),
+                ("package", "package_name", asset.package_name),
+                ("package path", "package_path", asset.package_path),
+                ("asset name", "asset_name", asset.asset_name),
+            ] {
+                node.children.push(Node::new(
+                    format!("{label}: {}", self.name(name)),
+                    Some(format!("{path}.{field}")),
+                ));
+            }
+
+            let handle = FPartialMapHandle::from_int(asset.tags);
+            let mut tags = Node::new(
+                format!("tags ({})", handle.num),
+                Some(format!("{path}.tags")),
+            );
+            let pairs = match handle.has_numberless_keys {
+                true => ("numberless_pairs", &registry.store.numberless_pairs),
+                false => ("pairs", &registry.store.pairs),
+            };
+            for i in handle.pair_range() {
+                let Some(pair) = pairs.1.get(i) else {
+                    tags.children
+                        .push(Node::new(format!("<missing pair {i}>"), None));
+                    continue;
+                };
+                let value = FValueId::from_int(pair.value)
+                    .and_then(|id| registry.store.value(id))
+                    .map_or_else(|e| format!("<{e}>"), |v| v.resolve(&registry.names));
+                tags.children.push(Node::new(
+                    format!("{} = {}", self.name(pair.key), value),
+                    Some(format!("store.{}[{i}]", pairs.0)),
+                ));
+            }
+            node.children.push(tags);
+
+            if !asset.bundles.is_empty() {
+                let mut bundles = Node::new(
+                    format!("bundles ({})", asset.bundles.len()),
+                    Some(format!("{path}.bundles")),
+                );
+                for (b, bundle) in asset.bundles.iter().enumerate() {
+                    let bundle_path = format!("{path}.bundles[{b}]");
+                    let mut entry =
+                        Node::new(self.name(bundle.bundle_name), Some(bundle_path.clone()));
+                    for (p, soft) in bundle.bundles.iter().enumerate() {
+                        let sub_path = soft.sub_path_string.as_str();
+                        entry.children.push(Node::new(
+                            match sub_path.is_empty() {
+                                true => self.name(soft.asset_path_name),
+                                false => {
+                                    format!("{}:{}", self.name(soft.asset_path_name), sub_path)
+                                }
+                            },
+                            Some(format!("{bundle_path}.bundles[{p}]")),
+                        ));
+                    }
+                    bundles.children.push(entry);
+                }
+                node.children.push(bundles);
+            }
+            root.children.push(node);
+        }
+        root
+    }
+
+    fn dependencies(&self) -> Node {
+        let nodes = &self.registry.dependencies.nodes;
+        let mut root = Node::new(
+            format!("Dependencies ({})", nodes.len()),
+            Some("dependencies".to_string()),
+        );
+        for (i, node) in nodes.iter().enumerate() {
+            let path = format!("dependencies.nodes[{i}]");
+            let mut entry = Node::new(self.node_label(i as i32), Some(path.clone()));
+            let edges = |label: &str, field: &str, dependencies: &[FDependency]| {
+                let mut list = Node::new(
+                    format!("{label} ({})", dependencies.len()),
+                    Some(format!("{path}.{field}")),
+                );
+                list.children = dependencies
+                    .iter()
+                    .map(|d| {
+                        Node::new(
+                            format!("{} [{}]", self.node_label(d.node), properties(d.properties)),
+                            None,
+                        )
+                    })
+                    .collect();
+                list
+            };
+            let indices = |label: &str, field: &str, indices: &[i32]| {
+                let mut list = Node::new(
+                    format!("{label} ({})", indices.len()),
+                    Some(format!("{path}.{field}")),
+                );
+                list.children = indices
+                    .iter()
+                    .enumerate()
+                    .map(|(j, n)| {
+                        Node::new(
+                            self.node_label(*n),
+                            Some(format!("{path}.{field}.indices[{j}]")),
+                        )
+                    })
+                    .collect();
+                list
+            };
+            entry.children = vec![
+                edges(
+                    "package dependencies",
+                    "package_dependencies",
+                    &node.package_dependencies,
+                ),
+                indices(
+                    "name dependencies",
+                    "name_dependencies",
+                    &node.name_dependencies,
+                ),
+                edges(
+                    "manage dependencies",
+                    "manage_dependencies",
+                    &node.manage_dependencies,
+                ),
+                indices("referencers", "referencers", &node.referencers),
+            ];
+            entry.children.retain(|c| !c.label.ends_with("(0)"));
+            root.children.push(entry);
+        }
+        root
+    }
+
+    fn package_data(&self) -> Node {
+        let packages = &self.registry.package_data.packages;
+        let mut root = Node::new(
+            format!("Package data ({})", packages.len()),
+            Some("package_data".to_string()),
+        );
+        for (i, package) in packages.iter().enumerate() {
+            let path = format!("package_data.packages[{i}]");
+            let mut node = Node::new(self.name(package.package_name), Some(path.clone()));
+            node.children.push(Node::new(
+                format!("disk size: {}", package.disk_size),
+                Some(format!("{path}.disk_size")),
+            ));
+            if let Some(hash) = package.cooked_hash {
+                let hash = hash.iter().map(|b| format!("{b:02x}")).collect::<String>();
+                node.children.push(Node::new(
+                    format!("cooked hash: {hash}"),
+                    Some(format!("{path}.cooked_hash")),
+                ));
+            }
+            root.children.push(node);
+        }
+        root
+    }
+
+    fn names(&self) -> Node {
+        let strings = &self.registry.names.strings;
+        let mut root = Node::new(
+            format!("Names ({})", strings.len()),
+            Some("names".to_string()),
+        );
+        root.children = strings
+            .iter()
+            .enumerate()
+            .map(|(i, s)| Node::new(format!("{i}: {s}"), Some(format!("names.strings[{i}]"))))
+            .collect();
+        root
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+/// The data the viewer script works on.
+fn viewer_data(
+    title: &str,
+    data: &[u8],
+    registry: &AssetRegistry,
+    spans: &SpanMap,
+    diagnostics: &[Diagnostic],
+) -> String {
+    let ranges = spans
+        .spans
+        .iter()
+        .map(|s| (s.path.as_str(), s.range.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let mut json = String::from("{\"title\":");
+    write_json_string(&mut json, title);
+    write!(json, ",\"bytes\":\"{}\"", base64(data)).unwrap();
+
+    json.push_str(",\"spans\":[");
+    for (i, span) in spans.spans.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        write!(json, "[{},{},", span.range.start, span.range.end).unwrap();
+        write_json_string(&mut json, &span.path);
+        json.push(']');
+    }
+
+    json.push_str("],\"diagnostics\":[");
+    for (i, diagnostic) in diagnostics.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        write_json_string(&mut json, &diagnostic.to_string());
+    }
+
+    json.push_str("],\"tree\":[");
+    let tree = TreeBuilder { registry }.tree();
+    for (i, node) in tree.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        node.write_json(&mut json, &ranges);
+    }
+    json.push_str("]}");
+    json
+}
+
+/// Write the viewer page for the registry file `data`. The file is parsed leniently, so damaged
+/// files can be inspected too; parse problems are listed on the page.
+pub fn write_html(out: &mut impl Write, title: &str, data: &[u8]) -> std::io::Result<()> {
+    let ((registry, diagnostics), spans) = AssetRegistry::read_lenient_with_spans(data);
+    let mut escaped_title = String::new();
+    for c in title.chars() {
+        match c {
+            '<' => escaped_title.push_str("&lt;"),
+            '>' => escaped_title.push_str("&gt;"),
+            '&' => escaped_title.push_str("&amp;"),
+            c => escaped_title.push(c),
+        }
+    }
+    let data = viewer_data(title, data, &registry, &spans, &diagnostics);
+    let page = fill(
+        VIEWER,
+        &[("{{TITLE}}", &escaped_title), ("{{DATA}}", &data)],
+    );
+    out.write_all(page.as_bytes())
+}
+
+/// Replace every placeholder of `template` in a single pass, so that a value containing a
+/// placeholder is left as it is.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut page =
+        String::with_capacity(template.len() + values.iter().map(|(_, v)| v.len()).sum::<usize>());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        page.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match values
+            .iter()
+            .find(|(placeholder, _)| rest.starts_with(placeholder))
+        {
+            Some((placeholder, value)) => {
+                page.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                page.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+    page.push_str(rest);
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::RegistryBuilder;
+    use crate::dependencies::EDependencyCategory;
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xFF, 0xEF]), "/+8=");
+    }
+
+    #[test]
+    fn test_fill() {
+        let values = [("{{A}}", "{{B}}"), ("{{B}}", "b")];
+        assert_eq!(
+            fill("{{A}} {{B}} {{C}} {{A}}{", &values),
+            "{{B}} b {{C}} {{B}}{"
+        );
+    }
+
+    #[test]
+    fn test_html() {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/A.A", "Blueprint")
+            .tag("Note", "</script><b>");
+        builder.add_asset("/Game/B.B", "Texture2D");
+        builder.add_dependency(
+            "/Game/A",
+            "/Game/B",
+            EDependencyCategory::Package,
+            EDependencyProperty::HARD | EDependencyProperty::GAME,
+        );
+        let registry = builder.build().unwrap();
+        let mut data = vec![];
+        registry.write(&mut Cursor::new(&mut data)).unwrap();
+
+        let mut out = vec![];
+        write_html(&mut out, "<A>.bin", &data).unwrap();
+        let page = String::from_utf8(out).unwrap();
+
+        assert!(page.contains("<title>&lt;A&gt;.bin</title>"));
+        assert!(page.contains("<h1>&lt;A&gt;.bin</h1>"));
+        // Embedded data can't close the script element.
+        assert_eq!(
+            page.matches("</script>").count(),
+            VIEWER.matches("</script>").count()
+        );
+        assert!(page.contains(
+            r#"{"l":"Note = \u003c/script>\u003cb>","p":"store.numberless_pairs[0]","r":["#
+        ));
+        assert!(page.contains(r#"{"l":"/Game/B [hard, game]"}"#));
+        assert!(page.contains(r#"{"l":"Assets (2)","p":"assets","r":["#));
+        assert!(page.contains(&format!("\"bytes\":\"{}\"", base64(&data))));
+
+        // A title that looks like a placeholder isn't substituted.
+        let mut out = vec![];
+        write_html(&mut out, "{{DATA}}", &data).unwrap();
+        let page = String::from_utf8(out).unwrap();
+        assert!(page.contains("<title>{{DATA}}</title>"));
+        assert_eq!(page.matches("\"bytes\":").count(), 1);
+    }
+}
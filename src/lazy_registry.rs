@@ -0,0 +1,795 @@
+//! Index-first access to a serialized registry: opening one only records where its sections and
+//! tables are, and names, assets and tag values are decoded one at a time when asked for.
+//!
+//! Opening walks the name headers, the store's texts and its numbered names, export paths and
+//! pairs (all variable-size, so their offsets can't be computed), but decodes no strings. The
+//! offsets of the assets, and with them the offsets of the sections after them, are computed on
+//! first use.
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::fmt::Write as _;
+use std::ops::Range;
+
+use color_eyre::eyre::{eyre, Report, Result as EResult, WrapErr};
+use tracing::*;
+
+use crate::asset_registry_header::AssetRegistryHeader;
+use crate::asset_registry_ref::{
+    decode_header, decode_numberless_export_path, decode_numberless_pair, decode_utf16,
+    decode_utf8, numberless_name_at, read_asset, u32_at, unpack_string, SliceReader, ANSI, WIDE,
+};
+use crate::asset_registry_version::AssetRegistryVersion;
+use crate::assets::AssetData;
+use crate::read::Readable;
+use crate::store_data::{StoreValue, END_MAGIC, START_MAGIC};
+use crate::unreal_types::*;
+
+/// A serialized registry, decoded on demand.
+pub struct LazyRegistry<'a> {
+    data: &'a [u8],
+    pub version: AssetRegistryVersion,
+    names: NamesIndex,
+    store: StoreIndex,
+    /// Offset of the asset count.
+    assets: usize,
+    asset_count: u32,
+    /// Offset of every asset, followed by the offset of the dependency section.
+    asset_offsets: OnceCell<Vec<usize>>,
+}
+
+/// A top-level section of the file.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Section {
+    pub name: &'static str,
+    pub range: Range<usize>,
+    /// What the section holds, e.g. `571 names`.
+    pub contents: String,
+}
+
+/// A run of `count` elements of `size` bytes.
+#[derive(Debug, Copy, Clone)]
+struct Table {
+    offset: usize,
+    count: u32,
+    size: usize,
+}
+
+impl Table {
+    fn read(reader: &mut SliceReader<'_>, count: u32, size: usize) -> EResult<Table> {
+        let offset = reader.offset();
+        let len = (count as usize)
+            .checked_mul(size)
+            .ok_or_else(|| eyre!("array of {} elements of {} bytes is too large", count, size))?;
+        reader.take(len)?;
+        Ok(Table {
+            offset,
+            count,
+            size,
+        })
+    }
+
+    fn get<'a>(&self, data: &'a [u8], i: u32) -> Option<&'a [u8]> {
+        let at = self.offset + i as usize * self.size;
+        (i < self.count).then(|| &data[at..at + self.size])
+    }
+}
+
+struct NamesIndex {
+    range: Range<usize>,
+    headers: Table,
+    /// Offset of every string, followed by the end of the last one.
+    strings: Vec<usize>,
+}
+
+impl NamesIndex {
+    fn read(reader: &mut SliceReader<'_>) -> EResult<Self> {
+        let start = reader.offset();
+        let count = reader.u32()?;
+        let string_bytes = reader.u32()?;
+        let _hash_version = reader.u64()?;
+        let _hashes = Table::read(reader, count, 8)?;
+        let headers = Table {
+            offset: reader.offset(),
+            count,
+            size: 2,
+        };
+        let (header_bytes, _) = reader.take(count as usize * 2)?.as_chunks::<2>();
+
+        let mut strings = Vec::with_capacity(count as usize + 1);
+        let mut offset = reader.offset();
+        strings.push(offset);
+        for header in header_bytes {
+            offset += decode_header(header).n_bytes() as usize;
+            strings.push(offset);
+        }
+        if offset - reader.offset() > string_bytes as usize {
+            return Err(eyre!(
+                "we got more string bytes than is expected from the NamesBatch header, what?"
+            ));
+        }
+        reader.take(offset - reader.offset())?;
+        Ok(NamesIndex {
+            range: start..reader.offset(),
+            headers,
+            strings,
+        })
+    }
+}
+
+struct StoreIndex {
+    range: Range<usize>,
+    /// Offset of every text.
+    texts: Vec<usize>,
+    numberless_names: Table,
+    /// Offset of every numbered name.
+    names: Vec<usize>,
+    numberless_export_paths: Table,
+    /// Offset of every numbered export path.
+    export_paths: Vec<usize>,
+    ansi_offsets: Table,
+    wide_offsets: Table,
+    ansi_strings: Range<usize>,
+    wide_strings: Range<usize>,
+    numberless_pairs: Table,
+    /// Offset of every numbered pair.
+    pairs: Vec<usize>,
+}
+
+/// Walk `count` variable-size elements with `skip`, returning the offset of each.
+fn element_offsets(
+    reader: &mut SliceReader<'_>,
+    count: u32,
+    skip: fn(&mut SliceReader<'_>) -> EResult<()>,
+) -> EResult<Vec<usize>> {
+    let mut offsets = Vec::with_capacity((count as usize).min(reader.rest.len() / 4));
+    for _ in 0..count {
+        offsets.push(reader.offset());
+        skip(reader)?;
+    }
+    Ok(offsets)
+}
+
+impl StoreIndex {
+    fn read(reader: &mut SliceReader<'_>) -> EResult<Self> {
+        let start = reader.offset();
+        let start_magic = reader.u32()?;
+        if start_magic != START_MAGIC {
+            return Err(eyre!(
+                "store data start magic mismatch: expected {:X} but found {:X}",
+                START_MAGIC,
+                start_magic,
+            ));
+        }
+
+        let numberless_names_count = reader.u32()?;
+        let names_count = reader.u32()?;
+        let numberless_export_paths_count = reader.u32()?;
+        let export_paths_count = reader.u32()?;
+        let text_data_count = reader.u32()?;
+        let ansi_string_offsets_count = reader.u32()?;
+        let wide_string_offsets_count = reader.u32()?;
+        let ansi_string_bytes = reader.u32()?;
+        let wide_string_units = reader.u32()?;
+        let numberless_pairs_count = reader.u32()?;
+        let pairs_count = reader.u32()?;
+
+        let text_data_bytes = reader.u32()?;
+        let texts_start = reader.offset();
+        let texts = element_offsets(reader, text_data_count, |reader| {
+            let len = reader.u32()?;
+            reader.take(len as usize)?;
+            Ok(())
+        })?;
+        if reader.offset() - texts_start != text_data_bytes as usize {
+            return Err(eyre!(
+                "texts take {} bytes but the store claims {}",
+                reader.offset() - texts_start,
+                text_data_bytes
+            ));
+        }
+        let numberless_names = Table::read(reader, numberless_names_count, 4)?;
+        let names = element_offsets(reader, names_count, |reader| {
+            reader.fname()?;
+            Ok(())
+        })?;
+        let numberless_export_paths = Table::read(reader, numberless_export_paths_count, 12)?;
+        let export_paths = element_offsets(reader, export_paths_count, |reader| {
+            for _ in 0..3 {
+                reader.fname()?;
+            }
+            Ok(())
+        })?;
+        let ansi_offsets = Table::read(reader, ansi_string_offsets_count, 4)?;
+        let wide_offsets = Table::read(reader, wide_string_offsets_count, 4)?;
+        let ansi_start = reader.offset();
+        reader.take(ansi_string_bytes as usize)?;
+        let wide_start = reader.offset();
+        reader.take(2 * wide_string_units as usize)?;
+        let numberless_pairs = Table::read(reader, numberless_pairs_count, 8)?;
+        let pairs = element_offsets(reader, pairs_count, |reader| {
+            reader.fname()?;
+            reader.u32()?;
+            Ok(())
+        })?;
+
+        let end_magic = reader.u32()?;
+        if end_magic != END_MAGIC {
+            return Err(eyre!(
+                "store data end magic mismatch: expected {:X} but found {:X}",
+                END_MAGIC,
+                end_magic,
+            ));
+        }
+
+        Ok(StoreIndex {
+            range: start..reader.offset(),
+            texts,
+            numberless_names,
+            names,
+            numberless_export_paths,
+            export_paths,
+            ansi_offsets,
+            wide_offsets,
+            ansi_strings: ansi_start..wide_start,
+            wide_strings: wide_start..numberless_pairs.offset,
+            numberless_pairs,
+            pairs,
+        })
+    }
+}
+
+/// Skip over an `AssetData` without decoding it.
+fn skip_asset(reader: &mut SliceReader<'_>) -> EResult<()> {
+    for _ in 0..5 {
+        reader.fname()?;
+    }
+    // The tag map handle.
+    reader.take(8)?;
+    let bundle_count = reader.u32()?;
+    for _ in 0..bundle_count {
+        reader.fname()?;
+        let path_count = reader.u32()?;
+        for _ in 0..path_count {
+            // The path name and the sub-path `FString`, whose length includes its NUL.
+            reader.fname()?;
+            let len = reader.u32()? as i32;
+            if len == 0 {
+                return Err(eyre!("FString length cannot be 0"));
+            }
+            reader.take(len.unsigned_abs() as usize)?;
+        }
+    }
+    let chunk_count = reader.u32()? as i32;
+    if chunk_count < 0 {
+        return Err(eyre!("negative count {}", chunk_count));
+    }
+    // The chunk IDs and the package flags.
+    reader.take(4 * chunk_count as usize + 4)?;
+    Ok(())
+}
+
+impl<'a> LazyRegistry<'a> {
+    /// Index the registry in `data`, checking the header and the store's magics.
+    #[instrument(name = "LazyRegistry_open", skip_all)]
+    pub fn open(data: &'a [u8]) -> EResult<Self> {
+        let mut reader = SliceReader::at(data, 0)?;
+        let header: AssetRegistryHeader = reader.read()?;
+        // Like every other read of the registry's structures, scoped to the file's version.
+        header.version.scope(|| {
+            let names = NamesIndex::read(&mut reader)?;
+            let store = StoreIndex::read(&mut reader)?;
+            let assets = reader.offset();
+            let asset_count = reader.u32()?;
+            Ok(LazyRegistry {
+                data,
+                version: header.version,
+                names,
+                store,
+                assets,
+                asset_count,
+                asset_offsets: OnceCell::new(),
+            })
+        })
+    }
+
+    pub fn name_count(&self) -> usize {
+        self.names.headers.count as usize
+    }
+
+    /// Decode the name at `index`, without its number suffix.
+    pub fn name(&self, index: u32) -> EResult<Cow<'a, str>> {
+        let header = self
+            .names
+            .headers
+            .get(self.data, index)
+            .map(|b| decode_header(b.try_into().unwrap()))
+            .ok_or_else(|| {
+                eyre!(
+                    "FName index {} out of bounds of {} names",
+                    index,
+                    self.name_count()
+                )
+            })?;
+        if header.len == 0 {
+            return Err(eyre!("got unexpected zero-length name"));
+        }
+        let bytes =
+            &self.data[self.names.strings[index as usize]..self.names.strings[index as usize + 1]];
+        match header.is_utf16 {
+            true => decode_utf16(bytes),
+            false => decode_utf8(bytes),
+        }
+        .wrap_err_with(|| "failed to build a UTF-8 string from NamesBatch string")
+    }
+
+    /// Resolve an [`FName`] to its display string, see
+    /// [`NamesBatch::resolve`](crate::names_batch::NamesBatch::resolve).
+    pub fn resolve(&self, name: FName) -> EResult<Cow<'a, str>> {
+        let base = self.name(name.index)?;
+        Ok(match name.number {
+            0 => base,
+            number => Cow::Owned(format!("{base}_{}", number - 1)),
+        })
+    }
+
+    pub fn asset_count(&self) -> usize {
+        self.asset_count as usize
+    }
+
+    /// The offsets of the assets and of the section after them, walking the assets on first use.
+    fn asset_offsets(&self) -> EResult<&[usize]> {
+        if let Some(offsets) = self.asset_offsets.get() {
+            return Ok(offsets);
+        }
+        let mut reader = SliceReader::at(self.data, self.assets + 4)?;
+        let mut offsets =
+            Vec::with_capacity((self.asset_count as usize).min(self.data.len() / 40) + 1);
+        self.version.scope(|| -> EResult<()> {
+            for _ in 0..self.asset_count {
+                offsets.push(reader.offset());
+                skip_asset(&mut reader)?;
+            }
+            Ok(())
+        })?;
+        offsets.push(reader.offset());
+        Ok(self.asset_offsets.get_or_init(|| offsets))
+    }
+
+    pub fn asset(&self, i: usize) -> EResult<AssetData> {
+        if i >= self.asset_count() {
+            return Err(eyre!(
+                "asset {} out of bounds of {} assets",
+                i,
+                self.asset_count
+            ));
+        }
+        let mut reader = SliceReader::at(self.data, self.asset_offsets()?[i])?;
+        self.version.scope(|| read_asset(&mut reader))
+    }
+
+    /// Indices of the assets whose object path contains `pattern`, ignoring ASCII case. Only the
+    /// object paths are decoded.
+    pub fn find(&self, pattern: &str) -> EResult<Vec<usize>> {
+        let pattern = pattern.to_ascii_lowercase();
+        let offsets = self.asset_offsets()?;
+        let mut found = vec![];
+        for (i, offset) in offsets[..self.asset_count()].iter().enumerate() {
+            let object_path = self.resolve(SliceReader::at(self.data, *offset)?.fname()?)?;
+            if object_path.to_ascii_lowercase().contains(&pattern) {
+                found.push(i);
+            }
+        }
+        Ok(found)
+    }
+
+    /// The key-value pairs that an `AssetData::tags` handle points at.
+    pub fn pairs(&self, handle: FPartialMapHandle) -> EResult<Vec<FNumberedPair>> {
+        let out_of_bounds = |count| {
+            eyre!(
+                "tag map handle {:?} out of bounds of {} pairs",
+                handle,
+                count
+            )
+        };
+        let numberless_pairs = self.store.numberless_pairs;
+        handle
+            .pair_range()
+            .map(|i| match handle.has_numberless_keys {
+                true => numberless_pairs
+                    .get(self.data, i as u32)
+                    .map(|bytes| decode_numberless_pair(bytes.try_into().unwrap()))
+                    .ok_or_else(|| out_of_bounds(numberless_pairs.count as usize)),
+                false => self.element(&self.store.pairs, i, out_of_bounds),
+            })
+            .collect()
+    }
+
+    /// The tags of an asset as resolved key-value strings, in stored order.
+    pub fn tags(&self, asset: &AssetData) -> EResult<Vec<(String, String)>> {
+        self.pairs(FPartialMapHandle::from_int(asset.tags))?
+            .into_iter()
+            .map(|pair| {
+                let value = self.value(FValueId::from_int(pair.value)?)?;
+                let value = value.resolve_with(|name| Some(self.resolve(name).ok()?.into_owned()));
+                Ok((self.resolve(pair.key)?.into_owned(), value))
+            })
+            .collect()
+    }
+
+    /// Decode the `i`th element of a variable-size array with the given element offsets.
+    fn element<T: Readable<&'a [u8]>>(
+        &self,
+        offsets: &[usize],
+        i: usize,
+        out_of_bounds: impl FnOnce(usize) -> Report,
+    ) -> EResult<T> {
+        let offset = *offsets.get(i).ok_or_else(|| out_of_bounds(offsets.len()))?;
+        self.version
+            .scope(|| SliceReader::at(self.data, offset)?.read())
+    }
+
+    fn string(&self, id: FValueId) -> EResult<String> {
+        let (offsets, blob, kind) = match id.ty {
+            EValueType::AnsiString => (
+                self.store.ansi_offsets,
+                &self.data[self.store.ansi_strings.clone()],
+                &ANSI,
+            ),
+            _ => (
+                self.store.wide_offsets,
+                &self.data[self.store.wide_strings.clone()],
+                &WIDE,
+            ),
+        };
+        let offset = |i| offsets.get(self.data, i).map(|b| u32_at(b, 0));
+        let start = offset(id.index).ok_or_else(|| {
+            eyre!(
+                "value id {:?} out of bounds of {} values",
+                id,
+                offsets.count
+            )
+        })?;
+        let end = offset(id.index + 1).unwrap_or(kind.units(blob));
+        Ok(unpack_string(blob, start..end, kind, id.index as usize)?.into_owned())
+    }
+
+    /// Decode a single tag value.
+    pub fn value(&self, id: FValueId) -> EResult<StoreValue> {
+        let out_of_bounds =
+            |count: usize| eyre!("value id {:?} out of bounds of {} values", id, count);
+        let get = |table: Table| {
+            table
+                .get(self.data, id.index)
+                .ok_or_else(|| out_of_bounds(table.count as usize))
+        };
+        let value = match id.ty {
+            EValueType::AnsiString => StoreValue::AnsiString(self.string(id)?),
+            EValueType::WideString => StoreValue::WideString(self.string(id)?),
+            EValueType::NumberlessName => {
+                StoreValue::NumberlessName(numberless_name_at(get(self.store.numberless_names)?, 0))
+            }
+            EValueType::Name => StoreValue::Name(self.element(
+                &self.store.names,
+                id.index as usize,
+                out_of_bounds,
+            )?),
+            EValueType::NumberlessExportPath => {
+                let bytes = get(self.store.numberless_export_paths)?;
+                StoreValue::NumberlessExportPath(decode_numberless_export_path(
+                    bytes.try_into().unwrap(),
+                ))
+            }
+            EValueType::ExportPath => StoreValue::ExportPath(self.element(
+                &self.store.export_paths,
+                id.index as usize,
+                out_of_bounds,
+            )?),
+            EValueType::LocalizedText => {
+                let offset = self
+                    .store
+                    .texts
+                    .get(id.index as usize)
+                    .ok_or_else(|| out_of_bounds(self.store.texts.len()))?;
+                StoreValue::LocalizedText(
+                    self.version
+                        .scope(|| FText::read(&mut &self.data[*offset..]))?,
+                )
+            }
+        };
+        Ok(value)
+    }
+
+    fn dependencies_offset(&self) -> EResult<usize> {
+        Ok(*self.asset_offsets()?.last().unwrap())
+    }
+
+    fn package_data_offset(&self) -> EResult<usize> {
+        let offset = self.dependencies_offset()?;
+        let size = SliceReader::at(self.data, offset)?.u64()? as i64;
+        usize::try_from(size)
+            .ok()
+            .and_then(|size| (offset + 8).checked_add(size))
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| eyre!("dependency section size {} is out of bounds", size))
+    }
+
+    /// The top-level sections with their byte ranges. Only the element counts are read.
+    pub fn sections(&self) -> EResult<Vec<Section>> {
+        let store = &self.store;
+        let mut store_contents = String::new();
+        for (i, (what, count)) in [
+            ("texts", store.texts.len()),
+            ("numberless names", store.numberless_names.count as usize),
+            ("names", store.names.len()),
+            (
+                "numberless export paths",
+                store.numberless_export_paths.count as usize,
+            ),
+            ("export paths", store.export_paths.len()),
+            ("ANSI strings", store.ansi_offsets.count as usize),
+            ("wide strings", store.wide_offsets.count as usize),
+            ("numberless pairs", store.numberless_pairs.count as usize),
+            ("pairs", store.pairs.len()),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if i > 0 {
+                store_contents.push_str(", ");
+            }
+            write!(store_contents, "{count} {what}").unwrap();
+        }
+
+        let dependencies = self.dependencies_offset()?;
+        let package_data = self.package_data_offset()?;
+        let node_count = SliceReader::at(self.data, dependencies + 8)?.u32()? as i32;
+        let mut reader = SliceReader::at(self.data, package_data)?;
+        let package_count = reader.u32()?;
+        for _ in 0..package_count {
+            // The cooked hash follows its validity flag, after the name, disk size and GUID.
+            reader.fname()?;
+            if u32_at(reader.bytes::<28>()?, 24) != 0 {
+                reader.take(16)?;
+            }
+        }
+
+        Ok(vec![
+            Section {
+                name: "header",
+                range: 0..self.names.range.start,
+                contents: format!("version {:?}", self.version),
+            },
+            Section {
+                name: "names",
+                range: self.names.range.clone(),
+                contents: format!("{} names", self.name_count()),
+            },
+            Section {
+                name: "store",
+                range: store.range.clone(),
+                contents: store_contents,
+            },
+            Section {
+                name: "assets",
+                range: self.assets..dependencies,
+                contents: format!("{} assets", self.asset_count),
+            },
+            Section {
+                name: "dependencies",
+                range: dependencies..package_data,
+                contents: format!("{node_count} nodes"),
+            },
+            Section {
+                name: "package_data",
+                range: package_data..reader.offset(),
+                contents: format!("{package_count} packages"),
+            },
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset_registry::AssetRegistry;
+    use crate::assets::{FAssetBundleEntry, FSoftObjectPath};
+    use crate::builder::{RegistryBuilder, TagValue};
+    use crate::dependencies::{EDependencyCategory, EDependencyProperty};
+    use crate::write::{SerializedSize, Writable};
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn bytes() -> Vec<u8> {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/Maps/Arena.Arena", "World")
+            .tag("Size", "big")
+            .tag("Label", TagValue::Text("Arena".to_string()))
+            .tag("Parent", TagValue::Name("Level_2".to_string()))
+            .bundle("Client", &["/Game/UI/Icon.Icon"]);
+        builder
+            .add_asset("/Game/UI/Icon.Icon", "Texture2D")
+            .tag("Size", "small")
+            .tag(
+                "Owner",
+                TagValue::ExportPath {
+                    class: "World".to_string(),
+                    object_path: "/Game/Maps/Arena.Arena".to_string(),
+                },
+            );
+        builder.add_asset("/Game/Maps/Arena_Lighting.Arena_Lighting", "World");
+        builder.add_dependency(
+            "/Game/Maps/Arena",
+            "/Game/UI/Icon",
+            EDependencyCategory::Package,
+            EDependencyProperty::HARD | EDependencyProperty::GAME,
+        );
+        builder.add_package_data("/Game/Maps/Arena", 100);
+        builder
+            .add_package_data("/Game/UI/Icon", 20)
+            .cooked_hash([7; 16]);
+        let mut buf = vec![];
+        builder
+            .build()
+            .unwrap()
+            .write(&mut Cursor::new(&mut buf))
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_matches_read() {
+        let buf = bytes();
+        let strict = AssetRegistry::read(&mut Cursor::new(&buf)).unwrap();
+        let lazy = LazyRegistry::open(&buf).unwrap();
+
+        assert_eq!(lazy.version, AssetRegistryVersion::LATEST_VERSION);
+        let names = (0..lazy.name_count() as u32)
+            .map(|i| lazy.name(i).unwrap().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, strict.names.strings);
+        let assets = (0..lazy.asset_count())
+            .map(|i| lazy.asset(i))
+            .collect::<EResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(assets, strict.assets.assets);
+        for asset in &assets {
+            let handle = FPartialMapHandle::from_int(asset.tags);
+            let pairs = lazy.pairs(handle).unwrap();
+            assert_eq!(pairs, strict.store.pairs_for(handle).unwrap());
+            for pair in pairs {
+                let id = FValueId::from_int(pair.value).unwrap();
+                assert_eq!(lazy.value(id).unwrap(), strict.store.value(id).unwrap());
+            }
+        }
+        let tags = lazy.tags(&assets[0]).unwrap();
+        assert_eq!(
+            tags,
+            strict
+                .store
+                .pairs_for(FPartialMapHandle::from_int(assets[0].tags))
+                .unwrap()
+                .iter()
+                .map(|pair| {
+                    let value = strict.store.value(FValueId::from_int(pair.value).unwrap());
+                    (
+                        strict.names.try_resolve(pair.key).unwrap(),
+                        value.unwrap().resolve(&strict.names),
+                    )
+                })
+                .collect::<Vec<_>>()
+        );
+        assert!(!tags.is_empty());
+    }
+
+    #[test]
+    fn test_sections() {
+        let buf = bytes();
+        let lazy = LazyRegistry::open(&buf).unwrap();
+        let sections = lazy.sections().unwrap();
+        assert_eq!(sections[0].range, 0..20);
+        assert!(sections
+            .windows(2)
+            .all(|w| w[0].range.end == w[1].range.start));
+        assert_eq!(sections.last().unwrap().range.end, buf.len());
+        assert_eq!(
+            sections
+                .iter()
+                .map(|s| (s.name, s.contents.as_str()))
+                .skip(3)
+                .collect::<Vec<_>>(),
+            vec![
+                ("assets", "3 assets"),
+                ("dependencies", "3 nodes"),
+                ("package_data", "2 packages")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_minimal() {
+        let buf = include_bytes!("../test_assets/minimal.bin");
+        let strict = AssetRegistry::read(&mut Cursor::new(buf)).unwrap();
+        let lazy = LazyRegistry::open(buf).unwrap();
+        let assets = (0..lazy.asset_count())
+            .map(|i| lazy.asset(i))
+            .collect::<EResult<Vec<_>>>()
+            .unwrap();
+        assert_eq!(assets, strict.assets.assets);
+        let sections = lazy.sections().unwrap();
+        assert_eq!(sections.last().unwrap().range.end, buf.len());
+    }
+
+    #[test]
+    fn test_find() {
+        let buf = bytes();
+        let lazy = LazyRegistry::open(&buf).unwrap();
+        assert_eq!(lazy.find("maps/arena").unwrap(), vec![0, 2]);
+        assert_eq!(lazy.find("ICON").unwrap(), vec![1]);
+        assert_eq!(lazy.find("Missing").unwrap(), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_lazy_errors() {
+        let buf = bytes();
+        let lazy = LazyRegistry::open(&buf).unwrap();
+        assert_eq!(
+            lazy.name(10_000).unwrap_err().to_string(),
+            format!(
+                "FName index 10000 out of bounds of {} names",
+                lazy.name_count()
+            )
+        );
+        assert!(lazy.asset(3).is_err());
+
+        // Sections after the store are only read when they're accessed.
+        let sections = lazy.sections().unwrap();
+        let truncated = &buf[..sections[3].range.start + 10];
+        let lazy = LazyRegistry::open(truncated).unwrap();
+        assert_eq!(lazy.asset_count(), 3);
+        assert_eq!(
+            lazy.name(0).unwrap(),
+            LazyRegistry::open(&buf).unwrap().name(0).unwrap()
+        );
+        assert!(lazy.asset(0).is_err());
+    }
+
+    #[test]
+    fn test_skip_asset() {
+        let path = |sub_path: &str| FSoftObjectPath {
+            asset_path_name: FName::default(),
+            sub_path_string: FString::from(sub_path),
+        };
+        let asset = |bundles: Vec<FSoftObjectPath>, chunk_ids: Vec<i32>| AssetData {
+            object_path: FName::default(),
+            package_path: FName::default(),
+            asset_class: FName::default(),
+            package_name: FName::default(),
+            asset_name: FName::default(),
+            tags: 0,
+            bundles: match bundles.is_empty() {
+                true => vec![],
+                false => vec![FAssetBundleEntry {
+                    bundle_name: FName::default(),
+                    bundles,
+                }],
+            },
+            chunk_ids,
+            package_flags: 0,
+        };
+        for asset in [
+            asset(vec![], vec![]),
+            asset(vec![path(""), path("Sub")], vec![0, 7]),
+            asset(vec![path("Ünter")], vec![3]),
+        ] {
+            let mut buf = vec![];
+            asset.write(&mut Cursor::new(&mut buf)).unwrap();
+            let mut reader = SliceReader::at(&buf, 0).unwrap();
+            skip_asset(&mut reader).unwrap();
+            assert_eq!(reader.offset() as u64, asset.serialized_size());
+            assert_eq!(reader.offset(), buf.len());
+        }
+    }
+}
@@ -0,0 +1,573 @@
+//! Lenient parsing of damaged or game-modified registries.
+//!
+//! The strict [`Readable`] impls abort on the first problem. [`AssetRegistry::read_lenient`]
+//! instead records a [`Diagnostic`] and carries on wherever the format allows it: bad strings are
+//! decoded lossily, a broken store is skipped up to its end magic, a broken dependency section is
+//! skipped using its size prefix, and truncated sections keep whatever was parsed before the
+//! problem.
+
+use std::io::Read;
+
+use byteorder::{ReadBytesExt, LE};
+use color_eyre::eyre::{eyre, Report, Result as EResult};
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::asset_registry_header::AssetRegistryHeader;
+use crate::asset_registry_version::AssetRegistryVersion;
+use crate::assets::{AssetData, FAssetPackageData};
+use crate::coverage::CoveredRange;
+use crate::dependencies::FDependsNode;
+use crate::names_batch::NamesBatch;
+use crate::read::{Positioned, Readable};
+use crate::spans::{self, Tracked};
+use crate::store_data::{StoreData, END_MAGIC, START_MAGIC};
+use crate::validate::Severity;
+
+/// A problem found while parsing.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Offset of the offending structure in the file.
+    pub offset: u64,
+    /// Path of the offending structure, e.g. `names.strings[12]`.
+    pub location: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{} at {:#010X}: {}: {}",
+            severity, self.offset, self.location, self.message
+        )
+    }
+}
+
+/// Decides what happens to problems a reader can work around: strict readers fail, lenient ones
+/// record a diagnostic and continue.
+#[derive(Debug, Default)]
+pub(crate) struct Recovery {
+    lenient: bool,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+impl Recovery {
+    pub(crate) fn strict() -> Self {
+        Recovery::default()
+    }
+
+    pub(crate) fn lenient() -> Self {
+        Recovery {
+            lenient: true,
+            diagnostics: vec![],
+        }
+    }
+
+    /// Fail with `error` when strict, otherwise record it. Callers continue with a best-effort
+    /// value when this returns `Ok`.
+    pub(crate) fn recover(
+        &mut self,
+        offset: u64,
+        location: impl FnOnce() -> String,
+        error: Report,
+    ) -> EResult<()> {
+        if !self.lenient {
+            return Err(error);
+        }
+        self.report(Severity::Error, offset, location(), error.to_string());
+        Ok(())
+    }
+
+    pub(crate) fn report(
+        &mut self,
+        severity: Severity,
+        offset: u64,
+        location: String,
+        message: String,
+    ) {
+        warn!(offset, %location, %message);
+        self.diagnostics.push(Diagnostic {
+            severity,
+            offset,
+            location,
+            message,
+        });
+    }
+}
+
+impl AssetRegistry {
+    /// Parse as much of `data` as possible, returning the (possibly partial) registry together
+    /// with every problem encountered. Never fails; a registry that can't be parsed at all is
+    /// returned empty.
+    #[instrument(name = "AssetRegistry_read_lenient", skip_all)]
+    pub fn read_lenient(data: &[u8]) -> (AssetRegistry, Vec<Diagnostic>) {
+        let reader = LenientReader::read(data);
+        debug!(diagnostics = reader.recovery.diagnostics.len());
+        (reader.registry, reader.recovery.diagnostics)
+    }
+}
+
+/// Size of the GUID and version preceding the name table.
+const HEADER_SIZE: u64 = 20;
+
+pub(crate) struct LenientReader<'a> {
+    data: &'a [u8],
+    cursor: Tracked<'a>,
+    pub(crate) recovery: Recovery,
+    pub(crate) registry: AssetRegistry,
+    /// The ranges consumed so far, in file order.
+    pub(crate) coverage: Vec<CoveredRange>,
+}
+
+impl<'a> LenientReader<'a> {
+    pub(crate) fn read(data: &'a [u8]) -> Self {
+        let mut reader = LenientReader {
+            data,
+            cursor: Tracked::new(data),
+            recovery: Recovery::lenient(),
+            registry: AssetRegistry::default(),
+            coverage: vec![],
+        };
+        if reader.read_sections() {
+            reader.trailing_bytes();
+        }
+        reader
+    }
+
+    /// Record that the bytes from `start` up to the current position belong to `structure`.
+    fn cover(&mut self, start: u64, structure: impl Into<String>) {
+        self.coverage.push(CoveredRange {
+            range: start..self.cursor.position(),
+            structure: structure.into(),
+        });
+    }
+
+    fn trailing_bytes(&mut self) {
+        let position = self.cursor.position();
+        let len = self.data.len() as u64;
+        if position < len {
+            self.recovery.report(
+                Severity::Warning,
+                position,
+                "trailing".to_string(),
+                format!("{} trailing bytes after the package data", len - position),
+            );
+        }
+    }
+
+    fn error(&mut self, offset: u64, location: &str, error: impl std::fmt::Display) {
+        self.recovery.report(
+            Severity::Error,
+            offset,
+            location.to_string(),
+            error.to_string(),
+        );
+    }
+
+    /// Offset just past the next occurrence of `magic` at or after `from`.
+    fn find_after(&self, from: u64, magic: u32) -> Option<u64> {
+        let from = (from as usize).min(self.data.len());
+        self.data[from..]
+            .windows(4)
+            .position(|w| w == magic.to_le_bytes())
+            .map(|i| (from + i + 4) as u64)
+    }
+
+    /// Read every section, returning whether the end of the package data was reached. Without a
+    /// readable header, the sections are read as the latest version.
+    fn read_sections(&mut self) -> bool {
+        let version = match spans::field("header", || AssetRegistryHeader::read(&mut self.cursor)) {
+            Ok(header) => {
+                self.cover(0, "header");
+                header.version
+            }
+            Err(e) => {
+                self.error(0, "header", e);
+                AssetRegistryVersion::LATEST_VERSION
+            }
+        };
+        self.cursor.set_position(HEADER_SIZE);
+        version.scope(|| self.read_body())
+    }
+
+    /// Read the sections after the header.
+    fn read_body(&mut self) -> bool {
+        let names_offset = self.cursor.position();
+        let names = spans::field("names", || {
+            NamesBatch::read_with(
+                &mut Positioned::new(&mut self.cursor, names_offset),
+                &mut self.recovery,
+            )
+        });
+        match names {
+            Ok(names) => {
+                self.registry.names = names;
+                self.cover(names_offset, "names");
+            }
+            Err(e) => self.error(names_offset, "names", e),
+        }
+        // The store starts right after the names. If a damaged name table left us somewhere else,
+        // look for the store's magic instead.
+        let position = self.cursor.position();
+        let at_store = self.data.get(position as usize..position as usize + 4)
+            == Some(&START_MAGIC.to_le_bytes()[..]);
+        if !at_store {
+            let Some(store_offset) = self.find_after(names_offset, START_MAGIC) else {
+                self.error(position, "store", "no store start magic found");
+                return false;
+            };
+            self.recovery.report(
+                Severity::Warning,
+                position,
+                "names".to_string(),
+                format!(
+                    "resuming at the store start magic at {:#X}",
+                    store_offset - 4
+                ),
+            );
+            self.cursor.set_position(store_offset - 4);
+        }
+
+        let store_offset = self.cursor.position();
+        let store = spans::field("store", || {
+            StoreData::read_with(
+                &mut Positioned::new(&mut self.cursor, store_offset),
+                &mut self.recovery,
+            )
+        });
+        match store {
+            Ok(store) => {
+                self.registry.store = store;
+                self.cover(store_offset, "store");
+            }
+            Err(e) => {
+                self.error(store_offset, "store", e);
+                let Some(end) = self.find_after(store_offset + 4, END_MAGIC) else {
+                    return false;
+                };
+                self.recovery.report(
+                    Severity::Warning,
+                    store_offset,
+                    "store".to_string(),
+                    format!("skipped the store up to its end magic at {:#X}", end - 4),
+                );
+                self.cursor.set_position(end);
+            }
+        }
+
+        self.assets() && self.dependencies() && self.package_data()
+    }
+
+    /// Read the `u32`-prefixed array `section.field` one element at a time, keeping the elements
+    /// read before a failure. Returns whether the whole array was read.
+    fn array<T>(
+        &mut self,
+        section: &'static str,
+        field: &'static str,
+        read: fn(&mut Tracked<'a>) -> EResult<T>,
+        items: &mut Vec<T>,
+    ) -> bool {
+        let _section = spans::enter(section);
+        let offset = self.cursor.position();
+        let count = match spans::field("count", || self.cursor.read_u32::<LE>()) {
+            Ok(count) => count,
+            Err(e) => {
+                self.error(offset, section, e);
+                return false;
+            }
+        };
+        self.cover(offset, format!("{section}.count"));
+        let _field = spans::enter(field);
+        for i in 0..count {
+            let offset = self.cursor.position();
+            match spans::index(i as u64, || read(&mut self.cursor)) {
+                Ok(item) => {
+                    items.push(item);
+                    self.cover(offset, format!("{section}.{field}[{i}]"));
+                }
+                Err(e) => {
+                    self.error(offset, &format!("{section}.{field}[{i}]"), e);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn assets(&mut self) -> bool {
+        let mut assets = vec![];
+        let complete = self.array("assets", "assets", AssetData::read, &mut assets);
+        self.registry.assets.assets = assets;
+        complete
+    }
+
+    /// The dependency section is size-prefixed, so parsing can resume after it even if its
+    /// content is broken.
+    fn dependencies(&mut self) -> bool {
+        let _section = spans::enter("dependencies");
+        let offset = self.cursor.position();
+        let size = match spans::field("size", || self.cursor.read_i64::<LE>()) {
+            Ok(size) => size,
+            Err(e) => {
+                self.error(offset, "dependencies", e);
+                return false;
+            }
+        };
+        self.cover(offset, "dependencies.size");
+        let start = self.cursor.position();
+        let end = u64::try_from(size)
+            .ok()
+            .and_then(|size| start.checked_add(size))
+            .filter(|end| *end <= self.data.len() as u64);
+        let Some(end) = end else {
+            self.error(
+                offset,
+                "dependencies",
+                eyre!("section size {} exceeds the file", size),
+            );
+            return false;
+        };
+
+        let mut section = Tracked::new(&self.data[..end as usize]);
+        section.set_position(start);
+        let mut nodes = vec![];
+        let mut coverage = vec![];
+        let result = (|| -> EResult<()> {
+            let count = spans::field("count", || section.read_i32::<LE>())?;
+            if count < 0 {
+                return Err(eyre!("negative dependency node count {}", count));
+            }
+            coverage.push(CoveredRange {
+                range: start..section.position(),
+                structure: "dependencies.count".to_string(),
+            });
+            let _nodes = spans::enter("nodes");
+            for i in 0..count {
+                let offset = section.position();
+                let node = spans::index(i as u64, || FDependsNode::read(&mut section))
+                    .map_err(|e| eyre!("dependencies.nodes[{}] at {:#X}: {}", i, offset, e))?;
+                nodes.push(node);
+                coverage.push(CoveredRange {
+                    range: offset..section.position(),
+                    structure: format!("dependencies.nodes[{i}]"),
+                });
+            }
+            Ok(())
+        })();
+        self.coverage.append(&mut coverage);
+        if let Err(e) = result {
+            self.error(start, "dependencies", e);
+        } else if section.position() != end {
+            self.recovery.report(
+                Severity::Warning,
+                section.position(),
+                "dependencies".to_string(),
+                format!(
+                    "{} bytes left unparsed in the section",
+                    end - section.position()
+                ),
+            );
+        }
+        self.registry.dependencies.nodes = nodes;
+        self.cursor.set_position(end);
+        true
+    }
+
+    fn package_data(&mut self) -> bool {
+        let mut packages = vec![];
+        let complete = self.array(
+            "package_data",
+            "packages",
+            FAssetPackageData::read,
+            &mut packages,
+        );
+        self.registry.package_data.packages = packages;
+        complete
+    }
+}
+
+/// Decode a string leniently: `decode` in strict mode, `lossy` after recording the problem in
+/// lenient mode.
+pub(crate) fn decode_string(
+    recovery: &mut Recovery,
+    offset: u64,
+    location: impl FnOnce() -> String,
+    decode: impl FnOnce() -> EResult<String>,
+    lossy: impl FnOnce() -> String,
+) -> EResult<String> {
+    match decode() {
+        Ok(s) => Ok(s),
+        Err(e) => {
+            recovery.recover(offset, location, e)?;
+            Ok(lossy())
+        }
+    }
+}
+
+/// Read exactly `len` bytes. `len` comes from the file, so the buffer only grows as bytes
+/// actually arrive instead of being allocated up front.
+pub(crate) fn read_bytes<R: Read>(reader: &mut R, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::RegistryBuilder;
+    use crate::dependencies::{EDependencyCategory, EDependencyProperty};
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/A.A", "Blueprint")
+            .tag("Color", "red");
+        builder.add_asset("/Game/B.B", "Texture2D");
+        builder.add_dependency(
+            "/Game/A",
+            "/Game/B",
+            EDependencyCategory::Package,
+            EDependencyProperty::HARD,
+        );
+        builder.add_package_data("/Game/A", 1);
+        builder.add_package_data("/Game/B", 2);
+        builder.build().unwrap()
+    }
+
+    fn bytes(registry: &AssetRegistry) -> Vec<u8> {
+        let mut buf = vec![];
+        registry.write(&mut Cursor::new(&mut buf)).unwrap();
+        buf
+    }
+
+    fn find(haystack: &[u8], needle: &[u8]) -> usize {
+        haystack
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .unwrap()
+    }
+
+    /// Offset of the dependency section's size prefix.
+    fn dependencies_offset(registry: &AssetRegistry) -> usize {
+        let mut buf = Cursor::new(vec![]);
+        AssetRegistryHeader {
+            version: crate::asset_registry_version::AssetRegistryVersion::LATEST_VERSION,
+        }
+        .write(&mut buf)
+        .unwrap();
+        registry.names.write(&mut buf).unwrap();
+        registry.store.write(&mut buf).unwrap();
+        registry.assets.write(&mut buf).unwrap();
+        buf.get_ref().len()
+    }
+
+    #[test]
+    fn test_valid() {
+        let registry = fixture();
+        let (read, diagnostics) = AssetRegistry::read_lenient(&bytes(&registry));
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(read, registry);
+    }
+
+    #[test]
+    fn test_bad_name() {
+        let registry = fixture();
+        let mut buf = bytes(&registry);
+        let offset = find(&buf, b"Blueprint");
+        buf[offset] = 0xFF;
+
+        assert!(AssetRegistry::read(&mut Cursor::new(&buf)).is_err());
+        let (read, diagnostics) = AssetRegistry::read_lenient(&buf);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].offset, offset as u64);
+        assert!(diagnostics[0].location.starts_with("names.strings["));
+        assert_eq!(read.assets, registry.assets);
+        assert_eq!(read.dependencies, registry.dependencies);
+        assert_eq!(read.package_data, registry.package_data);
+        assert!(read.names.strings.contains(&"\u{FFFD}lueprint".to_string()));
+    }
+
+    #[test]
+    fn test_bad_store_string() {
+        let registry = fixture();
+        let mut buf = bytes(&registry);
+        let offset = find(&buf, b"red\0");
+        buf[offset + 3] = b'!';
+
+        assert!(AssetRegistry::read(&mut Cursor::new(&buf)).is_err());
+        let (read, diagnostics) = AssetRegistry::read_lenient(&buf);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, "store.ansi_strings[0]");
+        assert_eq!(read.store.ansi_strings, vec!["red"]);
+        assert_eq!(read.assets, registry.assets);
+    }
+
+    #[test]
+    fn test_bad_dependencies() {
+        let registry = fixture();
+        let mut buf = bytes(&registry);
+        // Claim more nodes than the section holds.
+        let count = dependencies_offset(&registry) + 8;
+        buf[count..count + 4].copy_from_slice(&1000i32.to_le_bytes());
+
+        let (read, diagnostics) = AssetRegistry::read_lenient(&buf);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, "dependencies");
+        assert_eq!(read.dependencies.nodes, registry.dependencies.nodes);
+        // Parsing resumes after the section.
+        assert_eq!(read.package_data, registry.package_data);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let registry = fixture();
+        let buf = bytes(&registry);
+        let (read, diagnostics) = AssetRegistry::read_lenient(&buf[..buf.len() - 4]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, "package_data.packages[1]");
+        assert_eq!(read.assets, registry.assets);
+        assert_eq!(read.dependencies, registry.dependencies);
+        assert_eq!(
+            read.package_data.packages,
+            registry.package_data.packages[..1]
+        );
+    }
+
+    #[test]
+    fn test_string_length_out_of_range() {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/A.A", "Blueprint")
+            .bundle("Client", &["/Game/B.B:Sub"]);
+        let mut buf = bytes(&builder.build().unwrap());
+        // The length of the bundle entry's sub-path, `Sub` and its NUL.
+        let offset = find(&buf, b"Sub\0") - 4;
+        buf[offset..offset + 4].copy_from_slice(&i32::MIN.to_le_bytes());
+
+        assert!(AssetRegistry::read(&mut Cursor::new(&buf)).is_err());
+        let (_, diagnostics) = AssetRegistry::read_lenient(&buf);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("out of range"));
+    }
+
+    #[test]
+    fn test_read_bytes() {
+        assert_eq!(read_bytes(&mut &b"abc"[..], 2).unwrap(), b"ab");
+        let error = read_bytes(&mut &b"abc"[..], usize::MAX).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,35 @@
+//! Reading, inspecting and rewriting Unreal Engine `AssetRegistry.bin` files. The binary in
+//! `main.rs` is a command line front end over this library.
+
+#![feature(str_from_utf16_endian)]
+#![warn(unit_bindings)]
+
+pub mod asset_index;
+pub mod asset_registry;
+pub mod asset_registry_header;
+pub mod asset_registry_ref;
+pub mod asset_registry_version;
+pub mod assets;
+pub mod builder;
+pub mod cityhash;
+pub mod compact;
+pub mod coverage;
+pub mod dependencies;
+pub mod edit;
+pub mod hexdump;
+pub mod html;
+pub mod lazy_registry;
+pub mod lenient;
+pub mod merge;
+pub mod names_batch;
+pub mod path_tree;
+pub mod query;
+pub mod read;
+pub mod rename;
+pub mod serialized_name_header;
+pub mod spans;
+pub mod stats;
+pub mod store_data;
+pub mod unreal_types;
+pub mod validate;
+pub mod write;
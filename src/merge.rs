@@ -0,0 +1,696 @@
+//! Merging several asset registries into one, e.g. a game's registry with the registry fragments
+//! shipped by mods.
+//!
+//! Every registry has its own name table and store, so merging re-interns all names into a single
+//! [`NamesBatch`][crate::names_batch::NamesBatch] and copies tag values and pairs into a fresh
+//! store. Entries are matched across registries by their resolved key (object path for assets,
+//! package name for package data, asset identifier for dependency nodes).
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use color_eyre::eyre::{eyre, Result as EResult};
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::assets::{
+    AssetData, AssetDataCollection, AssetPackageDataCollection, FAssetBundleEntry,
+    FAssetPackageData, FSoftObjectPath,
+};
+use crate::dependencies::{
+    DependencySection, FAssetIdentifier, FDependency, FDependsNode, ResolvedIdentifier,
+};
+use crate::names_batch::NamesBuilder;
+use crate::store_data::{StoreBuilder, StoreValue};
+use crate::unreal_types::*;
+
+/// What to do when several registries define an entry with the same key.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ConflictPolicy {
+    /// Later registries replace entries of earlier ones.
+    OverlayWins,
+    /// The first registry to define an entry keeps it.
+    BaseWins,
+    /// Any conflict aborts the merge.
+    Error,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum MergeEntryKind {
+    Asset,
+    DependencyNode,
+    PackageData,
+}
+
+/// An entry dropped in favour of another registry's entry with the same key. Registries are
+/// numbered with `0` being the base and `n` being `overlays[n - 1]`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OverriddenEntry {
+    pub kind: MergeEntryKind,
+    pub key: String,
+    pub kept: usize,
+    pub discarded: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MergeReport {
+    pub overridden: Vec<OverriddenEntry>,
+}
+
+impl AssetRegistry {
+    /// Merge `overlays` into `base`, in order. Assets, dependency nodes and package data are
+    /// unioned; entries present in several registries are resolved according to `policy`.
+    #[instrument(name = "AssetRegistry_merge", skip_all, fields(overlays = overlays.len()))]
+    pub fn merge(
+        base: &AssetRegistry,
+        overlays: &[AssetRegistry],
+        policy: ConflictPolicy,
+    ) -> EResult<(AssetRegistry, MergeReport)> {
+        let sources = std::iter::once(base).chain(overlays).collect::<Vec<_>>();
+        let mut report = MergeReport::default();
+
+        let asset_keys = sources
+            .iter()
+            .map(|r| {
+                r.assets
+                    .assets
+                    .iter()
+                    .map(|a| r.names.try_resolve(a.object_path))
+                    .collect()
+            })
+            .collect::<EResult<Vec<Vec<_>>>>()?;
+        let (asset_winners, _) = pick_winners(
+            MergeEntryKind::Asset,
+            &asset_keys,
+            |k| k.clone(),
+            policy,
+            &mut report,
+        )?;
+
+        let node_keys = sources
+            .iter()
+            .map(|r| {
+                r.dependencies
+                    .nodes
+                    .iter()
+                    .map(|n| n.identifier.resolve(&r.names))
+                    .collect()
+            })
+            .collect::<EResult<Vec<Vec<_>>>>()?;
+        let (node_winners, node_slots) = pick_winners(
+            MergeEntryKind::DependencyNode,
+            &node_keys,
+            ResolvedIdentifier::to_string,
+            policy,
+            &mut report,
+        )?;
+
+        let package_keys = sources
+            .iter()
+            .map(|r| {
+                r.package_data
+                    .packages
+                    .iter()
+                    .map(|p| r.names.try_resolve(p.package_name))
+                    .collect()
+            })
+            .collect::<EResult<Vec<Vec<_>>>>()?;
+        let (package_winners, _) = pick_winners(
+            MergeEntryKind::PackageData,
+            &package_keys,
+            |k| k.clone(),
+            policy,
+            &mut report,
+        )?;
+
+        let mut merger = Merger::new(&sources, base.names.hash_version);
+
+        let assets = asset_winners
+            .iter()
+            .map(|&(r, i)| merger.asset(r, &sources[r].assets.assets[i]))
+            .collect::<EResult<Vec<_>>>()?;
+
+        // Old node index -> merged node index, for every node of every registry (not just the
+        // winners), so that edges pointing at an overridden node land on its replacement.
+        let node_maps = node_keys
+            .iter()
+            .map(|keys| keys.iter().map(|k| node_slots[k] as i32).collect())
+            .collect::<Vec<Vec<_>>>();
+        let nodes = node_winners
+            .iter()
+            .map(|&(r, i)| merger.node(r, &sources[r].dependencies.nodes[i], &node_maps[r]))
+            .collect::<EResult<Vec<_>>>()?;
+        let mut dependencies = DependencySection { nodes };
+        dependencies.rebuild_referencers();
+
+        let packages = package_winners
+            .iter()
+            .map(|&(r, i)| merger.package_data(r, &sources[r].package_data.packages[i]))
+            .collect::<EResult<Vec<_>>>()?;
+
+        debug!(
+            assets = assets.len(),
+            nodes = dependencies.nodes.len(),
+            packages = packages.len(),
+            overridden = report.overridden.len()
+        );
+
+        let merged = AssetRegistry {
+            names: merger.names.names,
+            store: merger.store.store,
+            assets: AssetDataCollection { assets },
+            dependencies,
+            package_data: AssetPackageDataCollection { packages },
+        };
+        Ok((merged, report))
+    }
+}
+
+/// The `(registry, item)` picked for every key, and the output slot of every key.
+type Winners<K> = (Vec<(usize, usize)>, HashMap<K, usize>);
+
+/// Pick one `(registry, item)` per key, ordered by where each key first appears.
+fn pick_winners<K: Eq + Hash + Clone>(
+    kind: MergeEntryKind,
+    keys: &[Vec<K>],
+    display: impl Fn(&K) -> String,
+    policy: ConflictPolicy,
+    report: &mut MergeReport,
+) -> EResult<Winners<K>> {
+    let mut winners = vec![];
+    let mut slots = HashMap::new();
+    for (r, registry_keys) in keys.iter().enumerate() {
+        for (i, key) in registry_keys.iter().enumerate() {
+            let Some(&slot) = slots.get(key) else {
+                slots.insert(key.clone(), winners.len());
+                winners.push((r, i));
+                continue;
+            };
+
+            let (previous, _) = winners[slot];
+            let (kept, discarded) = match policy {
+                ConflictPolicy::OverlayWins => {
+                    winners[slot] = (r, i);
+                    (r, previous)
+                }
+                ConflictPolicy::BaseWins => (previous, r),
+                ConflictPolicy::Error => {
+                    return Err(eyre!(
+                        "{:?} `{}` is defined by both registry {} and registry {}",
+                        kind,
+                        display(key),
+                        previous,
+                        r
+                    ));
+                }
+            };
+            report.overridden.push(OverriddenEntry {
+                kind,
+                key: display(key),
+                kept,
+                discarded,
+            });
+        }
+    }
+    Ok((winners, slots))
+}
+
+/// Copies entries of source registries into a fresh name table and store, interning only the
+/// names and values that the copied entries reference.
+pub(crate) struct Merger<'a> {
+    sources: &'a [&'a AssetRegistry],
+    pub(crate) names: NamesBuilder,
+    pub(crate) store: StoreBuilder,
+    /// `(registry, old handle)` -> new handle, so that shared tag maps stay shared.
+    tag_maps: HashMap<(usize, u64), u64>,
+}
+
+impl<'a> Merger<'a> {
+    pub(crate) fn new(sources: &'a [&'a AssetRegistry], hash_version: u64) -> Self {
+        Merger {
+            sources,
+            names: NamesBuilder::new(hash_version),
+            store: StoreBuilder::default(),
+            tag_maps: HashMap::new(),
+        }
+    }
+
+    fn name(&mut self, r: usize, name: FName) -> EResult<FName> {
+        let sources = self.sources;
+        let source = &sources[r].names;
+        let i = name.index as usize;
+        let (Some(string), Some(hash), Some(header)) = (
+            source.strings.get(i),
+            source.hashes.get(i),
+            source.headers.get(i),
+        ) else {
+            return Err(eyre!(
+                "FName index {} out of bounds of registry {}'s name table",
+                name.index,
+                r
+            ));
+        };
+
+        let index = self
+            .names
+            .intern_with(string, || Ok((*hash, header.clone())))?;
+        Ok(FName {
+            index,
+            number: name.number,
+        })
+    }
+
+    fn export_path(
+        &mut self,
+        r: usize,
+        path: FAssetRegistryExportPath,
+    ) -> EResult<FAssetRegistryExportPath> {
+        Ok(FAssetRegistryExportPath {
+            class: self.name(r, path.class)?,
+            object: self.name(r, path.object)?,
+            package: self.name(r, path.package)?,
+        })
+    }
+
+    fn value(&mut self, r: usize, value: u32) -> EResult<FValueId> {
+        let value = self.sources[r].store.value(FValueId::from_int(value)?)?;
+        let value = match value {
+            StoreValue::NumberlessName(n) => StoreValue::NumberlessName(self.name(r, n)?),
+            StoreValue::Name(n) => StoreValue::Name(self.name(r, n)?),
+            StoreValue::NumberlessExportPath(p) => {
+                StoreValue::NumberlessExportPath(self.export_path(r, p)?)
+            }
+            StoreValue::ExportPath(p) => StoreValue::ExportPath(self.export_path(r, p)?),
+            value => value,
+        };
+        Ok(self.store.push_value(value))
+    }
+
+    fn tags(&mut self, r: usize, tags: u64) -> EResult<u64> {
+        if let Some(handle) = self.tag_maps.get(&(r, tags)) {
+            return Ok(*handle);
+        }
+        let sources = self.sources;
+        let pairs = sources[r]
+            .store
+            .pairs_for(FPartialMapHandle::from_int(tags))?;
+        let map = pairs
+            .iter()
+            .map(|pair| Ok((self.name(r, pair.key)?, self.value(r, pair.value)?)))
+            .collect::<EResult<Vec<_>>>()?;
+        let handle = self.store.push_map(&map).to_int();
+        self.tag_maps.insert((r, tags), handle);
+        Ok(handle)
+    }
+
+    pub(crate) fn asset(&mut self, r: usize, asset: &AssetData) -> EResult<AssetData> {
+        let bundles = asset
+            .bundles
+            .iter()
+            .map(|entry| {
+                Ok(FAssetBundleEntry {
+                    bundle_name: self.name(r, entry.bundle_name)?,
+                    bundles: entry
+                        .bundles
+                        .iter()
+                        .map(|path| {
+                            Ok(FSoftObjectPath {
+                                asset_path_name: self.name(r, path.asset_path_name)?,
+                                sub_path_string: path.sub_path_string.clone(),
+                            })
+                        })
+                        .collect::<EResult<_>>()?,
+                })
+            })
+            .collect::<EResult<_>>()?;
+        Ok(AssetData {
+            object_path: self.name(r, asset.object_path)?,
+            package_path: self.name(r, asset.package_path)?,
+            asset_class: self.name(r, asset.asset_class)?,
+            package_name: self.name(r, asset.package_name)?,
+            asset_name: self.name(r, asset.asset_name)?,
+            tags: self.tags(r, asset.tags)?,
+            bundles,
+            chunk_ids: asset.chunk_ids.clone(),
+            package_flags: asset.package_flags,
+        })
+    }
+
+    pub(crate) fn node(
+        &mut self,
+        r: usize,
+        node: &FDependsNode,
+        node_map: &[i32],
+    ) -> EResult<FDependsNode> {
+        let map_index = |i: i32| {
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| node_map.get(i).copied())
+                .ok_or_else(|| eyre!("dependency on unknown node {} in registry {}", i, r))
+        };
+        let map_dependency = |d: &FDependency| {
+            Ok(FDependency {
+                node: map_index(d.node)?,
+                properties: d.properties,
+            })
+        };
+
+        let identifier = &node.identifier;
+        let mut name = |n: Option<FName>| n.map(|n| self.name(r, n)).transpose();
+        let identifier = FAssetIdentifier {
+            package_name: name(identifier.package_name)?,
+            primary_asset_type: name(identifier.primary_asset_type)?,
+            object_name: name(identifier.object_name)?,
+            value_name: name(identifier.value_name)?,
+        };
+
+        Ok(FDependsNode {
+            identifier,
+            package_dependencies: node
+                .package_dependencies
+                .iter()
+                .map(map_dependency)
+                .collect::<EResult<_>>()?,
+            name_dependencies: node
+                .name_dependencies
+                .iter()
+                .map(|&i| map_index(i))
+                .collect::<EResult<_>>()?,
+            manage_dependencies: node
+                .manage_dependencies
+                .iter()
+                .map(map_dependency)
+                .collect::<EResult<_>>()?,
+            // Rebuilt once all nodes are merged.
+            referencers: vec![],
+        })
+    }
+
+    pub(crate) fn package_data(
+        &mut self,
+        r: usize,
+        package: &FAssetPackageData,
+    ) -> EResult<FAssetPackageData> {
+        Ok(FAssetPackageData {
+            package_name: self.name(r, package.package_name)?,
+            ..package.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dependencies::EDependencyProperty;
+    use crate::serialized_name_header::SerializedNameHeader;
+
+    use pretty_assertions::assert_eq;
+
+    /// `(package, class, tags)`
+    type TestAsset<'a> = (&'a str, &'a str, &'a [(&'a str, &'a str)]);
+
+    /// Minimal registry: each dependency is `(package, hard dependencies)`.
+    fn registry(
+        assets: &[TestAsset],
+        dependencies: &[(&str, &[&str])],
+        packages: &[(&str, i64)],
+    ) -> AssetRegistry {
+        let mut registry = AssetRegistry::default();
+        let mut store = StoreBuilder::default();
+        let mut name = |s: &str| {
+            let names = &mut registry.names;
+            let index = match names.strings.iter().position(|n| n == s) {
+                Some(index) => index,
+                None => {
+                    names.strings.push(s.to_string());
+                    names.hashes.push(names.strings.len() as u64);
+                    names.headers.push(SerializedNameHeader {
+                        is_utf16: false,
+                        len: s.len() as u16,
+                    });
+                    names.strings.len() - 1
+                }
+            };
+            FName {
+                index: index as u32,
+                number: 0,
+            }
+        };
+
+        for (package, class, tags) in assets {
+            let (package_path, asset_name) = package.rsplit_once('/').unwrap();
+            let map = tags
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        name(k),
+                        store.push_value(StoreValue::AnsiString(v.to_string())),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let asset = AssetData {
+                object_path: name(&format!("{package}.{asset_name}")),
+                package_path: name(package_path),
+                asset_class: name(class),
+                package_name: name(package),
+                asset_name: name(asset_name),
+                tags: store.push_map(&map).to_int(),
+                bundles: vec![],
+                chunk_ids: vec![],
+                package_flags: 0,
+            };
+            registry.assets.assets.push(asset);
+        }
+
+        let mut node_indices = HashMap::new();
+        let mut node_index = |package: &str, nodes: &mut Vec<FDependsNode>| {
+            *node_indices.entry(package.to_string()).or_insert_with(|| {
+                nodes.push(FDependsNode {
+                    identifier: FAssetIdentifier::package(name(package)),
+                    ..Default::default()
+                });
+                nodes.len() as i32 - 1
+            })
+        };
+        let mut nodes = vec![];
+        for (package, targets) in dependencies {
+            let from = node_index(package, &mut nodes);
+            for target in *targets {
+                let to = node_index(target, &mut nodes);
+                nodes[from as usize].package_dependencies.push(FDependency {
+                    node: to,
+                    properties: EDependencyProperty::HARD,
+                });
+            }
+        }
+        registry.dependencies.nodes = nodes;
+        registry.dependencies.rebuild_referencers();
+
+        for (package, disk_size) in packages {
+            let package_name = name(package);
+            registry.package_data.packages.push(FAssetPackageData {
+                package_name,
+                disk_size: *disk_size,
+                package_guid: [0; 16],
+                cooked_hash: None,
+            });
+        }
+
+        registry.store = store.store;
+        registry
+    }
+
+    fn object_paths(registry: &AssetRegistry) -> Vec<String> {
+        registry
+            .assets
+            .assets
+            .iter()
+            .map(|a| registry.names.try_resolve(a.object_path).unwrap())
+            .collect()
+    }
+
+    fn tags(registry: &AssetRegistry, object_path: &str) -> Vec<(String, StoreValue)> {
+        let asset = registry
+            .assets
+            .assets
+            .iter()
+            .find(|a| registry.names.try_resolve(a.object_path).unwrap() == object_path)
+            .unwrap();
+        registry
+            .store
+            .pairs_for(FPartialMapHandle::from_int(asset.tags))
+            .unwrap()
+            .iter()
+            .map(|p| {
+                let value = registry
+                    .store
+                    .value(FValueId::from_int(p.value).unwrap())
+                    .unwrap();
+                (registry.names.try_resolve(p.key).unwrap(), value)
+            })
+            .collect()
+    }
+
+    fn dependencies(registry: &AssetRegistry) -> Vec<(String, Vec<String>, Vec<String>)> {
+        let nodes = &registry.dependencies.nodes;
+        let package = |i: i32| {
+            let identifier = &nodes[i as usize].identifier;
+            registry
+                .names
+                .try_resolve(identifier.package_name.unwrap())
+                .unwrap()
+        };
+        (0..nodes.len() as i32)
+            .map(|i| {
+                let node = &nodes[i as usize];
+                (
+                    package(i),
+                    node.package_dependencies
+                        .iter()
+                        .map(|d| package(d.node))
+                        .collect(),
+                    node.referencers.iter().map(|&r| package(r)).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn fixtures() -> (AssetRegistry, AssetRegistry) {
+        let base = registry(
+            &[
+                ("/Game/A", "Blueprint", &[("ParentClass", "Actor")]),
+                ("/Game/B", "Texture2D", &[]),
+            ],
+            &[("/Game/A", &["/Game/B"])],
+            &[("/Game/A", 10), ("/Game/B", 20)],
+        );
+        let overlay = registry(
+            &[
+                ("/Game/Mod/C", "Blueprint", &[("ParentClass", "Pawn")]),
+                ("/Game/A", "Blueprint", &[("ParentClass", "Character")]),
+            ],
+            &[("/Game/Mod/C", &["/Game/A"]), ("/Game/A", &[])],
+            &[("/Game/A", 30), ("/Game/Mod/C", 40)],
+        );
+        (base, overlay)
+    }
+
+    #[test]
+    fn test_merge_overlay_wins() {
+        let (base, overlay) = fixtures();
+        let (merged, report) =
+            AssetRegistry::merge(&base, &[overlay], ConflictPolicy::OverlayWins).unwrap();
+
+        assert_eq!(
+            object_paths(&merged),
+            vec!["/Game/A.A", "/Game/B.B", "/Game/Mod/C.C"]
+        );
+        assert_eq!(
+            tags(&merged, "/Game/A.A"),
+            vec![(
+                "ParentClass".to_string(),
+                StoreValue::AnsiString("Character".to_string())
+            )]
+        );
+        assert_eq!(
+            tags(&merged, "/Game/Mod/C.C"),
+            vec![(
+                "ParentClass".to_string(),
+                StoreValue::AnsiString("Pawn".to_string())
+            )]
+        );
+        assert_eq!(
+            dependencies(&merged),
+            vec![
+                (
+                    "/Game/A".to_string(),
+                    vec![],
+                    vec!["/Game/Mod/C".to_string()]
+                ),
+                ("/Game/B".to_string(), vec![], vec![]),
+                (
+                    "/Game/Mod/C".to_string(),
+                    vec!["/Game/A".to_string()],
+                    vec![]
+                ),
+            ]
+        );
+        let disk_sizes = merged
+            .package_data
+            .packages
+            .iter()
+            .map(|p| p.disk_size)
+            .collect::<Vec<_>>();
+        assert_eq!(disk_sizes, vec![30, 20, 40]);
+
+        let overridden = |kind| OverriddenEntry {
+            kind,
+            key: match kind {
+                MergeEntryKind::Asset => "/Game/A.A",
+                _ => "/Game/A",
+            }
+            .to_string(),
+            kept: 1,
+            discarded: 0,
+        };
+        assert_eq!(
+            report.overridden,
+            vec![
+                overridden(MergeEntryKind::Asset),
+                overridden(MergeEntryKind::DependencyNode),
+                overridden(MergeEntryKind::PackageData),
+            ]
+        );
+
+        // Every name is interned exactly once.
+        let mut strings = merged.names.strings.clone();
+        strings.sort();
+        strings.dedup();
+        assert_eq!(strings.len(), merged.names.strings.len());
+        assert_eq!(merged.names.hashes.len(), merged.names.strings.len());
+    }
+
+    #[test]
+    fn test_merge_base_wins() {
+        let (base, overlay) = fixtures();
+        let (merged, report) =
+            AssetRegistry::merge(&base, &[overlay], ConflictPolicy::BaseWins).unwrap();
+
+        assert_eq!(
+            tags(&merged, "/Game/A.A"),
+            vec![(
+                "ParentClass".to_string(),
+                StoreValue::AnsiString("Actor".to_string())
+            )]
+        );
+        assert_eq!(
+            dependencies(&merged)[0],
+            (
+                "/Game/A".to_string(),
+                vec!["/Game/B".to_string()],
+                vec!["/Game/Mod/C".to_string()]
+            )
+        );
+        assert_eq!(report.overridden.len(), 3);
+        assert!(report
+            .overridden
+            .iter()
+            .all(|o| o.kept == 0 && o.discarded == 1));
+    }
+
+    #[test]
+    fn test_merge_error() {
+        let (base, overlay) = fixtures();
+        assert!(AssetRegistry::merge(&base, &[overlay], ConflictPolicy::Error).is_err());
+
+        let (base, _) = fixtures();
+        let disjoint = registry(&[("/Game/D", "World", &[])], &[], &[]);
+        let (merged, report) =
+            AssetRegistry::merge(&base, &[disjoint], ConflictPolicy::Error).unwrap();
+        assert_eq!(
+            object_paths(&merged),
+            vec!["/Game/A.A", "/Game/B.B", "/Game/D.D"]
+        );
+        assert!(report.overridden.is_empty());
+    }
+}
@@ -1,108 +1,322 @@
-use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::io::{Read, Seek, Write};
 
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 use color_eyre::eyre::{eyre, Result as EResult, WrapErr};
 use tracing::*;
 
-use crate::read::{read_array, Readable};
+use crate::cityhash::city_hash64;
+use crate::lenient::{decode_string, read_bytes, Recovery};
+use crate::read::{read_array, Positioned, Readable};
 use crate::serialized_name_header::SerializedNameHeader;
-use crate::write::{write_array, Writable};
+use crate::spans;
+use crate::unreal_types::FName;
+use crate::write::{
+    count_u32, size_u32, write_array, PatchWriter, SerializedSize, Writable, WriteError,
+};
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct NamesBatch {
     // TODO: determine the actual hash and versions related to this.
     pub hash_version: u64,
-    // FIXME: generate the hashes!
     pub hashes: Vec<u64>,
-    // FIXME: generate the headers!
     pub headers: Vec<SerializedNameHeader>,
     pub strings: Vec<String>,
 }
 
-impl<W: Write> Writable<W> for NamesBatch {
-    #[instrument(name = "NamesBatch_write", skip_all)]
-    fn write(&self, writer: &mut W) -> EResult<()> {
-        assert_eq!(self.hashes.len(), self.headers.len());
-        assert_eq!(self.headers.len(), self.strings.len());
+impl NamesBatch {
+    /// The `FNameHash` algorithm id that Unreal writes as the hash version (CityHash64 of the
+    /// lowercased string).
+    pub const HASH_VERSION: u64 = 0xC164_0000;
 
-        trace!(count = self.strings.len());
-        writer.write_u32::<LE>(self.strings.len() as u32)?;
+    /// Hash a name like Unreal does: CityHash64 of the lowercased string, as bytes for ANSI names
+    /// and as UTF-16LE code units for wide names.
+    pub fn hash(s: &str) -> u64 {
+        let lower = s.to_lowercase();
+        match lower.is_ascii() {
+            true => city_hash64(lower.as_bytes()),
+            false => {
+                let bytes = lower
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes)
+                    .collect::<Vec<_>>();
+                city_hash64(&bytes)
+            }
+        }
+    }
 
-        // `s.len() + 1` for the terminating NUL-byte that we need to append.
-        let string_bytes = self
-            .strings
-            .iter()
-            .map(|s| (s.len() + 1) as u32)
-            .sum::<u32>();
-        writer.write_u32::<LE>(string_bytes)?;
+    /// The string an [`FName`] refers to, without its number suffix.
+    pub fn get(&self, name: FName) -> Option<&str> {
+        self.strings.get(name.index as usize).map(String::as_str)
+    }
 
-        writer.write_u64::<LE>(self.hash_version)?;
+    /// Resolve an [`FName`] to its display string. Like Unreal, a number of `0` means "no number"
+    /// and a number of `N + 1` is displayed as a `_N` suffix.
+    pub fn resolve(&self, name: FName) -> Option<String> {
+        let base = self.get(name)?;
+        Some(match name.number {
+            0 => base.to_string(),
+            number => format!("{base}_{}", number - 1),
+        })
+    }
 
-        write_array(writer, &self.hashes, |w, h| w.write_u64::<LE>(*h))?;
+    /// Like [`NamesBatch::resolve`], but errors out on dangling indices.
+    pub fn try_resolve(&self, name: FName) -> EResult<String> {
+        self.resolve(name).ok_or_else(|| {
+            eyre!(
+                "FName index {} out of bounds of {} names",
+                name.index,
+                self.strings.len()
+            )
+        })
+    }
+}
+
+/// Incrementally builds a [`NamesBatch`], interning every string once.
+#[derive(Debug, Default)]
+pub(crate) struct NamesBuilder {
+    pub(crate) names: NamesBatch,
+    indices: HashMap<String, u32>,
+}
+
+impl NamesBuilder {
+    pub(crate) fn new(hash_version: u64) -> Self {
+        NamesBuilder {
+            names: NamesBatch {
+                hash_version,
+                ..Default::default()
+            },
+            indices: HashMap::new(),
+        }
+    }
 
-        write_array(writer, &self.headers, |w, h| h.write(w))?;
+    /// Continue interning into an existing name table.
+    pub(crate) fn from_names(names: NamesBatch) -> Self {
+        let mut indices = HashMap::with_capacity(names.strings.len());
+        for (i, s) in names.strings.iter().enumerate() {
+            indices.entry(s.clone()).or_insert(i as u32);
+        }
+        NamesBuilder { names, indices }
+    }
 
-        write_array(writer, &self.strings, |w, s| -> EResult<()> {
-            w.write_all(s.as_bytes())?;
-            w.write_u8(0)?;
-            Ok(())
+    /// Intern a display string, splitting off its number suffix. Fails if the name is too long
+    /// to be written.
+    pub(crate) fn intern(&mut self, s: &str) -> EResult<FName> {
+        let (base, number) = FName::split_number(s);
+        let index = self.intern_with(base, || {
+            Ok((
+                NamesBatch::hash(base),
+                SerializedNameHeader::for_string(base)?,
+            ))
         })?;
+        Ok(FName { index, number })
+    }
 
+    /// Intern a base string, computing its hash and header only if it's new.
+    pub(crate) fn intern_with(
+        &mut self,
+        s: &str,
+        hash_and_header: impl FnOnce() -> EResult<(u64, SerializedNameHeader)>,
+    ) -> EResult<u32> {
+        if let Some(index) = self.indices.get(s) {
+            return Ok(*index);
+        }
+        let (hash, header) = hash_and_header()?;
+        let index = self.names.strings.len() as u32;
+        self.names.strings.push(s.to_string());
+        self.names.hashes.push(hash);
+        self.names.headers.push(header);
+        self.indices.insert(s.to_string(), index);
+        Ok(index)
+    }
+}
+
+impl NamesBatch {
+    /// Check that the hashes and headers describe the strings, before anything is written.
+    fn check(&self) -> EResult<()> {
+        for (what, len) in [
+            ("name hashes", self.hashes.len()),
+            ("name headers", self.headers.len()),
+        ] {
+            if len != self.strings.len() {
+                return Err(WriteError::Mismatch {
+                    what,
+                    expected: self.strings.len() as u64,
+                    found: len as u64,
+                }
+                .into());
+            }
+        }
+        for (i, (header, s)) in self.headers.iter().zip(&self.strings).enumerate() {
+            let len = match header.is_utf16 {
+                true => s.encode_utf16().count(),
+                false => s.len(),
+            };
+            let location = || format!("names.strings[{i}]");
+            if len > SerializedNameHeader::MAX_LEN as usize {
+                return Err(WriteError::NameTooLong { len: len as u64 }).wrap_err_with(location);
+            }
+            if len != header.len as usize {
+                return Err(WriteError::Mismatch {
+                    what: "characters",
+                    expected: header.len as u64,
+                    found: len as u64,
+                })
+                .wrap_err_with(location);
+            }
+        }
         Ok(())
     }
 }
 
+impl<W: Write + Seek> Writable<W> for NamesBatch {
+    #[instrument(name = "NamesBatch_write", skip_all)]
+    fn write(&self, writer: &mut W) -> EResult<()> {
+        self.check()?;
+
+        trace!(count = self.strings.len());
+        let mut writer = PatchWriter::new(writer)?;
+        writer.write_u32::<LE>(count_u32("names", self.strings.len())?)?;
+        let string_bytes = writer.reserve::<u32>()?;
+        writer.write_u64::<LE>(self.hash_version)?;
+
+        write_array(&mut writer, &self.hashes, |w, h| w.write_u64::<LE>(*h))?;
+
+        write_array(&mut writer, &self.headers, |w, h| h.write(w))?;
+
+        // Strings are not NUL-terminated, the headers carry their lengths.
+        let start = writer.position();
+        for (header, s) in self.headers.iter().zip(&self.strings) {
+            match header.is_utf16 {
+                true => s
+                    .encode_utf16()
+                    .try_for_each(|c| writer.write_u16::<LE>(c))?,
+                false => writer.write_all(s.as_bytes())?,
+            }
+        }
+        let end = writer.position();
+        writer.patch(string_bytes, size_u32("name strings", end - start)?)?;
+        writer.finish()
+    }
+}
+
+impl SerializedSize for NamesBatch {
+    fn serialized_size(&self) -> u64 {
+        let string_bytes = self
+            .headers
+            .iter()
+            .zip(&self.strings)
+            .map(|(header, s)| match header.is_utf16 {
+                true => 2 * s.encode_utf16().count() as u64,
+                false => s.len() as u64,
+            })
+            .sum::<u64>();
+        4 + 4 + 8 + 8 * self.hashes.len() as u64 + self.headers.serialized_size() + string_bytes
+    }
+}
+
 impl<R: Read> Readable<R> for NamesBatch {
     #[instrument(name = "NamesBatch_read", skip_all)]
     fn read(reader: &mut R) -> EResult<Self> {
-        let count = reader.read_u32::<LE>()?;
+        NamesBatch::read_with(&mut Positioned::new(reader, 0), &mut Recovery::strict())
+    }
+}
+
+impl NamesBatch {
+    /// Read the name table, letting `recovery` decide what to do about malformed strings. A
+    /// truncated table keeps the names read so far.
+    #[instrument(name = "NamesBatch_read_with", skip_all)]
+    pub(crate) fn read_with<R: Read>(
+        reader: &mut Positioned<R>,
+        recovery: &mut Recovery,
+    ) -> EResult<Self> {
+        let count = spans::field("count", || reader.read_u32::<LE>())?;
         debug!(count);
 
-        let expected_string_bytes = reader.read_u32::<LE>()?;
+        let expected_string_bytes = spans::field("string_bytes", || reader.read_u32::<LE>())?;
 
-        let hash_version = reader.read_u64::<LE>()?;
-        let hashes = read_array(count, reader, |r| r.read_u64::<LE>())?;
-        let headers = read_array(count, reader, SerializedNameHeader::read)?;
+        let hash_version = spans::field("hash_version", || reader.read_u64::<LE>())?;
+        let mut hashes = spans::field("hashes", || {
+            read_array(count, reader, |r| r.read_u64::<LE>())
+        })?;
+        let mut headers = spans::field("headers", || {
+            read_array(count, reader, SerializedNameHeader::read)
+        })?;
 
         let mut strings = Vec::with_capacity(count as usize);
         let mut processed_string_bytes = 0u32;
-        for header @ SerializedNameHeader { is_utf16, len } in &headers {
+        let _strings = spans::enter("strings");
+        for (i, header @ SerializedNameHeader { is_utf16, len }) in headers.iter().enumerate() {
+            #[cfg(feature = "trace-fields")]
             trace!(?header);
+            let offset = reader.offset;
+            let location = || format!("names.strings[{i}]");
             if *len == 0 {
-                return Err(eyre!(
-                    "got unexpected zero-length NUL-terminated string, how did this happen?"
-                ));
+                recovery.recover(offset, location, eyre!("got unexpected zero-length name"))?;
             }
 
             if processed_string_bytes.saturating_add(header.n_bytes()) > expected_string_bytes {
-                return Err(eyre!(
-                    "we got more string bytes than is expected from the NamesBatch header, what?"
-                ));
+                recovery.recover(
+                    offset,
+                    location,
+                    eyre!(
+                        "we got more string bytes than is expected from the NamesBatch header, what?"
+                    ),
+                )?;
             }
 
-            if *is_utf16 {
-                let mut buf = vec![0u16; *len as usize];
-                for _ in 0..len - 1 {
-                    let b = reader.read_u16::<LE>()?;
-                    buf.push(b);
+            let buf = match spans::index(i as u64, || read_bytes(reader, header.n_bytes() as usize))
+            {
+                Ok(buf) => buf,
+                Err(e) => {
+                    recovery.recover(offset, location, e.into())?;
+                    break;
                 }
-                let s = String::from_utf16(&buf)
-                    .wrap_err_with(|| "failed to build a UTF-8 string from NamesBatch string")?;
-                strings.push(s);
+            };
+            #[cfg(feature = "trace-fields")]
+            trace!(?buf);
+            let s = if *is_utf16 {
+                let buf = buf
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>();
+                decode_string(
+                    recovery,
+                    offset,
+                    location,
+                    || {
+                        String::from_utf16(&buf).wrap_err_with(|| {
+                            "failed to build a UTF-8 string from NamesBatch string"
+                        })
+                    },
+                    || String::from_utf16_lossy(&buf),
+                )?
             } else {
-                let mut buf = vec![0u8; *len as usize];
-                reader.read_exact(&mut buf)?;
-                trace!(?buf);
-                let s = String::from_utf8(buf)
-                    .wrap_err_with(|| "failed to build a UTF-8 string from NamesBatch string")?;
-                strings.push(s);
-            }
+                decode_string(
+                    recovery,
+                    offset,
+                    location,
+                    || {
+                        String::from_utf8(buf.clone()).wrap_err_with(|| {
+                            "failed to build a UTF-8 string from NamesBatch string"
+                        })
+                    },
+                    || String::from_utf8_lossy(&buf).into_owned(),
+                )?
+            };
+            strings.push(s);
 
-            processed_string_bytes += header.n_bytes();
+            processed_string_bytes = processed_string_bytes.saturating_add(header.n_bytes());
         }
 
-        if processed_string_bytes != expected_string_bytes {
-            return Err(eyre!("the NamesBatch header says to expect {:X} string bytes, but we processed {:X} string bytes", processed_string_bytes, expected_string_bytes));
+        drop(_strings);
+
+        if strings.len() < headers.len() {
+            // Keep the table consistent with the names that could be read.
+            hashes.truncate(strings.len());
+            headers.truncate(strings.len());
+        } else if processed_string_bytes != expected_string_bytes {
+            recovery.recover(reader.offset, || "names".to_string(), eyre!("the NamesBatch header says to expect {:X} string bytes, but we processed {:X} string bytes", processed_string_bytes, expected_string_bytes))?;
         }
 
         Ok(NamesBatch {
@@ -122,24 +336,28 @@ mod tests {
     #[test]
     fn test_roundtrip() {
         let hash_version = 0xDEAD_BEEFu64;
-        let hashes = vec![0xBAAAAAADu64, 0xDEADC0DE];
+        let hashes = vec![0xBAAAAAADu64, 0xDEADC0DE, 0xC0FFEE];
         let headers = vec![
             SerializedNameHeader {
                 is_utf16: false,
-                len: 3,
+                len: 2,
             },
             SerializedNameHeader {
                 is_utf16: false,
+                len: 1,
+            },
+            SerializedNameHeader {
+                is_utf16: true,
                 len: 2,
             },
         ];
-        let strings = vec!["12".to_string(), "3".to_string()];
+        let strings = vec!["12".to_string(), "3".to_string(), "🙇".to_string()];
 
         let mut buf = vec![];
         let mut writer = Cursor::new(&mut buf);
 
         NamesBatch {
-            hash_version: hash_version.clone(),
+            hash_version,
             hashes: hashes.clone(),
             headers: headers.clone(),
             strings: strings.clone(),
@@ -155,4 +373,76 @@ mod tests {
         assert_eq!(names_batch.headers, headers);
         assert_eq!(names_batch.strings, strings);
     }
+
+    #[test]
+    fn test_write_errors() {
+        let names = NamesBatch {
+            hash_version: NamesBatch::HASH_VERSION,
+            hashes: vec![0],
+            headers: vec![SerializedNameHeader::for_string("a").unwrap()],
+            strings: vec!["a".to_string(), "b".to_string()],
+        };
+        let err = names.write(&mut Cursor::new(vec![])).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<WriteError>(),
+            Some(&WriteError::Mismatch {
+                what: "name hashes",
+                expected: 2,
+                found: 1
+            })
+        );
+
+        let long = "a".repeat(0x8000);
+        let names = NamesBatch {
+            hash_version: NamesBatch::HASH_VERSION,
+            hashes: vec![0],
+            headers: vec![SerializedNameHeader {
+                is_utf16: false,
+                len: 0x8000,
+            }],
+            strings: vec![long],
+        };
+        let mut buf = Cursor::new(vec![]);
+        let err = names.write(&mut buf).unwrap_err();
+        assert_eq!(err.to_string(), "names.strings[0]");
+        assert_eq!(
+            err.downcast_ref::<WriteError>(),
+            Some(&WriteError::NameTooLong { len: 0x8000 })
+        );
+        assert!(buf.get_ref().is_empty());
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut builder = NamesBuilder::new(NamesBatch::HASH_VERSION);
+        let a = builder.intern("World").unwrap();
+        let b = builder.intern("Actor_3").unwrap();
+        let c = builder.intern("Actor").unwrap();
+        assert_eq!(
+            a,
+            FName {
+                index: 0,
+                number: 0
+            }
+        );
+        assert_eq!(
+            b,
+            FName {
+                index: 1,
+                number: 4
+            }
+        );
+        assert_eq!(
+            c,
+            FName {
+                index: 1,
+                number: 0
+            }
+        );
+
+        let names = builder.names;
+        assert_eq!(names.strings, vec!["World", "Actor"]);
+        assert_eq!(names.hashes[0], 16436542438370751598);
+        assert_eq!(names.resolve(b).unwrap(), "Actor_3");
+    }
 }
@@ -0,0 +1,237 @@
+//! The folder hierarchy of a registry, like UE's `FPathTree` behind the Content Browser.
+//!
+//! Folders come from the assets' package paths; every ancestor of such a path is a folder too,
+//! up to the root `/` whose children are the mount points (`/Game`, `/Engine`, ...). Each folder
+//! knows its assets and the disk size of its packages, directly and including sub-folders.
+//!
+//! Like names, folders are case-insensitive: `/Game/Weapons` and `/Game/weapons` are the same
+//! folder, shown with the spelling seen first.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use color_eyre::eyre::{eyre, Result as EResult};
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Folder {
+    /// Full path of the folder, as first spelled.
+    pub path: String,
+    /// Full paths of the direct sub-folders, sorted.
+    pub children: BTreeSet<String>,
+    /// Indices of the assets directly in the folder.
+    pub assets: Vec<usize>,
+    /// Disk size of the packages directly in the folder, from the package data.
+    pub size: i64,
+    /// Number of assets in the folder and its sub-folders.
+    pub total_assets: usize,
+    /// Disk size of the packages in the folder and its sub-folders.
+    pub total_size: i64,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PathTree {
+    /// Folders by [`PathTree::key`].
+    folders: HashMap<String, Folder>,
+}
+
+impl PathTree {
+    pub const ROOT: &'static str = "/";
+
+    pub fn get(&self, path: &str) -> Option<&Folder> {
+        self.folders.get(&PathTree::key(path))
+    }
+
+    /// Visit `path` and its sub-folders depth-first, down to `max_depth` levels below `path`.
+    /// `visit` gets each folder with its depth and returns whether to descend into it.
+    pub fn walk<'a>(
+        &'a self,
+        path: &str,
+        max_depth: usize,
+        visit: &mut dyn FnMut(&'a str, usize) -> bool,
+    ) -> EResult<()> {
+        let folder = self
+            .get(path)
+            .ok_or_else(|| eyre!("no folder `{}` in the registry", path))?;
+        let mut stack = vec![(folder, 0)];
+        while let Some((folder, depth)) = stack.pop() {
+            if visit(&folder.path, depth) && depth < max_depth {
+                stack.extend(
+                    folder
+                        .children
+                        .iter()
+                        .rev()
+                        .map(|c| (&self.folders[&PathTree::key(c)], depth + 1)),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The last component of a folder path, e.g. `Weapons` for `/Game/Weapons`.
+    pub fn name(path: &str) -> &str {
+        match path {
+            PathTree::ROOT => path,
+            _ => path.rsplit_once('/').map_or(path, |(_, name)| name),
+        }
+    }
+
+    fn parent(path: &str) -> Option<&str> {
+        if path == PathTree::ROOT {
+            return None;
+        }
+        match path.rsplit_once('/')? {
+            ("", _) => Some(PathTree::ROOT),
+            (parent, _) => Some(parent),
+        }
+    }
+
+    /// The case-insensitive key of a folder path.
+    fn key(path: &str) -> String {
+        path.to_lowercase()
+    }
+
+    /// The keys of `path` and its ancestors up to the root, creating missing folders.
+    fn ancestors(&mut self, path: &str) -> Vec<String> {
+        let mut ancestors = vec![];
+        let mut child: Option<String> = None;
+        let mut current = Some(path);
+        while let Some(path) = current {
+            let key = PathTree::key(path);
+            let folder = self.folders.entry(key.clone()).or_insert_with(|| Folder {
+                path: path.to_string(),
+                ..Default::default()
+            });
+            if let Some(child) = child {
+                folder.children.insert(child);
+            }
+            child = Some(folder.path.clone());
+            ancestors.push(key);
+            current = PathTree::parent(path);
+        }
+        ancestors
+    }
+}
+
+impl AssetRegistry {
+    /// Build the folder hierarchy of the assets. Fails on names that don't resolve.
+    #[instrument(name = "AssetRegistry_path_tree", skip_all)]
+    pub fn path_tree(&self) -> EResult<PathTree> {
+        let mut disk_sizes = HashMap::new();
+        for package in &self.package_data.packages {
+            disk_sizes.insert(package.package_name, package.disk_size);
+        }
+
+        let mut tree = PathTree::default();
+        tree.ancestors(PathTree::ROOT);
+        let mut packages = HashSet::new();
+        for (i, asset) in self.assets.assets.iter().enumerate() {
+            let path = self.names.try_resolve(asset.package_path)?;
+            let path = path.trim_end_matches('/');
+            let path = if path.is_empty() {
+                PathTree::ROOT
+            } else {
+                path
+            };
+            // Packages with several assets only count once towards the sizes.
+            let size = match packages.insert(asset.package_name) {
+                true => disk_sizes.get(&asset.package_name).copied().unwrap_or(0),
+                false => 0,
+            };
+            let ancestors = tree.ancestors(path);
+            let folder = tree.folders.get_mut(&ancestors[0]).unwrap();
+            folder.assets.push(i);
+            folder.size += size;
+            for ancestor in &ancestors {
+                let folder = tree.folders.get_mut(ancestor).unwrap();
+                folder.total_assets += 1;
+                folder.total_size += size;
+            }
+        }
+        debug!(folders = tree.folders.len());
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::RegistryBuilder;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        builder.add_asset("/Game/Weapons/Rifles/BP_Rifle.BP_Rifle", "Blueprint");
+        builder.add_asset(
+            "/Game/Weapons/Rifles/BP_Rifle.BP_Rifle_C",
+            "BlueprintGeneratedClass",
+        );
+        builder.add_asset("/Game/Weapons/BP_Pistol.BP_Pistol", "Blueprint");
+        builder.add_asset("/Game/Maps/Arena.Arena", "World");
+        builder.add_asset("/Game/weapons/BP_Knife.BP_Knife", "Blueprint");
+        builder.add_asset("/Engine/BasicShapes/Cube.Cube", "StaticMesh");
+        builder.add_package_data("/Game/Weapons/Rifles/BP_Rifle", 100);
+        builder.add_package_data("/Game/Weapons/BP_Pistol", 20);
+        builder.add_package_data("/Game/Maps/Arena", 3);
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_path_tree() {
+        let registry = fixture();
+        let tree = registry.path_tree().unwrap();
+
+        let mut visited = vec![];
+        tree.walk("/game", usize::MAX, &mut |path, depth| {
+            visited.push((path, depth));
+            true
+        })
+        .unwrap();
+        assert_eq!(
+            visited,
+            [
+                ("/Game", 0),
+                ("/Game/Maps", 1),
+                ("/Game/Weapons", 1),
+                ("/Game/Weapons/Rifles", 2)
+            ]
+        );
+
+        // `/Game/weapons` is the same folder as `/Game/Weapons`.
+        let weapons = tree.get("/Game/Weapons").unwrap();
+        assert_eq!(tree.get("/GAME/WEAPONS"), Some(weapons));
+        assert_eq!(weapons.assets, [2, 4]);
+        assert_eq!((weapons.size, weapons.total_size), (20, 120));
+        assert_eq!(weapons.total_assets, 4);
+        let root = tree.get(PathTree::ROOT).unwrap();
+        assert_eq!((root.total_assets, root.total_size), (6, 123));
+        assert_eq!(tree.get("/Game/Weapons/Rifles").unwrap().size, 100);
+
+        let mut visited = vec![];
+        tree.walk("/", 1, &mut |path, depth| {
+            visited.push((path, depth));
+            true
+        })
+        .unwrap();
+        assert_eq!(visited, [("/", 0), ("/Engine", 1), ("/Game", 1)]);
+
+        assert_eq!(
+            tree.walk("/Game/Missing", 0, &mut |_, _| true)
+                .unwrap_err()
+                .to_string(),
+            "no folder `/Game/Missing` in the registry"
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(PathTree::name("/"), "/");
+        assert_eq!(PathTree::name("/Game"), "Game");
+        assert_eq!(PathTree::name("/Game/Weapons"), "Weapons");
+        assert_eq!(PathTree::parent("/Game/Weapons"), Some("/Game"));
+        assert_eq!(PathTree::parent("/Game"), Some("/"));
+        assert_eq!(PathTree::parent("/"), None);
+    }
+}
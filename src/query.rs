@@ -0,0 +1,611 @@
+//! A small query language over resolved assets.
+//!
+//! ```text
+//! class = Blueprint and path ~ "/Game/Weapons/**"
+//!     and tag.ParentClass =~ "BP_GunBase" and tag.ChunkIDs contains 3
+//! ```
+//!
+//! A query combines comparisons with `and`, `or`, `not` (or `&&`, `||`, `!`) and parentheses.
+//! The fields are `class`, `path` (the object path), `package`, `folder` (the package path),
+//! `name` (the asset name) and `tag.<Key>`. A field on its own checks that it is present, which
+//! matters for tags. The comparisons are:
+//!
+//! - `=`, `!=`: equality, ignoring ASCII case like `FName` comparisons do.
+//! - `~`: glob, ignoring ASCII case. `*` and `?` stay within a path segment, `**` crosses them.
+//! - `=~`: regular expression, matching anywhere unless anchored.
+//! - `<`, `<=`, `>`, `>=`, `in <start>..<end>`, `in <start>..=<end>`: numeric comparisons. Values
+//!   that aren't numbers never match.
+//! - `contains`: one of the items of a list value such as `(0,3,5)` equals the operand.
+//!
+//! Values are bare words or double-quoted strings with `\"` and `\\` escapes. A comparison on a
+//! tag that the asset doesn't have is false, whichever the operator.
+
+use std::fmt::Write as _;
+
+use color_eyre::eyre::{eyre, Result as EResult};
+use regex::{Regex, RegexBuilder};
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::assets::ResolvedAssetData;
+use crate::spans::write_json_string;
+
+#[derive(Debug, Clone)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    /// The field is present.
+    Has(Field),
+    Compare(Field, Comparison),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Field {
+    Class,
+    ObjectPath,
+    PackageName,
+    PackagePath,
+    AssetName,
+    Tag(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Comparison {
+    Eq(String),
+    Ne(String),
+    Glob(Regex),
+    Regex(Regex),
+    Lt(f64),
+    Le(f64),
+    Gt(f64),
+    Ge(f64),
+    /// `start..end`, or `start..=end` when `inclusive`.
+    Range {
+        start: f64,
+        end: f64,
+        inclusive: bool,
+    },
+    Contains(String),
+}
+
+impl Field {
+    fn parse(word: &str) -> Option<Self> {
+        if let Some(key) = word.strip_prefix("tag.") {
+            return (!key.is_empty()).then(|| Field::Tag(key.to_string()));
+        }
+        Some(match word {
+            "class" => Field::Class,
+            "path" => Field::ObjectPath,
+            "package" => Field::PackageName,
+            "folder" => Field::PackagePath,
+            "name" => Field::AssetName,
+            _ => return None,
+        })
+    }
+
+    pub fn get<'a>(&self, asset: &'a ResolvedAssetData) -> Option<&'a str> {
+        Some(match self {
+            Field::Class => &asset.asset_class,
+            Field::ObjectPath => &asset.object_path,
+            Field::PackageName => &asset.package_name,
+            Field::PackagePath => &asset.package_path,
+            Field::AssetName => &asset.asset_name,
+            Field::Tag(key) => return asset.tag(key),
+        })
+    }
+}
+
+impl Comparison {
+    fn matches(&self, value: &str) -> bool {
+        let number = || value.trim().parse::<f64>().ok();
+        match self {
+            Comparison::Eq(s) => value.eq_ignore_ascii_case(s),
+            Comparison::Ne(s) => !value.eq_ignore_ascii_case(s),
+            Comparison::Glob(re) | Comparison::Regex(re) => re.is_match(value),
+            Comparison::Lt(n) => number().is_some_and(|v| v < *n),
+            Comparison::Le(n) => number().is_some_and(|v| v <= *n),
+            Comparison::Gt(n) => number().is_some_and(|v| v > *n),
+            Comparison::Ge(n) => number().is_some_and(|v| v >= *n),
+            Comparison::Range {
+                start,
+                end,
+                inclusive,
+            } => number().is_some_and(|v| v >= *start && (v < *end || (*inclusive && v == *end))),
+            Comparison::Contains(item) => value
+                .split(|c: char| c == ',' || "()[]{}".contains(c) || c.is_whitespace())
+                .any(|i| i.eq_ignore_ascii_case(item)),
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(expr: &str) -> EResult<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?,
+            pos: 0,
+            len: expr.len(),
+        };
+        let query = parser.or()?;
+        match parser.tokens.get(parser.pos) {
+            Some((at, token)) => Err(eyre!("unexpected {} at column {}", token, at + 1)),
+            None => Ok(query),
+        }
+    }
+
+    pub fn matches(&self, asset: &ResolvedAssetData) -> bool {
+        match self {
+            Query::And(a, b) => a.matches(asset) && b.matches(asset),
+            Query::Or(a, b) => a.matches(asset) || b.matches(asset),
+            Query::Not(q) => !q.matches(asset),
+            Query::Has(field) => field.get(asset).is_some(),
+            Query::Compare(field, comparison) => field
+                .get(asset)
+                .is_some_and(|value| comparison.matches(value)),
+        }
+    }
+
+    /// The tag keys the query refers to, in order of first mention.
+    pub fn tag_keys(&self) -> Vec<&str> {
+        fn walk<'a>(query: &'a Query, keys: &mut Vec<&'a str>) {
+            match query {
+                Query::And(a, b) | Query::Or(a, b) => {
+                    walk(a, keys);
+                    walk(b, keys);
+                }
+                Query::Not(q) => walk(q, keys),
+                Query::Has(Field::Tag(key)) | Query::Compare(Field::Tag(key), _) => {
+                    if !keys.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+                        keys.push(key);
+                    }
+                }
+                Query::Has(_) | Query::Compare(..) => {}
+            }
+        }
+        let mut keys = vec![];
+        walk(self, &mut keys);
+        keys
+    }
+}
+
+impl AssetRegistry {
+    /// The assets matching a [`Query`] expression, in registry order.
+    pub fn query(&self, expr: &str) -> EResult<Vec<ResolvedAssetData>> {
+        self.assets_matching(&Query::parse(expr)?)
+    }
+
+    #[instrument(name = "AssetRegistry_assets_matching", skip_all)]
+    pub fn assets_matching(&self, query: &Query) -> EResult<Vec<ResolvedAssetData>> {
+        let mut matches = vec![];
+        for asset in &self.assets.assets {
+            let asset = asset.resolve(&self.names, &self.store)?;
+            if query.matches(&asset) {
+                matches.push(asset);
+            }
+        }
+        debug!(matches = matches.len());
+        Ok(matches)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    LParen,
+    RParen,
+    /// An operator made of punctuation.
+    Op(&'static str),
+    Word(String),
+    /// A double-quoted string, never taken for a keyword or field.
+    Str(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::LParen => write!(f, "`(`"),
+            Token::RParen => write!(f, "`)`"),
+            Token::Op(op) => write!(f, "`{op}`"),
+            Token::Word(word) => write!(f, "`{word}`"),
+            Token::Str(s) => write!(f, "{s:?}"),
+        }
+    }
+}
+
+/// Longest first, so that `<=` isn't read as `<` followed by `=`.
+const OPERATORS: &[&str] = &["&&", "||", "!=", "=~", "<=", ">=", "=", "~", "<", ">", "!"];
+
+/// Split `expr` into tokens, each with its byte offset.
+fn tokenize(expr: &str) -> EResult<Vec<(usize, Token)>> {
+    let is_word = |c: char| !c.is_whitespace() && !"()\"=!~<>&|".contains(c);
+    let mut tokens = vec![];
+    let mut chars = expr.char_indices().peekable();
+    while let Some(&(at, c)) = chars.peek() {
+        let rest = &expr[at..];
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' || c == ')' {
+            chars.next();
+            tokens.push((
+                at,
+                if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                },
+            ));
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c @ ('"' | '\\'))) => s.push(c),
+                        Some((i, c)) => {
+                            return Err(eyre!("unknown escape `\\{}` at column {}", c, i + 1))
+                        }
+                        None => return Err(eyre!("unterminated string at column {}", at + 1)),
+                    },
+                    Some((_, c)) => s.push(c),
+                    None => return Err(eyre!("unterminated string at column {}", at + 1)),
+                }
+            }
+            tokens.push((at, Token::Str(s)));
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            for _ in 0..op.len() {
+                chars.next();
+            }
+            tokens.push((at, Token::Op(op)));
+        } else if is_word(c) {
+            // The `=` of an inclusive range such as `1..=10` belongs to the word.
+            let len = rest
+                .char_indices()
+                .find(|&(i, c)| !is_word(c) && !(c == '=' && rest[..i].ends_with("..")))
+                .map_or(rest.len(), |(i, _)| i);
+            for _ in rest[..len].chars() {
+                chars.next();
+            }
+            tokens.push((at, Token::Word(rest[..len].to_string())));
+        } else {
+            return Err(eyre!("unexpected `{}` at column {}", c, at + 1));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over `or := and ("or" and)*`, `and := not ("and" not)*`,
+/// `not := "not" not | "(" or ")" | field [comparison]`.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Length of the expression, for errors at its end.
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn next(&mut self, what: &str) -> EResult<(usize, Token)> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| eyre!("expected {} at column {}", what, self.len + 1))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    /// Consume the next token if it is the keyword `keyword` or the operator `op`.
+    fn eat(&mut self, keyword: &str, op: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Word(word)) => word.eq_ignore_ascii_case(keyword),
+            Some(Token::Op(o)) => *o == op,
+            _ => false,
+        };
+        self.pos += found as usize;
+        found
+    }
+
+    fn or(&mut self) -> EResult<Query> {
+        let mut query = self.and()?;
+        while self.eat("or", "||") {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> EResult<Query> {
+        let mut query = self.not()?;
+        while self.eat("and", "&&") {
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> EResult<Query> {
+        if self.eat("not", "!") {
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        let (at, token) = self.next("a field or `(`")?;
+        let field = match token {
+            Token::LParen => {
+                let query = self.or()?;
+                match self.next("`)`")? {
+                    (_, Token::RParen) => return Ok(query),
+                    (at, token) => {
+                        return Err(eyre!(
+                            "expected `)` but found {} at column {}",
+                            token,
+                            at + 1
+                        ))
+                    }
+                }
+            }
+            Token::Word(word) => Field::parse(&word)
+                .ok_or_else(|| eyre!("unknown field `{}` at column {}", word, at + 1))?,
+            token => {
+                return Err(eyre!(
+                    "expected a field but found {} at column {}",
+                    token,
+                    at + 1
+                ))
+            }
+        };
+
+        let op = match self.peek() {
+            Some(Token::Op(op)) if !matches!(*op, "&&" | "||" | "!") => *op,
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("in") => "in",
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("contains") => "contains",
+            _ => return Ok(Query::Has(field)),
+        };
+        self.pos += 1;
+        let (at, value) = match self.next("a value")? {
+            (at, Token::Word(value) | Token::Str(value)) => (at, value),
+            (at, token) => {
+                return Err(eyre!(
+                    "expected a value but found {} at column {}",
+                    token,
+                    at + 1
+                ))
+            }
+        };
+        let number = || {
+            value.parse::<f64>().map_err(|_| {
+                eyre!(
+                    "expected a number but found `{}` at column {}",
+                    value,
+                    at + 1
+                )
+            })
+        };
+        let comparison = match op {
+            "=" => Comparison::Eq(value),
+            "!=" => Comparison::Ne(value),
+            "~" => Comparison::Glob(glob(&value)?),
+            "=~" => Comparison::Regex(
+                Regex::new(&value)
+                    .map_err(|e| eyre!("invalid regex at column {}: {}", at + 1, e))?,
+            ),
+            "<" => Comparison::Lt(number()?),
+            "<=" => Comparison::Le(number()?),
+            ">" => Comparison::Gt(number()?),
+            ">=" => Comparison::Ge(number()?),
+            "in" => {
+                let (start, end, inclusive) = match value.split_once("..=") {
+                    Some((start, end)) => (start, end, true),
+                    None => match value.split_once("..") {
+                        Some((start, end)) => (start, end, false),
+                        None => {
+                            return Err(eyre!(
+                                "expected a range such as `1..10` but found `{}` at column {}",
+                                value,
+                                at + 1
+                            ))
+                        }
+                    },
+                };
+                let bound = |s: &str| {
+                    s.parse::<f64>().map_err(|_| {
+                        eyre!("expected a number but found `{}` at column {}", s, at + 1)
+                    })
+                };
+                Comparison::Range {
+                    start: bound(start)?,
+                    end: bound(end)?,
+                    inclusive,
+                }
+            }
+            "contains" => Comparison::Contains(value),
+            _ => unreachable!("`{op}` is not a comparison"),
+        };
+        Ok(Query::Compare(field, comparison))
+    }
+}
+
+/// Compile a glob into a case-insensitive regex matching the whole string.
+fn glob(pattern: &str) -> EResult<Regex> {
+    let mut re = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                re.push_str(".*");
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    re.push('$');
+    Ok(RegexBuilder::new(&re).case_insensitive(true).build()?)
+}
+
+/// Format assets as a table of object path, class and the given tags, padded to align.
+pub fn to_table(assets: &[ResolvedAssetData], tag_keys: &[&str]) -> String {
+    let mut rows = vec![["path", "class"]
+        .into_iter()
+        .chain(tag_keys.iter().copied())
+        .map(str::to_string)
+        .collect::<Vec<_>>()];
+    for asset in assets {
+        let mut row = vec![asset.object_path.clone(), asset.asset_class.clone()];
+        row.extend(
+            tag_keys
+                .iter()
+                .map(|key| asset.tag(key).unwrap_or_default().to_string()),
+        );
+        rows.push(row);
+    }
+    let widths = (0..rows[0].len())
+        .map(|i| rows.iter().map(|row| row[i].chars().count()).max().unwrap())
+        .collect::<Vec<_>>();
+    let mut table = String::new();
+    for row in &rows {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(&widths) {
+            write!(line, "{cell:<width$}  ").unwrap();
+        }
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// The assets as a JSON array of objects with their names and a `tags` object.
+pub fn to_json(assets: &[ResolvedAssetData]) -> String {
+    let mut json = String::from("[");
+    for (i, asset) in assets.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        json.push_str("\n  {");
+        for (key, value) in [
+            ("object_path", &asset.object_path),
+            ("package_path", &asset.package_path),
+            ("asset_class", &asset.asset_class),
+            ("package_name", &asset.package_name),
+            ("asset_name", &asset.asset_name),
+        ] {
+            write!(json, "\"{key}\": ").unwrap();
+            write_json_string(&mut json, value);
+            json.push_str(", ");
+        }
+        json.push_str("\"tags\": {");
+        for (j, (key, value)) in asset.tags.iter().enumerate() {
+            if j > 0 {
+                json.push_str(", ");
+            }
+            write_json_string(&mut json, key);
+            json.push_str(": ");
+            write_json_string(&mut json, value);
+        }
+        json.push_str("}}");
+    }
+    json.push_str("\n]\n");
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{RegistryBuilder, TagValue};
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/Weapons/Rifles/BP_Rifle.BP_Rifle", "Blueprint")
+            .tag("ParentClass", TagValue::Name("BP_GunBase".to_string()))
+            .tag("ChunkIDs", "(0,3,5)")
+            .tag("Damage", "25.5");
+        builder
+            .add_asset("/Game/Weapons/BP_Pistol.BP_Pistol", "Blueprint")
+            .tag("ParentClass", TagValue::Name("BP_GunBase".to_string()))
+            .tag("ChunkIDs", "(1)")
+            .tag("Damage", "10");
+        builder
+            .add_asset("/Game/Weapons/T_Rifle.T_Rifle", "Texture2D")
+            .tag("Damage", "high");
+        builder.add_asset("/Game/Maps/Arena.Arena", "World");
+        builder.build().unwrap()
+    }
+
+    fn query(expr: &str) -> Vec<String> {
+        fixture()
+            .query(expr)
+            .unwrap()
+            .into_iter()
+            .map(|a| a.asset_name)
+            .collect()
+    }
+
+    #[test]
+    fn test_query() {
+        assert_eq!(
+            query(
+                r#"class = blueprint and path ~ "/Game/Weapons/**"
+                   and tag.ParentClass = BP_GunBase and tag.ChunkIDs contains 3"#
+            ),
+            ["BP_Rifle"]
+        );
+        assert_eq!(query("path ~ /game/weapons/*"), ["BP_Pistol", "T_Rifle"]);
+        assert_eq!(
+            query("name =~ ^BP_ && !(package ~ **/Rifles/**)"),
+            ["BP_Pistol"]
+        );
+        assert_eq!(query("tag.Damage >= 10"), ["BP_Rifle", "BP_Pistol"]);
+        assert_eq!(query("tag.Damage in 10..25.5"), ["BP_Pistol"]);
+        assert_eq!(query("tag.Damage in 10..=25.5"), ["BP_Rifle", "BP_Pistol"]);
+        assert_eq!(query("not tag.Damage"), ["Arena"]);
+        assert_eq!(query("tag.damage != high"), ["BP_Rifle", "BP_Pistol"]);
+        assert_eq!(
+            query("class = World or folder = /Game/Weapons and tag.Damage < 20"),
+            ["BP_Pistol", "Arena"]
+        );
+        assert_eq!(query(r#"tag.ChunkIDs = "(1)""#), ["BP_Pistol"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |expr: &str| Query::parse(expr).unwrap_err().to_string();
+        assert_eq!(err("colour = red"), "unknown field `colour` at column 1");
+        assert_eq!(err("class ="), "expected a value at column 8");
+        assert_eq!(err("(class = A"), "expected `)` at column 11");
+        assert_eq!(err("class = A B"), "unexpected `B` at column 11");
+        assert_eq!(
+            err("tag.Size < big"),
+            "expected a number but found `big` at column 12"
+        );
+        assert_eq!(
+            err("tag.Size in 1-2"),
+            "expected a range such as `1..10` but found `1-2` at column 13"
+        );
+        assert_eq!(err("name = \"A"), "unterminated string at column 8");
+    }
+
+    #[test]
+    fn test_output() {
+        let assets = fixture().query("tag.ChunkIDs").unwrap();
+        let query = Query::parse("tag.ChunkIDs contains 1 or tag.chunkids").unwrap();
+        assert_eq!(query.tag_keys(), ["ChunkIDs"]);
+        assert_eq!(
+            to_table(&assets, &query.tag_keys()),
+            "\
+path                                    class      ChunkIDs
+/Game/Weapons/Rifles/BP_Rifle.BP_Rifle  Blueprint  (0,3,5)
+/Game/Weapons/BP_Pistol.BP_Pistol       Blueprint  (1)
+"
+        );
+        assert_eq!(
+            to_json(&assets[1..]),
+            r#"[
+  {"object_path": "/Game/Weapons/BP_Pistol.BP_Pistol", "package_path": "/Game/Weapons", "asset_class": "Blueprint", "package_name": "/Game/Weapons/BP_Pistol", "asset_name": "BP_Pistol", "tags": {"ParentClass": "BP_GunBase", "ChunkIDs": "(1)", "Damage": "10"}}
+]
+"#
+        );
+    }
+}
@@ -1,18 +1,183 @@
-use color_eyre::eyre::Result as EResult;
+use std::io::Read;
+
+use byteorder::{ReadBytesExt, LE};
+use color_eyre::eyre::{eyre, Result as EResult};
+#[cfg(feature = "trace-fields")]
 use tracing::*;
 
+use crate::spans;
+use crate::write::{count_i32, count_u32, WriteError};
+
+pub use ar_derive::Readable;
+
 pub trait Readable<R> {
     fn read(reader: &mut R) -> EResult<Self>
     where
         Self: Sized;
 }
 
-#[must_use]
-#[instrument(name = "read_array", skip(reader, f))]
+macro_rules! readable_int {
+    ($($ty:ty => $read:ident),* $(,)?) => {
+        $(
+            impl<R: Read> Readable<R> for $ty {
+                fn read(reader: &mut R) -> EResult<Self> {
+                    Ok(reader.$read::<LE>()?)
+                }
+            }
+        )*
+    };
+}
+
+readable_int!(u16 => read_u16, u32 => read_u32, u64 => read_u64, i32 => read_i32, i64 => read_i64);
+
+impl<R: Read> Readable<R> for u8 {
+    fn read(reader: &mut R) -> EResult<Self> {
+        Ok(reader.read_u8()?)
+    }
+}
+
+impl<R: Read, const N: usize> Readable<R> for [u8; N] {
+    fn read(reader: &mut R) -> EResult<Self> {
+        let mut bytes = [0; N];
+        reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// An integer type that prefixes arrays with their length.
+pub trait ArrayLen: Sized {
+    /// The number of elements that follow, rejecting negative counts.
+    fn count(self) -> EResult<u32>;
+
+    /// The prefix for `len` elements of `what`.
+    fn from_len(what: &'static str, len: usize) -> Result<Self, WriteError>;
+}
+
+impl ArrayLen for u32 {
+    fn count(self) -> EResult<u32> {
+        Ok(self)
+    }
+
+    fn from_len(what: &'static str, len: usize) -> Result<Self, WriteError> {
+        count_u32(what, len)
+    }
+}
+
+impl ArrayLen for i32 {
+    fn count(self) -> EResult<u32> {
+        u32::try_from(self).map_err(|_| eyre!("negative count {}", self))
+    }
+
+    fn from_len(what: &'static str, len: usize) -> Result<Self, WriteError> {
+        count_i32(what, len)
+    }
+}
+
+#[cfg_attr(
+    feature = "trace-fields",
+    instrument(name = "read_array", skip(reader, f))
+)]
 pub fn read_array<R, T, E>(
     length: u32,
     reader: &mut R,
     f: fn(&mut R) -> Result<T, E>,
 ) -> Result<Vec<T>, E> {
-    (0..length).map(|_| f(reader)).collect()
+    (0..length)
+        .map(|i| spans::index(i as u64, || f(reader)))
+        .collect()
+}
+
+/// A reader that keeps track of its absolute offset, for readers that don't implement `Seek`.
+pub struct Positioned<'r, R> {
+    inner: &'r mut R,
+    pub offset: u64,
+}
+
+impl<'r, R> Positioned<'r, R> {
+    pub fn new(inner: &'r mut R, offset: u64) -> Self {
+        Positioned { inner, offset }
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for Positioned<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::asset_registry_version::AssetRegistryVersion;
+    use crate::write::{SerializedSize, Writable};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Readable, Writable, SerializedSize)]
+    #[magic(start = 0xAAAA_0001, end = 0xAAAA_0002)]
+    struct Derived {
+        kind: u8,
+        #[cond(*kind == 1)]
+        extra: Option<u16>,
+        #[len(i32)]
+        values: Vec<u64>,
+        #[since(AddedDependencyFlags)]
+        flags: u32,
+    }
+
+    fn roundtrip(version: AssetRegistryVersion, value: &Derived) -> Derived {
+        version.scope(|| {
+            let mut buf = vec![];
+            value.write(&mut Cursor::new(&mut buf)).unwrap();
+            assert_eq!(buf.len() as u64, value.serialized_size());
+            Derived::read(&mut Cursor::new(&buf)).unwrap()
+        })
+    }
+
+    #[test]
+    fn test_derive_roundtrip() {
+        for kind in [0, 1] {
+            let value = Derived {
+                kind,
+                extra: (kind == 1).then_some(0x1234),
+                values: vec![1, 2, 3],
+                flags: 7,
+            };
+            assert_eq!(
+                roundtrip(AssetRegistryVersion::LATEST_VERSION, &value),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_derive_since() {
+        let value = Derived {
+            kind: 0,
+            extra: None,
+            values: vec![],
+            flags: 7,
+        };
+        let old = roundtrip(AssetRegistryVersion::AddedCookedMD5Hash, &value);
+        assert_eq!(old, Derived { flags: 0, ..value });
+    }
+
+    #[test]
+    fn test_derive_checks() {
+        let mut buf = vec![];
+        0xDEADu32.write(&mut buf).unwrap();
+        let err = Derived::read(&mut Cursor::new(&buf)).unwrap_err();
+        assert!(err.to_string().contains("start magic mismatch"), "{err}");
+
+        let value = Derived {
+            kind: 0,
+            extra: Some(1),
+            values: vec![],
+            flags: 0,
+        };
+        assert!(value.write(&mut vec![]).is_err());
+    }
 }
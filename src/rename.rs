@@ -0,0 +1,607 @@
+//! Renaming packages and moving folders, rewriting every reference to the moved packages.
+//!
+//! Name table entries are shared between unrelated fields (the same `/Game/Old` may be the package
+//! path of many assets), so references are rewritten field by field, interning new names rather
+//! than editing strings in place. Object names equal to the package's short name (and the
+//! Blueprint generated class and CDO derived from it) follow a change of short name, like they do
+//! when renaming an asset in the editor.
+
+use std::collections::{BTreeSet, HashMap};
+
+use color_eyre::eyre::{eyre, Result as EResult};
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::assets::AssetData;
+use crate::dependencies::{EDependencyProperty, FAssetIdentifier, FDependency, FDependsNode};
+use crate::names_batch::NamesBuilder;
+use crate::store_data::StoreData;
+use crate::unreal_types::*;
+
+/// Whether to leave `ObjectRedirector` entries at the old locations.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum RedirectorPolicy {
+    Skip,
+    /// Add an `ObjectRedirector` asset at every moved object path and, when the new package has a
+    /// dependency node, a node for the old package hard-depending on it.
+    Create,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct RenameReport {
+    /// `(old, new)` package names.
+    pub renamed: Vec<(String, String)>,
+    pub redirectors: usize,
+}
+
+impl AssetRegistry {
+    /// Rename a single package, e.g. `/Game/Old/Thing` to `/Game/New/Thing`.
+    #[instrument(name = "AssetRegistry_rename_package", skip(self))]
+    pub fn rename_package(
+        &mut self,
+        from: &str,
+        to: &str,
+        redirectors: RedirectorPolicy,
+    ) -> EResult<RenameReport> {
+        validate_package_name(to)?;
+        let renamed = self
+            .package_names()?
+            .into_iter()
+            .filter(|p| p == from)
+            .map(|p| (p, to.to_string()))
+            .collect();
+        self.relocate(renamed, redirectors)
+    }
+
+    /// Move every package under the folder `from` (recursively) to the folder `to`.
+    #[instrument(name = "AssetRegistry_move_folder", skip(self))]
+    pub fn move_folder(
+        &mut self,
+        from: &str,
+        to: &str,
+        redirectors: RedirectorPolicy,
+    ) -> EResult<RenameReport> {
+        let from = from.trim_end_matches('/');
+        let to = to.trim_end_matches('/');
+        validate_folder(from)?;
+        validate_folder(to)?;
+        let renamed = self
+            .package_names()?
+            .into_iter()
+            .filter_map(|p| {
+                let rest = p.strip_prefix(from)?.strip_prefix('/')?;
+                let moved = format!("{to}/{rest}");
+                Some((p, moved))
+            })
+            .collect();
+        self.relocate(renamed, redirectors)
+    }
+
+    /// Every package name mentioned by an asset, a dependency node or package data.
+    fn package_names(&self) -> EResult<BTreeSet<String>> {
+        let assets = self.assets.assets.iter().map(|a| a.package_name);
+        let nodes = self
+            .dependencies
+            .nodes
+            .iter()
+            .filter_map(|n| n.identifier.package_name);
+        let packages = self.package_data.packages.iter().map(|p| p.package_name);
+        assets
+            .chain(nodes)
+            .chain(packages)
+            .map(|n| self.names.try_resolve(n))
+            .collect()
+    }
+
+    fn relocate(
+        &mut self,
+        renamed: Vec<(String, String)>,
+        redirectors: RedirectorPolicy,
+    ) -> EResult<RenameReport> {
+        if renamed.is_empty() {
+            return Err(eyre!("no package to rename"));
+        }
+        let existing = self.package_names()?;
+        let relocation = Relocation {
+            packages: renamed.iter().cloned().collect(),
+        };
+        for (old, new) in &renamed {
+            if old == new {
+                return Err(eyre!("cannot rename `{}` to itself", old));
+            }
+            if existing.contains(new) && !relocation.packages.contains_key(new) {
+                return Err(eyre!("cannot rename `{}`: `{}` already exists", old, new));
+            }
+        }
+
+        // Rewrite a copy so that a failure halfway leaves `self` untouched.
+        let mut registry = self.clone();
+        let mut names = NamesBuilder::from_names(std::mem::take(&mut registry.names));
+        let moved_assets = registry
+            .assets
+            .assets
+            .iter()
+            .filter(|a| {
+                names
+                    .names
+                    .resolve(a.package_name)
+                    .is_some_and(|p| relocation.packages.contains_key(&p))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for asset in &mut registry.assets.assets {
+            relocation.asset(&mut names, asset)?;
+        }
+        relocation.store(&mut names, &mut registry.store)?;
+        for node in &mut registry.dependencies.nodes {
+            relocation.identifier(&mut names, &mut node.identifier)?;
+        }
+        for package in &mut registry.package_data.packages {
+            package.package_name = rewrite(&mut names, package.package_name, |p| {
+                relocation.packages.get(p).cloned()
+            })?;
+        }
+
+        let mut report = RenameReport {
+            renamed,
+            redirectors: 0,
+        };
+        if redirectors == RedirectorPolicy::Create {
+            for asset in moved_assets {
+                add_redirector(&mut registry, &mut names, &relocation, asset)?;
+                report.redirectors += 1;
+            }
+        }
+
+        debug!(
+            renamed = report.renamed.len(),
+            redirectors = report.redirectors
+        );
+        registry.names = names.names;
+        *self = registry;
+        Ok(report)
+    }
+}
+
+/// Package names must be absolute and not contain object path separators.
+fn validate_package_name(name: &str) -> EResult<()> {
+    let valid = name.starts_with('/')
+        && !name.ends_with('/')
+        && !name.contains(['.', ':', '\''])
+        && name
+            .rsplit_once('/')
+            .is_some_and(|(parent, _)| !parent.is_empty());
+    match valid {
+        true => Ok(()),
+        false => Err(eyre!("`{}` is not a valid package name", name)),
+    }
+}
+
+/// Folders must be absolute, without empty components or object path separators. Unlike a
+/// package name, a folder may be a mount root such as `/Game`.
+fn validate_folder(folder: &str) -> EResult<()> {
+    let valid = folder
+        .strip_prefix('/')
+        .is_some_and(|rest| rest.split('/').all(|c| !c.is_empty()))
+        && !folder.contains(['.', ':', '\'']);
+    match valid {
+        true => Ok(()),
+        false => Err(eyre!("`{}` is not a valid folder", folder)),
+    }
+}
+
+/// Last path component of a package name.
+fn short_name(package: &str) -> &str {
+    package.rsplit_once('/').map_or(package, |(_, short)| short)
+}
+
+/// Parent folder of a package name.
+fn package_path(package: &str) -> &str {
+    package
+        .rsplit_once('/')
+        .map_or(package, |(parent, _)| parent)
+}
+
+/// Resolve `name`, and intern `f`'s replacement if it has one.
+fn rewrite(
+    names: &mut NamesBuilder,
+    name: FName,
+    f: impl FnOnce(&str) -> Option<String>,
+) -> EResult<FName> {
+    let s = names.names.try_resolve(name)?;
+    Ok(match f(&s) {
+        Some(new) => names.intern(&new)?,
+        None => name,
+    })
+}
+
+struct Relocation {
+    /// Old package name -> new package name.
+    packages: HashMap<String, String>,
+}
+
+impl Relocation {
+    /// New name of an object in `old_package`, if its package's short name changes.
+    fn object_name(&self, old_package: &str, object: &str) -> Option<String> {
+        let old_short = short_name(old_package);
+        let new_short = short_name(self.packages.get(old_package)?);
+        if old_short == new_short {
+            return None;
+        }
+        let class = format!("{old_short}_C");
+        let cdo = format!("Default__{old_short}_C");
+        match object {
+            o if o == old_short => Some(new_short.to_string()),
+            o if o == class => Some(format!("{new_short}_C")),
+            o if o == cdo => Some(format!("Default__{new_short}_C")),
+            _ => None,
+        }
+    }
+
+    /// New object path for `/Package.Object[:SubObject]` (or a bare package name).
+    fn object_path(&self, path: &str) -> Option<String> {
+        let package_end = path.find(['.', ':']).unwrap_or(path.len());
+        let (package, rest) = path.split_at(package_end);
+        let new_package = self.packages.get(package)?;
+        let rest = match rest.strip_prefix('.') {
+            Some(rest) => {
+                let object_end = rest.find([':', '.']).unwrap_or(rest.len());
+                let (object, sub_object) = rest.split_at(object_end);
+                let object = self
+                    .object_name(package, object)
+                    .unwrap_or_else(|| object.to_string());
+                format!(".{object}{sub_object}")
+            }
+            None => rest.to_string(),
+        };
+        Some(format!("{new_package}{rest}"))
+    }
+
+    /// New value for a string that may hold an object path, either bare or as `Class'Path'`.
+    fn reference(&self, s: &str) -> Option<String> {
+        if let Some((class, path)) = s.split_once('\'') {
+            let path = path.strip_suffix('\'')?;
+            return Some(format!("{class}'{}'", self.object_path(path)?));
+        }
+        match s.starts_with('/') {
+            true => self.object_path(s),
+            false => None,
+        }
+    }
+
+    fn asset(&self, names: &mut NamesBuilder, asset: &mut AssetData) -> EResult<()> {
+        let package = names.names.try_resolve(asset.package_name)?;
+        if let Some(new_package) = self.packages.get(&package) {
+            asset.asset_name = rewrite(names, asset.asset_name, |o| self.object_name(&package, o))?;
+            asset.object_path = rewrite(names, asset.object_path, |p| self.object_path(p))?;
+            asset.package_path = names.intern(package_path(new_package))?;
+            asset.package_name = names.intern(new_package)?;
+        }
+        for path in asset.bundles.iter_mut().flat_map(|b| &mut b.bundles) {
+            path.asset_path_name = rewrite(names, path.asset_path_name, |p| self.object_path(p))?;
+        }
+        Ok(())
+    }
+
+    fn store(&self, names: &mut NamesBuilder, store: &mut StoreData) -> EResult<()> {
+        for path in &mut store.numberless_export_paths {
+            let Some(package) = self.export_path(names, path)? else {
+                continue;
+            };
+            if !path.is_numberless() {
+                return Err(eyre!(
+                    "moving `{}` gives a numbered export path, which a numberless export path cannot hold",
+                    package
+                ));
+            }
+        }
+        for path in &mut store.export_paths {
+            self.export_path(names, path)?;
+        }
+        for name in &mut store.names {
+            *name = rewrite(names, *name, |s| self.reference(s))?;
+        }
+        for name in &mut store.numberless_names {
+            *name = rewrite(names, *name, |s| self.reference(s))?;
+            if name.number != 0 {
+                return Err(eyre!(
+                    "rewritten name value `{}` has a number, which a numberless name cannot hold",
+                    names.names.try_resolve(*name)?
+                ));
+            }
+        }
+        for s in &mut store.ansi_strings {
+            if let Some(new) = self.reference(s) {
+                if !new.is_ascii() {
+                    return Err(eyre!("rewritten ANSI string value `{}` is not ASCII", new));
+                }
+                *s = new;
+            }
+        }
+        for s in &mut store.wide_strings {
+            if let Some(new) = self.reference(s) {
+                *s = new;
+            }
+        }
+        Ok(())
+    }
+
+    /// Point an export path in a moved package at its new location, returning the old package.
+    fn export_path(
+        &self,
+        names: &mut NamesBuilder,
+        path: &mut FAssetRegistryExportPath,
+    ) -> EResult<Option<String>> {
+        let package = names.names.try_resolve(path.package)?;
+        let Some(new_package) = self.packages.get(&package) else {
+            return Ok(None);
+        };
+        path.object = rewrite(names, path.object, |o| self.object_name(&package, o))?;
+        path.package = names.intern(new_package)?;
+        Ok(Some(package))
+    }
+
+    fn identifier(
+        &self,
+        names: &mut NamesBuilder,
+        identifier: &mut FAssetIdentifier,
+    ) -> EResult<()> {
+        let Some(package_name) = identifier.package_name else {
+            return Ok(());
+        };
+        let package = names.names.try_resolve(package_name)?;
+        let Some(new_package) = self.packages.get(&package) else {
+            return Ok(());
+        };
+        if let Some(object) = identifier.object_name {
+            identifier.object_name =
+                Some(rewrite(names, object, |o| self.object_name(&package, o))?);
+        }
+        identifier.package_name = Some(names.intern(new_package)?);
+        Ok(())
+    }
+}
+
+/// Leave an `ObjectRedirector` at a moved asset's old location.
+fn add_redirector(
+    registry: &mut AssetRegistry,
+    names: &mut NamesBuilder,
+    relocation: &Relocation,
+    old: AssetData,
+) -> EResult<()> {
+    let old_package = names.names.try_resolve(old.package_name)?;
+    let new_package = names.intern(&relocation.packages[&old_package])?;
+    registry.assets.assets.push(AssetData {
+        asset_class: names.intern("ObjectRedirector")?,
+        tags: FPartialMapHandle::default().to_int(),
+        bundles: vec![],
+        ..old
+    });
+
+    let nodes = &mut registry.dependencies.nodes;
+    let is_package =
+        |n: &FDependsNode, package: FName| n.identifier == FAssetIdentifier::package(package);
+    let Some(target) = nodes.iter().position(|n| is_package(n, new_package)) else {
+        return Ok(());
+    };
+    if nodes.iter().any(|n| is_package(n, old.package_name)) {
+        return Ok(());
+    }
+    let source = nodes.len() as i32;
+    nodes.push(FDependsNode {
+        identifier: FAssetIdentifier::package(old.package_name),
+        package_dependencies: vec![FDependency {
+            node: target as i32,
+            properties: EDependencyProperty::HARD | EDependencyProperty::GAME,
+        }],
+        ..Default::default()
+    });
+    nodes[target].referencers.push(source);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{RegistryBuilder, TagValue};
+    use crate::dependencies::EDependencyCategory;
+    use crate::read::Readable;
+    use crate::store_data::StoreValue;
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/Old/Thing.Thing", "Blueprint")
+            .tag(
+                "GeneratedClass",
+                TagValue::ExportPath {
+                    class: "BlueprintGeneratedClass".to_string(),
+                    object_path: "/Game/Old/Thing.Thing_C".to_string(),
+                },
+            )
+            .tag("Parent", "Blueprint'/Game/Old/Base.Base'");
+        builder
+            .add_asset("/Game/Old/Base.Base", "Blueprint")
+            .bundle("Client", &["/Game/Old/Thing.Thing:Sub"]);
+        builder.add_asset("/Game/Keep/K.K", "World");
+        for (from, to) in [
+            ("/Game/Keep/K", "/Game/Old/Thing"),
+            ("/Game/Old/Thing", "/Game/Old/Base"),
+        ] {
+            builder.add_dependency(
+                from,
+                to,
+                EDependencyCategory::Package,
+                EDependencyProperty::HARD,
+            );
+        }
+        builder.add_package_data("/Game/Old/Thing", 1);
+        builder.add_package_data("/Game/Keep/K", 2);
+        builder.build().unwrap()
+    }
+
+    fn resolve(registry: &AssetRegistry, name: FName) -> String {
+        registry.names.try_resolve(name).unwrap()
+    }
+
+    fn roundtrip(registry: &AssetRegistry) -> AssetRegistry {
+        let mut buf = vec![];
+        registry.write(&mut Cursor::new(&mut buf)).unwrap();
+        AssetRegistry::read(&mut Cursor::new(&buf)).unwrap()
+    }
+
+    #[test]
+    fn test_rename_package() {
+        let mut registry = fixture();
+        let report = registry
+            .rename_package(
+                "/Game/Old/Thing",
+                "/Game/New/Thing2",
+                RedirectorPolicy::Create,
+            )
+            .unwrap();
+        assert_eq!(
+            report,
+            RenameReport {
+                renamed: vec![(
+                    "/Game/Old/Thing".to_string(),
+                    "/Game/New/Thing2".to_string()
+                )],
+                redirectors: 1,
+            }
+        );
+
+        let thing =
+            &registry.assets.assets[registry.find_asset("/Game/New/Thing2.Thing2").unwrap()];
+        assert_eq!(resolve(&registry, thing.package_name), "/Game/New/Thing2");
+        assert_eq!(resolve(&registry, thing.package_path), "/Game/New");
+        assert_eq!(resolve(&registry, thing.asset_name), "Thing2");
+        let StoreValue::NumberlessExportPath(generated) =
+            &registry.asset_tags("/Game/New/Thing2.Thing2").unwrap()[0].1
+        else {
+            panic!("expected an export path");
+        };
+        assert_eq!(resolve(&registry, generated.package), "/Game/New/Thing2");
+        assert_eq!(resolve(&registry, generated.object), "Thing2_C");
+
+        let base = &registry.assets.assets[registry.find_asset("/Game/Old/Base.Base").unwrap()];
+        assert_eq!(
+            resolve(&registry, base.bundles[0].bundles[0].asset_path_name),
+            "/Game/New/Thing2.Thing2"
+        );
+
+        let redirector =
+            &registry.assets.assets[registry.find_asset("/Game/Old/Thing.Thing").unwrap()];
+        assert_eq!(
+            resolve(&registry, redirector.asset_class),
+            "ObjectRedirector"
+        );
+
+        let nodes = registry
+            .dependencies
+            .nodes
+            .iter()
+            .map(|n| n.identifier.resolve(&registry.names).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            nodes,
+            vec![
+                "/Game/New/Thing2",
+                "/Game/Old/Base",
+                "/Game/Keep/K",
+                "/Game/Old/Thing"
+            ]
+        );
+        assert_eq!(registry.dependencies.nodes[0].referencers, vec![2, 3]);
+        assert_eq!(
+            resolve(&registry, registry.package_data.packages[0].package_name),
+            "/Game/New/Thing2"
+        );
+        assert_eq!(roundtrip(&registry), registry);
+    }
+
+    #[test]
+    fn test_move_folder() {
+        let mut registry = fixture();
+        let report = registry
+            .move_folder("/Game/Old/", "/Game/New", RedirectorPolicy::Skip)
+            .unwrap();
+        assert_eq!(report.renamed.len(), 2);
+        assert_eq!(report.redirectors, 0);
+
+        let object_paths = registry
+            .assets
+            .assets
+            .iter()
+            .map(|a| resolve(&registry, a.object_path))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            object_paths,
+            vec![
+                "/Game/New/Thing.Thing",
+                "/Game/New/Base.Base",
+                "/Game/Keep/K.K"
+            ]
+        );
+        assert_eq!(
+            registry.asset_tags("/Game/New/Thing.Thing").unwrap()[1].1,
+            StoreValue::AnsiString("Blueprint'/Game/New/Base.Base'".to_string())
+        );
+        let base = &registry.assets.assets[1];
+        assert_eq!(resolve(&registry, base.package_path), "/Game/New");
+        assert_eq!(
+            resolve(&registry, base.bundles[0].bundles[0].asset_path_name),
+            "/Game/New/Thing.Thing"
+        );
+        assert_eq!(roundtrip(&registry), registry);
+
+        // Mount roots are folders too.
+        let report = registry
+            .move_folder("/Game", "/Mod", RedirectorPolicy::Skip)
+            .unwrap();
+        assert_eq!(report.renamed.len(), 3);
+        assert_eq!(
+            resolve(&registry, registry.assets.assets[2].object_path),
+            "/Mod/Keep/K.K"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut registry = fixture();
+        let original = registry.clone();
+        assert!(registry
+            .rename_package("/Game/Missing", "/Game/New", RedirectorPolicy::Skip)
+            .is_err());
+        assert!(registry
+            .rename_package("/Game/Old/Thing", "/Game/Keep/K", RedirectorPolicy::Skip)
+            .is_err());
+        assert!(registry
+            .rename_package(
+                "/Game/Old/Thing",
+                "/Game/New/Thing.Thing",
+                RedirectorPolicy::Skip
+            )
+            .is_err());
+        assert!(registry
+            .move_folder("/Game/Ol", "/Game/New", RedirectorPolicy::Skip)
+            .is_err());
+        for (from, to) in [
+            ("Game/Old", "/Game/New"),
+            ("/Game/Old", "/Game//New"),
+            ("/Game/Old", "/Game/New.New"),
+            ("/", "/Game/New"),
+            ("/Game/Old", ""),
+        ] {
+            assert!(registry
+                .move_folder(from, to, RedirectorPolicy::Skip)
+                .is_err());
+        }
+        assert_eq!(registry, original);
+    }
+}
@@ -1,11 +1,12 @@
 use std::io::{Read, Write};
 
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use color_eyre::eyre::{Result as EResult};
+use color_eyre::eyre::Result as EResult;
+#[cfg(feature = "trace-fields")]
 use tracing::*;
 
 use crate::read::Readable;
-use crate::write::Writable;
+use crate::write::{SerializedSize, Writable, WriteError};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct SerializedNameHeader {
@@ -16,6 +17,23 @@ pub struct SerializedNameHeader {
 }
 
 impl SerializedNameHeader {
+    /// The longest name a header can describe; the top bit of its `u16` is the UTF-16 flag.
+    pub const MAX_LEN: u16 = 0x7FFF;
+
+    /// Header for a name string: ANSI if the string is pure ASCII, UTF-16 otherwise. Fails if
+    /// the string is longer than [`SerializedNameHeader::MAX_LEN`].
+    pub fn for_string(s: &str) -> Result<Self, WriteError> {
+        let is_utf16 = !s.is_ascii();
+        let len = match is_utf16 {
+            true => s.encode_utf16().count(),
+            false => s.len(),
+        };
+        match u16::try_from(len) {
+            Ok(len) if len <= Self::MAX_LEN => Ok(SerializedNameHeader { is_utf16, len }),
+            _ => Err(WriteError::NameTooLong { len: len as u64 }),
+        }
+    }
+
     pub fn n_bytes(&self) -> u32 {
         match self.is_utf16 {
             true => self.len as u32 * std::mem::size_of::<u16>() as u32,
@@ -25,8 +43,17 @@ impl SerializedNameHeader {
 }
 
 impl<W: Write> Writable<W> for SerializedNameHeader {
-    #[instrument(name = "SerializedNameHeader_write", skip_all)]
+    #[cfg_attr(
+        feature = "trace-fields",
+        instrument(name = "SerializedNameHeader_write", skip_all)
+    )]
     fn write(&self, writer: &mut W) -> EResult<()> {
+        if self.len > Self::MAX_LEN {
+            return Err(WriteError::NameTooLong {
+                len: self.len as u64,
+            }
+            .into());
+        }
         let b0 = ((self.is_utf16 as u16) << 7 | self.len >> 8) as u8;
         let b1 = self.len as u8;
         writer.write_u8(b0)?;
@@ -35,8 +62,17 @@ impl<W: Write> Writable<W> for SerializedNameHeader {
     }
 }
 
+impl SerializedSize for SerializedNameHeader {
+    fn serialized_size(&self) -> u64 {
+        2
+    }
+}
+
 impl<R: Read> Readable<R> for SerializedNameHeader {
-    #[instrument(name = "SerializedNameHeader_read", skip_all)]
+    #[cfg_attr(
+        feature = "trace-fields",
+        instrument(name = "SerializedNameHeader_read", skip_all)
+    )]
     fn read(reader: &mut R) -> EResult<Self> {
         let packed = reader.read_u16::<LE>()?;
         let bytes = packed.to_le_bytes();
@@ -68,4 +104,35 @@ mod tests {
         assert_eq!(header.len, len);
         assert_eq!(header.is_utf16, is_utf16);
     }
+
+    #[test]
+    fn test_too_long() {
+        let err = SerializedNameHeader {
+            len: 0x8000,
+            is_utf16: false,
+        }
+        .write(&mut Cursor::new(vec![]))
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<WriteError>(),
+            Some(&WriteError::NameTooLong { len: 0x8000 })
+        );
+
+        let long = "ü".repeat(0x8000);
+        assert_eq!(
+            SerializedNameHeader::for_string(&long[..0xFFFE]),
+            Ok(SerializedNameHeader {
+                len: 0x7FFF,
+                is_utf16: true
+            })
+        );
+        assert_eq!(
+            SerializedNameHeader::for_string(&long),
+            Err(WriteError::NameTooLong { len: 0x8000 })
+        );
+        assert_eq!(
+            SerializedNameHeader::for_string(&"a".repeat(0x1_0001)),
+            Err(WriteError::NameTooLong { len: 0x1_0001 })
+        );
+    }
 }
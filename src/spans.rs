@@ -0,0 +1,450 @@
+//! Byte ranges of every parsed structure and field.
+//!
+//! Readers describe what they read by wrapping each field in [`field`] (and each array element in
+//! [`index`], which [`read_array`](crate::read::read_array) does automatically). When a recording
+//! is in progress, every such scope becomes a [`Span`] with its path, e.g.
+//! `store.pairs[42].key.index`. Otherwise the scopes cost a thread-local lookup and nothing else.
+//!
+//! Offsets come from the [`Tracked`] reader driving the parse, so they are absolute even when the
+//! parse skips around the file.
+
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::io::{Cursor, Read};
+use std::ops::Range;
+
+use color_eyre::eyre::Result as EResult;
+
+use crate::asset_registry::AssetRegistry;
+use crate::lenient::{Diagnostic, LenientReader};
+use crate::read::Readable;
+
+/// The bytes read for one structure or field.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Span {
+    pub range: Range<u64>,
+    pub path: String,
+}
+
+/// Every span of a parse, sorted by start offset with enclosing spans before the spans they
+/// contain.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct SpanMap {
+    pub spans: Vec<Span>,
+    /// For each span, the index of the last span before it that contains its start: its parent
+    /// when spans nest, which they only fail to do around the seeks of a lenient parse.
+    parents: Vec<Option<usize>>,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Segment {
+    Field(&'static str),
+    Index(u64),
+}
+
+#[derive(Debug, Default)]
+struct Recorder {
+    offset: u64,
+    path: Vec<Segment>,
+    spans: Vec<Span>,
+}
+
+impl Recorder {
+    fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in &self.path {
+            match segment {
+                Segment::Field(name) if path.is_empty() => path.push_str(name),
+                Segment::Field(name) => write!(path, ".{name}").unwrap(),
+                Segment::Index(i) => write!(path, "[{i}]").unwrap(),
+            }
+        }
+        path
+    }
+}
+
+thread_local! {
+    static RECORDER: RefCell<Option<Recorder>> = const { RefCell::new(None) };
+}
+
+fn with_recorder<T>(f: impl FnOnce(&mut Recorder) -> T) -> Option<T> {
+    RECORDER.with(|r| r.borrow_mut().as_mut().map(f))
+}
+
+/// A scope that records its span when dropped.
+pub(crate) struct Entered {
+    start: Option<u64>,
+}
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        let Some(start) = self.start else {
+            return;
+        };
+        // Failed reads are recorded too; the bytes they consumed are often the interesting ones.
+        with_recorder(|r| {
+            if r.offset > start {
+                let path = r.path();
+                r.spans.push(Span {
+                    range: start..r.offset,
+                    path,
+                });
+            }
+            r.path.pop();
+        });
+    }
+}
+
+impl Entered {
+    /// Record the `i`th element of this scope for bytes that were read in bulk; `range` is
+    /// relative to the start of the scope.
+    pub(crate) fn index_at(&self, i: u64, range: Range<u64>) {
+        let Some(start) = self.start else {
+            return;
+        };
+        with_recorder(|r| {
+            if range.end > range.start {
+                r.path.push(Segment::Index(i));
+                let path = r.path();
+                r.path.pop();
+                r.spans.push(Span {
+                    range: start + range.start..start + range.end,
+                    path,
+                });
+            }
+        });
+    }
+}
+
+fn enter_segment(segment: Segment) -> Entered {
+    Entered {
+        start: with_recorder(|r| {
+            r.path.push(segment);
+            r.offset
+        }),
+    }
+}
+
+/// Enter the field `name` of the current structure until the returned guard is dropped, for
+/// fields whose reading doesn't fit in a closure.
+pub(crate) fn enter(name: &'static str) -> Entered {
+    enter_segment(Segment::Field(name))
+}
+
+/// Read the field `name` of the current structure.
+pub(crate) fn field<T, E>(name: &'static str, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    let _entered = enter(name);
+    f()
+}
+
+/// Read the `i`th element of the current array.
+pub(crate) fn index<T, E>(i: u64, f: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+    let _entered = enter_segment(Segment::Index(i));
+    f()
+}
+
+/// A cursor that reports its position to the span recorder.
+pub(crate) struct Tracked<'a> {
+    cursor: Cursor<&'a [u8]>,
+}
+
+impl<'a> Tracked<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Tracked {
+            cursor: Cursor::new(data),
+        }
+    }
+
+    pub(crate) fn position(&self) -> u64 {
+        self.cursor.position()
+    }
+
+    pub(crate) fn set_position(&mut self, position: u64) {
+        self.cursor.set_position(position);
+        with_recorder(|r| r.offset = position);
+    }
+}
+
+impl Read for Tracked<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.cursor.read(buf)?;
+        let position = self.cursor.position();
+        with_recorder(|r| r.offset = position);
+        Ok(n)
+    }
+}
+
+impl SpanMap {
+    /// Run `f` with span recording enabled, collecting the spans of everything it reads through a
+    /// [`Tracked`] reader.
+    fn record<T>(f: impl FnOnce() -> T) -> (T, SpanMap) {
+        let previous = RECORDER.with(|r| r.replace(Some(Recorder::default())));
+        let result = f();
+        let recorder = RECORDER.with(|r| r.replace(previous)).unwrap_or_default();
+        (result, SpanMap::new(recorder.spans))
+    }
+
+    fn new(mut spans: Vec<Span>) -> Self {
+        // Spans are recorded as they end, so children come before their parents. A parent's path is
+        // a prefix of its children's, which orders spans covering the same bytes.
+        spans.sort_by(|a, b| {
+            a.range
+                .start
+                .cmp(&b.range.start)
+                .then(b.range.end.cmp(&a.range.end))
+                .then(a.path.len().cmp(&b.path.len()))
+        });
+        let mut parents = Vec::with_capacity(spans.len());
+        // Spans that may contain the start of a later span. Starts only grow, so a span ending at
+        // or before one start contains no later one.
+        let mut open: Vec<usize> = vec![];
+        for (i, span) in spans.iter().enumerate() {
+            while open
+                .last()
+                .is_some_and(|&j| spans[j].range.end <= span.range.start)
+            {
+                open.pop();
+            }
+            parents.push(open.last().copied());
+            open.push(i);
+        }
+        SpanMap { spans, parents }
+    }
+
+    /// The spans that may contain `offset`, last first: the last span starting at or before
+    /// `offset` and its chain of `parents`. Every span containing `offset` is on
+    /// it, as it contains the start of each later span of the chain.
+    fn candidates(&self, offset: u64) -> impl Iterator<Item = &Span> {
+        let last = self
+            .spans
+            .partition_point(|s| s.range.start <= offset)
+            .checked_sub(1);
+        std::iter::successors(last, |&i| self.parents[i]).map(|i| &self.spans[i])
+    }
+
+    /// The innermost span containing `offset`, i.e. the field at that offset.
+    pub fn span_at(&self, offset: u64) -> Option<&Span> {
+        self.candidates(offset).find(|s| s.range.contains(&offset))
+    }
+
+    /// All spans containing `offset`, outermost first.
+    pub fn spans_at(&self, offset: u64) -> Vec<&Span> {
+        let mut spans = self
+            .candidates(offset)
+            .filter(|s| s.range.contains(&offset))
+            .collect::<Vec<_>>();
+        spans.reverse();
+        spans
+    }
+
+    /// The spans as a JSON array of `{"start": .., "end": .., "path": ".."}` objects.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[");
+        for (i, span) in self.spans.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "\n  {{\"start\": {}, \"end\": {}, \"path\": ",
+                span.range.start, span.range.end
+            )
+            .unwrap();
+            write_json_string(&mut json, &span.path);
+            json.push('}');
+        }
+        json.push_str("\n]\n");
+        json
+    }
+}
+
+/// Append `s` as a JSON string literal. `<` is escaped as well so the result can be embedded in
+/// HTML.
+pub(crate) fn write_json_string(json: &mut String, s: &str) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() || c == '<' => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+impl AssetRegistry {
+    /// Parse `data`, recording the span of every structure and field. The spans read before a
+    /// failure are returned too.
+    pub fn read_with_spans(data: &[u8]) -> (EResult<AssetRegistry>, SpanMap) {
+        SpanMap::record(|| AssetRegistry::read(&mut Tracked::new(data)))
+    }
+
+    /// [`AssetRegistry::read_lenient`], recording the span of every structure and field.
+    pub fn read_lenient_with_spans(data: &[u8]) -> ((AssetRegistry, Vec<Diagnostic>), SpanMap) {
+        SpanMap::record(|| {
+            let reader = LenientReader::read(data);
+            (reader.registry, reader.recovery.diagnostics)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::RegistryBuilder;
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn bytes() -> Vec<u8> {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/A.A", "Blueprint")
+            .tag("Color", "red")
+            .tag("Size", "big");
+        builder.add_package_data("/Game/A", 1);
+        let mut buf = vec![];
+        builder
+            .build()
+            .unwrap()
+            .write(&mut Cursor::new(&mut buf))
+            .unwrap();
+        buf
+    }
+
+    fn find<'a>(spans: &'a SpanMap, path: &str) -> &'a Span {
+        spans.spans.iter().find(|s| s.path == path).unwrap()
+    }
+
+    #[test]
+    fn test_spans() {
+        let buf = bytes();
+        let (registry, spans) = AssetRegistry::read_with_spans(&buf);
+        let registry = registry.unwrap();
+
+        assert_eq!(find(&spans, "header").range, 0..20);
+        assert_eq!(find(&spans, "header.version").range, 16..20);
+        let pair = find(&spans, "store.numberless_pairs[1]");
+        assert_eq!(pair.range.end - pair.range.start, 8);
+        let key = find(&spans, "store.numberless_pairs[1].key");
+        assert_eq!(key.range, pair.range.start..pair.range.start + 4);
+        assert_eq!(
+            spans.span_at(key.range.start + 4).unwrap().path,
+            "store.numberless_pairs[1].value"
+        );
+        assert_eq!(
+            spans
+                .spans_at(key.range.start)
+                .iter()
+                .map(|s| s.path.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "store",
+                "store.numberless_pairs",
+                "store.numberless_pairs[1]",
+                "store.numberless_pairs[1].key",
+                "store.numberless_pairs[1].key.index"
+            ]
+        );
+
+        let red = find(&spans, "store.ansi_strings[0]");
+        assert_eq!(
+            &buf[red.range.start as usize..red.range.end as usize],
+            b"red\0"
+        );
+        let class = registry.assets.assets[0].asset_class.index;
+        assert_eq!(registry.names.strings[class as usize], "Blueprint");
+        let class = find(&spans, &format!("names.strings[{class}]"));
+        assert_eq!(
+            &buf[class.range.start as usize..class.range.end as usize],
+            b"Blueprint"
+        );
+
+        // Every byte belongs to some top-level section.
+        let top_level = spans
+            .spans
+            .iter()
+            .filter(|s| !s.path.contains(['.', '[']))
+            .collect::<Vec<_>>();
+        assert_eq!(top_level.first().unwrap().range.start, 0);
+        assert_eq!(top_level.last().unwrap().range.end, buf.len() as u64);
+        assert!(top_level
+            .windows(2)
+            .all(|w| w[0].range.end == w[1].range.start));
+    }
+
+    #[test]
+    fn test_span_at() {
+        let buf = bytes();
+        // Make the first name longer than all the strings, so that the names run into the store
+        // and the lenient reader seeks back to it: the spans then overlap without nesting.
+        let mut damaged = buf.clone();
+        let names = u32::from_le_bytes(buf[20..24].try_into().unwrap()) as usize;
+        let header = 36 + 8 * names;
+        damaged[header..header + 2].copy_from_slice(&[0x00, 0x7F]);
+        let (_, spans) = AssetRegistry::read_with_spans(&buf);
+        let ((_, diagnostics), lenient) = AssetRegistry::read_lenient_with_spans(&damaged);
+        assert!(!diagnostics.is_empty());
+        let crosses = |a: &Span, b: &Span| {
+            a.range.start < b.range.start
+                && b.range.start < a.range.end
+                && a.range.end < b.range.end
+        };
+        assert!(lenient
+            .spans
+            .iter()
+            .any(|a| lenient.spans.iter().any(|b| crosses(a, b))));
+        for (spans, buf) in [(spans, &buf), (lenient, &damaged)] {
+            for offset in 0..=buf.len() as u64 {
+                let containing = spans
+                    .spans
+                    .iter()
+                    .filter(|s| s.range.contains(&offset))
+                    .collect::<Vec<_>>();
+                assert_eq!(spans.spans_at(offset), containing, "{offset}");
+                assert_eq!(spans.span_at(offset), containing.last().copied());
+            }
+        }
+    }
+
+    #[test]
+    fn test_spans_of_failed_read() {
+        let buf = bytes();
+        let (registry, spans) = AssetRegistry::read_with_spans(&buf[..buf.len() - 1]);
+        assert!(registry.is_err());
+        assert!(spans.spans.iter().any(|s| s.path == "assets.assets[0]"));
+        assert_eq!(spans.span_at(buf.len() as u64), None);
+        assert!(spans.spans_at(buf.len() as u64).is_empty());
+    }
+
+    #[test]
+    fn test_lenient_spans() {
+        let buf = bytes();
+        let ((_, diagnostics), lenient) = AssetRegistry::read_lenient_with_spans(&buf);
+        assert_eq!(diagnostics, vec![]);
+        let (_, strict) = AssetRegistry::read_with_spans(&buf);
+        assert_eq!(lenient, strict);
+    }
+
+    #[test]
+    fn test_not_recording() {
+        let buf = bytes();
+        AssetRegistry::read(&mut Tracked::new(&buf)).unwrap();
+        let (_, spans) = SpanMap::record(|| ());
+        assert_eq!(spans, SpanMap::default());
+    }
+
+    #[test]
+    fn test_json() {
+        let spans = SpanMap::new(vec![Span {
+            range: 0..4,
+            path: "a[0].\"b\"".to_string(),
+        }]);
+        assert_eq!(
+            spans.to_json(),
+            "[\n  {\"start\": 0, \"end\": 4, \"path\": \"a[0].\\\"b\\\"\"}\n]\n"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Summary statistics of a registry, for spotting bloat between builds.
+//!
+//! [`AssetRegistry::stats`] counts assets by class and folder, measures the name table and store,
+//! finds the most common tags and totals the dependency edges. The byte size of each section is
+//! taken from the [`Section`]s of the file the registry was read from. The
+//! [`Display`][std::fmt::Display] impl prints a plain text report meant to be diffed.
+
+use std::collections::HashMap;
+
+use color_eyre::eyre::Result as EResult;
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::dependencies::EDependencyProperty;
+use crate::lazy_registry::Section;
+use crate::unreal_types::*;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Stats {
+    pub assets: usize,
+    /// Asset count per class, most common first.
+    pub assets_per_class: Vec<(String, usize)>,
+    /// Asset count per top-level folder (mount point plus first folder, e.g. `/Game/Weapons`),
+    /// most common first.
+    pub assets_per_folder: Vec<(String, usize)>,
+    pub names: NameStats,
+    /// Length of each store array, in serialization order.
+    pub store: Vec<(&'static str, usize)>,
+    /// The most frequent tag keys, most common first.
+    pub tag_keys: Vec<(String, usize)>,
+    /// The most frequent tag key-value pairs, most common first.
+    pub tag_values: Vec<((String, String), usize)>,
+    pub average_tags: f64,
+    pub dependencies: DependencyStats,
+    /// Bytes taken by each section in the file.
+    pub sections: Vec<(&'static str, u64)>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct NameStats {
+    pub count: usize,
+    pub ansi: usize,
+    pub wide: usize,
+    /// Bytes of string data, excluding hashes and headers.
+    pub string_bytes: u64,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DependencyStats {
+    pub nodes: usize,
+    pub hard_package: usize,
+    pub soft_package: usize,
+    pub manage: usize,
+    pub searchable_name: usize,
+    pub referencers: usize,
+}
+
+impl AssetRegistry {
+    /// Gather the [`Stats`] of the registry, keeping the `top` most frequent tag keys and values.
+    /// `sections` are those of the file the registry was read from.
+    #[instrument(name = "AssetRegistry_stats", skip(self, sections))]
+    pub fn stats(&self, top: usize, sections: &[Section]) -> EResult<Stats> {
+        let names = &self.names;
+        let mut classes = HashMap::<String, usize>::new();
+        let mut folders = HashMap::<String, usize>::new();
+        let mut keys = HashMap::<String, usize>::new();
+        let mut values = HashMap::<(String, String), usize>::new();
+        let mut tags = 0;
+        for asset in &self.assets.assets {
+            *classes
+                .entry(names.try_resolve(asset.asset_class)?)
+                .or_default() += 1;
+            *folders
+                .entry(top_level_folder(&names.try_resolve(asset.package_path)?))
+                .or_default() += 1;
+            let pairs = self
+                .store
+                .pairs_for(FPartialMapHandle::from_int(asset.tags))?;
+            tags += pairs.len();
+            for pair in pairs {
+                let key = names.try_resolve(pair.key)?;
+                let value = self.store.value(FValueId::from_int(pair.value)?)?;
+                *values
+                    .entry((key.clone(), value.resolve(names)))
+                    .or_default() += 1;
+                *keys.entry(key).or_default() += 1;
+            }
+        }
+
+        let ansi = names.headers.iter().filter(|h| !h.is_utf16).count();
+        let name_stats = NameStats {
+            count: names.strings.len(),
+            ansi,
+            wide: names.headers.len() - ansi,
+            string_bytes: names.headers.iter().map(|h| h.n_bytes() as u64).sum(),
+        };
+
+        let store = &self.store;
+        let store_stats = vec![
+            ("text_data", store.text_data.len()),
+            ("numberless_names", store.numberless_names.len()),
+            ("names", store.names.len()),
+            (
+                "numberless_export_paths",
+                store.numberless_export_paths.len(),
+            ),
+            ("export_paths", store.export_paths.len()),
+            ("ansi_strings", store.ansi_strings.len()),
+            ("wide_strings", store.wide_strings.len()),
+            ("numberless_pairs", store.numberless_pairs.len()),
+            ("pairs", store.pairs.len()),
+        ];
+
+        let mut dependencies = DependencyStats {
+            nodes: self.dependencies.nodes.len(),
+            ..Default::default()
+        };
+        for node in &self.dependencies.nodes {
+            let hard = node
+                .package_dependencies
+                .iter()
+                .filter(|d| d.properties.contains(EDependencyProperty::HARD))
+                .count();
+            dependencies.hard_package += hard;
+            dependencies.soft_package += node.package_dependencies.len() - hard;
+            dependencies.manage += node.manage_dependencies.len();
+            dependencies.searchable_name += node.name_dependencies.len();
+            dependencies.referencers += node.referencers.len();
+        }
+
+        let sections = sections
+            .iter()
+            .map(|section| (section.name, section.range.len() as u64))
+            .collect();
+
+        let assets = self.assets.assets.len();
+        Ok(Stats {
+            assets,
+            assets_per_class: by_frequency(classes, usize::MAX),
+            assets_per_folder: by_frequency(folders, usize::MAX),
+            names: name_stats,
+            store: store_stats,
+            tag_keys: by_frequency(keys, top),
+            tag_values: by_frequency(values, top),
+            average_tags: tags as f64 / assets.max(1) as f64,
+            dependencies,
+            sections,
+        })
+    }
+}
+
+/// `/Game/Weapons` for `/Game/Weapons/Rifles`; a mount point such as `/Game` stays as is.
+fn top_level_folder(package_path: &str) -> String {
+    let end = package_path
+        .match_indices('/')
+        .nth(2)
+        .map_or(package_path.len(), |(i, _)| i);
+    package_path[..end].to_string()
+}
+
+/// The `top` most frequent entries, most common first and then by key so that reports are stable.
+fn by_frequency<K: Ord>(counts: HashMap<K, usize>, top: usize) -> Vec<(K, usize)> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_unstable_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.cmp(b)));
+    counts.truncate(top);
+    counts
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "sections:")?;
+        for (section, bytes) in &self.sections {
+            writeln!(f, "  {section:<24} {bytes:>12}")?;
+        }
+        let total = self.sections.iter().map(|(_, bytes)| bytes).sum::<u64>();
+        writeln!(f, "  {:<24} {:>12}", "total", total)?;
+
+        let names = &self.names;
+        writeln!(f, "names:")?;
+        for (what, count) in [
+            ("count", names.count as u64),
+            ("ansi", names.ansi as u64),
+            ("wide", names.wide as u64),
+            ("string bytes", names.string_bytes),
+        ] {
+            writeln!(f, "  {what:<24} {count:>12}")?;
+        }
+
+        writeln!(f, "store:")?;
+        for (array, len) in &self.store {
+            writeln!(f, "  {array:<24} {len:>12}")?;
+        }
+
+        let dependencies = &self.dependencies;
+        writeln!(f, "dependencies:")?;
+        for (what, count) in [
+            ("nodes", dependencies.nodes),
+            ("hard package", dependencies.hard_package),
+            ("soft package", dependencies.soft_package),
+            ("manage", dependencies.manage),
+            ("searchable name", dependencies.searchable_name),
+            ("referencers", dependencies.referencers),
+        ] {
+            writeln!(f, "  {what:<24} {count:>12}")?;
+        }
+
+        writeln!(f, "assets: {}", self.assets)?;
+        writeln!(f, "assets per class:")?;
+        for (class, count) in &self.assets_per_class {
+            writeln!(f, "  {count:>12} {class}")?;
+        }
+        writeln!(f, "assets per folder:")?;
+        for (folder, count) in &self.assets_per_folder {
+            writeln!(f, "  {count:>12} {folder}")?;
+        }
+
+        writeln!(f, "tags: {:.2} per asset", self.average_tags)?;
+        writeln!(f, "most frequent tag keys:")?;
+        for (key, count) in &self.tag_keys {
+            writeln!(f, "  {count:>12} {key}")?;
+        }
+        writeln!(f, "most frequent tag values:")?;
+        for ((key, value), count) in &self.tag_values {
+            writeln!(f, "  {count:>12} {key} = {value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{RegistryBuilder, TagValue};
+    use crate::dependencies::EDependencyCategory;
+    use crate::lazy_registry::LazyRegistry;
+    use crate::write::Writable;
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/Weapons/Rifles/BP_Rifle.BP_Rifle", "Blueprint")
+            .tag("ParentClass", TagValue::Name("BP_GunBase".to_string()))
+            .tag("Rarity", "common");
+        builder
+            .add_asset("/Game/Weapons/BP_Pistol.BP_Pistol", "Blueprint")
+            .tag("ParentClass", TagValue::Name("BP_GunBase".to_string()));
+        builder.add_asset("/Game/T_Ünicode.T_Ünicode", "Texture2D");
+        builder.add_dependency(
+            "/Game/Weapons/Rifles/BP_Rifle",
+            "/Game/T_Ünicode",
+            EDependencyCategory::Package,
+            EDependencyProperty::HARD | EDependencyProperty::GAME,
+        );
+        builder.add_dependency(
+            "/Game/Weapons/BP_Pistol",
+            "/Game/T_Ünicode",
+            EDependencyCategory::Package,
+            EDependencyProperty::GAME,
+        );
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_stats() {
+        let registry = fixture();
+        let mut buf = vec![];
+        registry.write(&mut Cursor::new(&mut buf)).unwrap();
+        let sections = LazyRegistry::open(&buf).unwrap().sections().unwrap();
+        let stats = registry.stats(1, &sections).unwrap();
+        assert_eq!(stats.assets, 3);
+        assert_eq!(
+            stats.assets_per_class,
+            [("Blueprint".to_string(), 2), ("Texture2D".to_string(), 1)]
+        );
+        assert_eq!(
+            stats.assets_per_folder,
+            [("/Game/Weapons".to_string(), 2), ("/Game".to_string(), 1)]
+        );
+        assert_eq!(stats.tag_keys, [("ParentClass".to_string(), 2)]);
+        assert_eq!(
+            stats.tag_values,
+            [(("ParentClass".to_string(), "BP_GunBase".to_string()), 2)]
+        );
+        assert_eq!(stats.average_tags, 1.0);
+        assert_eq!(stats.names.count, stats.names.ansi + stats.names.wide);
+        assert!(stats.names.wide > 0);
+        assert_eq!(
+            stats.dependencies,
+            DependencyStats {
+                nodes: 3,
+                hard_package: 1,
+                soft_package: 1,
+                manage: 0,
+                searchable_name: 0,
+                referencers: 2,
+            }
+        );
+
+        let total = stats.sections.iter().map(|(_, bytes)| bytes).sum::<u64>();
+        assert_eq!(total, buf.len() as u64);
+    }
+
+    #[test]
+    fn test_top_level_folder() {
+        assert_eq!(top_level_folder("/Game/Weapons/Rifles"), "/Game/Weapons");
+        assert_eq!(top_level_folder("/Game/Weapons"), "/Game/Weapons");
+        assert_eq!(top_level_folder("/Game"), "/Game");
+    }
+}
@@ -1,100 +1,388 @@
-use std::io::{Read, Write};
+use std::collections::HashMap;
+use std::io::{Read, Seek, Write};
 
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 use color_eyre::eyre::{eyre, Result as EResult};
 use itertools::Itertools;
 use tracing::*;
 
-use crate::read::{read_array, Readable};
+use crate::lenient::{decode_string, read_bytes, Recovery};
+use crate::names_batch::NamesBatch;
+use crate::read::{read_array, Positioned, Readable};
+use crate::spans;
 use crate::unreal_types::*;
-use crate::write::Writable;
+use crate::write::{count_u32, size_u32, PatchWriter, SerializedSize, Writable};
 
 pub const START_MAGIC: u32 = 0x12345679;
 pub const END_MAGIC: u32 = 0x87654321;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Default)]
 pub struct StoreData {
     pub text_data: Vec<FText>,
     pub numberless_names: Vec<FName>,
     pub names: Vec<FName>,
     pub numberless_export_paths: Vec<FAssetRegistryExportPath>,
+    pub export_paths: Vec<FAssetRegistryExportPath>,
     pub ansi_strings: Vec<String>,
     pub wide_strings: Vec<String>,
     pub numberless_pairs: Vec<FNumberedPair>,
     pub pairs: Vec<FNumberedPair>,
 }
 
-impl<W: Write> Writable<W> for StoreData {
-    #[instrument(name = "StoreData_write", skip_all)]
-    fn write(&self, writer: &mut W) -> EResult<()> {
-        writer.write_u32::<LE>(START_MAGIC)?;
+/// A tag value, as stored in one of the [`StoreData`] value arrays.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum StoreValue {
+    AnsiString(String),
+    WideString(String),
+    NumberlessName(FName),
+    Name(FName),
+    NumberlessExportPath(FAssetRegistryExportPath),
+    ExportPath(FAssetRegistryExportPath),
+    LocalizedText(FText),
+}
 
-        // === Counts (of elements and bytes) header ===
-        writer.write_u32::<LE>(self.numberless_names.len() as u32)?;
-        writer.write_u32::<LE>(self.names.len() as u32)?;
-        writer.write_u32::<LE>(self.numberless_export_paths.len() as u32)?;
-        writer.write_u32::<LE>(self.text_data.len() as u32)?;
-        writer.write_u32::<LE>(self.ansi_strings.len() as u32)?;
-        writer.write_u32::<LE>(self.wide_strings.len() as u32)?;
+impl StoreValue {
+    /// The value as text, in the form Unreal displays tag values (`Class'/Path.Object'` for export
+    /// paths). Names that don't resolve are shown by index.
+    pub fn resolve(&self, names: &NamesBatch) -> String {
+        self.resolve_with(|name| names.resolve(name))
+    }
 
-        {
-            let mut ansi_string_bytes = 0u32;
-            self.ansi_strings
-                .iter()
-                .for_each(|s| ansi_string_bytes += s.len() as u32 + 1);
-            writer.write_u32::<LE>(ansi_string_bytes)?;
+    /// [`StoreValue::resolve`] with names looked up by `resolve_name`.
+    pub fn resolve_with(&self, resolve_name: impl Fn(FName) -> Option<String>) -> String {
+        let name =
+            |name: &FName| resolve_name(*name).unwrap_or_else(|| format!("<name {}>", name.index));
+        match self {
+            StoreValue::AnsiString(s) | StoreValue::WideString(s) => s.clone(),
+            StoreValue::NumberlessName(n) | StoreValue::Name(n) => name(n),
+            StoreValue::NumberlessExportPath(path) | StoreValue::ExportPath(path) => format!(
+                "{}'{}.{}'",
+                name(&path.class),
+                name(&path.package),
+                name(&path.object)
+            ),
+            StoreValue::LocalizedText(text) => {
+                text.try_into_string().unwrap_or_else(|e| format!("<{e}>"))
+            }
         }
+    }
+}
 
-        {
-            let mut wide_string_bytes = 0u32;
-            self.wide_strings
-                .iter()
-                .for_each(|s| wide_string_bytes += s.len() as u32 + 1);
-            writer.write_u32::<LE>(wide_string_bytes)?;
+impl StoreData {
+    /// The key-value pairs that an `AssetData::tags` handle points at.
+    pub fn pairs_for(&self, handle: FPartialMapHandle) -> EResult<&[FNumberedPair]> {
+        let pairs = match handle.has_numberless_keys {
+            true => &self.numberless_pairs,
+            false => &self.pairs,
+        };
+        pairs.get(handle.pair_range()).ok_or_else(|| {
+            eyre!(
+                "tag map handle {:?} out of bounds of {} pairs",
+                handle,
+                pairs.len()
+            )
+        })
+    }
+
+    pub fn value(&self, id: FValueId) -> EResult<StoreValue> {
+        fn get<T: Clone>(values: &[T], id: FValueId) -> EResult<T> {
+            values
+                .get(id.index as usize)
+                .cloned()
+                .ok_or_else(|| eyre!("value id {:?} out of bounds of {} values", id, values.len()))
         }
 
-        writer.write_u32::<LE>(self.numberless_pairs.len() as u32)?;
-        writer.write_u32::<LE>(self.pairs.len() as u32)?;
+        let value = match id.ty {
+            EValueType::AnsiString => StoreValue::AnsiString(get(&self.ansi_strings, id)?),
+            EValueType::WideString => StoreValue::WideString(get(&self.wide_strings, id)?),
+            EValueType::NumberlessName => {
+                StoreValue::NumberlessName(get(&self.numberless_names, id)?)
+            }
+            EValueType::Name => StoreValue::Name(get(&self.names, id)?),
+            EValueType::NumberlessExportPath => {
+                StoreValue::NumberlessExportPath(get(&self.numberless_export_paths, id)?)
+            }
+            EValueType::ExportPath => StoreValue::ExportPath(get(&self.export_paths, id)?),
+            EValueType::LocalizedText => StoreValue::LocalizedText(get(&self.text_data, id)?),
+        };
+        Ok(value)
+    }
+}
 
-        // === Content ===
-        write_array_content(writer, &self.text_data)?;
-        write_array_content(writer, &self.numberless_names)?;
-        write_array_content(writer, &self.names)?;
-        write_array_content(writer, &self.numberless_export_paths)?;
+/// Incrementally builds a [`StoreData`], deduplicating values.
+#[derive(Debug, Default)]
+pub(crate) struct StoreBuilder {
+    pub(crate) store: StoreData,
+    values: HashMap<StoreValue, FValueId>,
+}
 
-        {
-            let mut offset = 0u32;
-            for s in &self.ansi_strings {
-                writer.write_u32::<LE>(offset)?;
-                offset += s.len() as u32 + 1;
+impl StoreBuilder {
+    /// Continue appending to an existing store, deduplicating against its values.
+    pub(crate) fn from_store(store: StoreData) -> Self {
+        let mut values = HashMap::new();
+        let mut index = |ty: EValueType, values_of_type: Vec<StoreValue>| {
+            for (i, value) in values_of_type.into_iter().enumerate() {
+                values.entry(value).or_insert(FValueId {
+                    ty,
+                    index: i as u32,
+                });
             }
+        };
+        index(
+            EValueType::AnsiString,
+            store
+                .ansi_strings
+                .iter()
+                .cloned()
+                .map(StoreValue::AnsiString)
+                .collect(),
+        );
+        index(
+            EValueType::WideString,
+            store
+                .wide_strings
+                .iter()
+                .cloned()
+                .map(StoreValue::WideString)
+                .collect(),
+        );
+        index(
+            EValueType::NumberlessName,
+            store
+                .numberless_names
+                .iter()
+                .copied()
+                .map(StoreValue::NumberlessName)
+                .collect(),
+        );
+        index(
+            EValueType::Name,
+            store.names.iter().copied().map(StoreValue::Name).collect(),
+        );
+        index(
+            EValueType::NumberlessExportPath,
+            store
+                .numberless_export_paths
+                .iter()
+                .copied()
+                .map(StoreValue::NumberlessExportPath)
+                .collect(),
+        );
+        index(
+            EValueType::ExportPath,
+            store
+                .export_paths
+                .iter()
+                .copied()
+                .map(StoreValue::ExportPath)
+                .collect(),
+        );
+        index(
+            EValueType::LocalizedText,
+            store
+                .text_data
+                .iter()
+                .cloned()
+                .map(StoreValue::LocalizedText)
+                .collect(),
+        );
+        StoreBuilder { store, values }
+    }
+
+    pub(crate) fn push_value(&mut self, value: StoreValue) -> FValueId {
+        if let Some(id) = self.values.get(&value) {
+            return *id;
         }
 
-        {
-            let mut offset = 0u32;
-            for s in &self.wide_strings {
-                writer.write_u32::<LE>(offset)?;
-                offset += s.len() as u32 + 1;
-            }
+        fn push<T>(values: &mut Vec<T>, value: T) -> u32 {
+            values.push(value);
+            values.len() as u32 - 1
         }
 
-        self.ansi_strings.iter().try_for_each(|s| {
-            writer.write_all(s.as_bytes())?;
-            writer.write_u8(b'\0')
-        })?;
-        self.wide_strings.iter().try_for_each(|s| {
-            let elems = s.encode_utf16().collect::<Vec<_>>();
-            elems
-                .into_iter()
-                .try_for_each(|e| writer.write_u16::<LE>(e))?;
-            writer.write_u8(b'\0')
-        })?;
+        let store = &mut self.store;
+        let id = match value.clone() {
+            StoreValue::AnsiString(s) => FValueId {
+                ty: EValueType::AnsiString,
+                index: push(&mut store.ansi_strings, s),
+            },
+            StoreValue::WideString(s) => FValueId {
+                ty: EValueType::WideString,
+                index: push(&mut store.wide_strings, s),
+            },
+            StoreValue::NumberlessName(n) => FValueId {
+                ty: EValueType::NumberlessName,
+                index: push(&mut store.numberless_names, n),
+            },
+            StoreValue::Name(n) => FValueId {
+                ty: EValueType::Name,
+                index: push(&mut store.names, n),
+            },
+            StoreValue::NumberlessExportPath(p) => FValueId {
+                ty: EValueType::NumberlessExportPath,
+                index: push(&mut store.numberless_export_paths, p),
+            },
+            StoreValue::ExportPath(p) => FValueId {
+                ty: EValueType::ExportPath,
+                index: push(&mut store.export_paths, p),
+            },
+            StoreValue::LocalizedText(t) => FValueId {
+                ty: EValueType::LocalizedText,
+                index: push(&mut store.text_data, t),
+            },
+        };
+        self.values.insert(value, id);
+        id
+    }
+
+    /// Append a tag map and return the handle pointing at it. Keys are stored as numberless pairs
+    /// when none of them has a number.
+    pub(crate) fn push_map(&mut self, map: &[(FName, FValueId)]) -> FPartialMapHandle {
+        if map.is_empty() {
+            return FPartialMapHandle::default();
+        }
+
+        let has_numberless_keys = map.iter().all(|(key, _)| key.number == 0);
+        let pairs = match has_numberless_keys {
+            true => &mut self.store.numberless_pairs,
+            false => &mut self.store.pairs,
+        };
+        let pair_begin = pairs.len() as u32;
+        pairs.extend(map.iter().map(|(key, value)| FNumberedPair {
+            key: *key,
+            value: value.to_int(),
+        }));
+        FPartialMapHandle {
+            has_numberless_keys,
+            num: map.len() as u16,
+            pair_begin,
+        }
+    }
+}
+
+impl<W: Write + Seek> Writable<W> for StoreData {
+    #[instrument(name = "StoreData_write", skip_all)]
+    fn write(&self, writer: &mut W) -> EResult<()> {
+        let mut writer = PatchWriter::new(writer)?;
+        writer.write_u32::<LE>(START_MAGIC)?;
 
-        write_array_content(writer, &self.numberless_pairs)?;
-        write_array_content(writer, &self.pairs)?;
+        // === Counts (of elements and bytes) header ===
+        let counts = [
+            count_u32("numberless names", self.numberless_names.len())?,
+            count_u32("names", self.names.len())?,
+            count_u32(
+                "numberless export paths",
+                self.numberless_export_paths.len(),
+            )?,
+            count_u32("export paths", self.export_paths.len())?,
+            count_u32("texts", self.text_data.len())?,
+            count_u32("ANSI strings", self.ansi_strings.len())?,
+            count_u32("wide strings", self.wide_strings.len())?,
+        ];
+        let pair_counts = [
+            count_u32("numberless pairs", self.numberless_pairs.len())?,
+            count_u32("pairs", self.pairs.len())?,
+        ];
+        counts.iter().try_for_each(|c| writer.write_u32::<LE>(*c))?;
+        let ansi_string_bytes = writer.reserve::<u32>()?;
+        let wide_string_units = writer.reserve::<u32>()?;
+        pair_counts
+            .iter()
+            .try_for_each(|c| writer.write_u32::<LE>(*c))?;
+
+        // === Content ===
+        // The texts are prefixed with their total size, so that loaders can skip them.
+        let text_data_bytes = writer.reserve::<u32>()?;
+        let start = writer.position();
+        write_array_content(&mut writer, &self.text_data)?;
+        let bytes = size_u32("texts", writer.position() - start)?;
+        writer.patch(text_data_bytes, bytes)?;
+        self.numberless_names
+            .iter()
+            .try_for_each(|n| n.write_numberless(&mut writer))?;
+        write_array_content(&mut writer, &self.names)?;
+        self.numberless_export_paths
+            .iter()
+            .try_for_each(|p| p.write_numberless(&mut writer))?;
+        write_array_content(&mut writer, &self.export_paths)?;
+
+        // The offsets into the packed strings that follow them.
+        let ansi_string_offsets = writer.reserve_table::<u32>(self.ansi_strings.len())?;
+        let wide_string_offsets = writer.reserve_table::<u32>(self.wide_strings.len())?;
+
+        let (offsets, bytes) = write_packed_strings(
+            &mut writer,
+            &self.ansi_strings,
+            "ANSI strings",
+            1,
+            |w, s| {
+                w.write_all(s.as_bytes())?;
+                w.write_u8(b'\0')
+            },
+        )?;
+        writer.patch_table(ansi_string_offsets, &offsets)?;
+        writer.patch(ansi_string_bytes, bytes)?;
+
+        let (offsets, units) = write_packed_strings(
+            &mut writer,
+            &self.wide_strings,
+            "wide strings",
+            2,
+            |w, s| {
+                s.encode_utf16().try_for_each(|c| w.write_u16::<LE>(c))?;
+                w.write_u16::<LE>(0)
+            },
+        )?;
+        writer.patch_table(wide_string_offsets, &offsets)?;
+        writer.patch(wide_string_units, units)?;
+
+        self.numberless_pairs
+            .iter()
+            .try_for_each(|p| p.write_numberless(&mut writer))?;
+        write_array_content(&mut writer, &self.pairs)?;
 
         writer.write_u32::<LE>(END_MAGIC)?;
-        Ok(())
+        writer.finish()
+    }
+}
+
+/// Write NUL-terminated `strings` back to back, returning the offset of each string and the total
+/// size, both counted in code units of `unit` bytes.
+fn write_packed_strings<W: Write + Seek>(
+    writer: &mut PatchWriter<W>,
+    strings: &[String],
+    what: &'static str,
+    unit: u64,
+    write: impl Fn(&mut PatchWriter<W>, &str) -> std::io::Result<()>,
+) -> EResult<(Vec<u32>, u32)> {
+    let start = writer.position();
+    let mut offsets = Vec::with_capacity(strings.len());
+    for s in strings {
+        offsets.push(size_u32(what, (writer.position() - start) / unit)?);
+        write(writer, s)?;
+    }
+    Ok((offsets, size_u32(what, (writer.position() - start) / unit)?))
+}
+
+impl SerializedSize for StoreData {
+    fn serialized_size(&self) -> u64 {
+        let ansi_string_bytes = self.ansi_strings.iter().map(|s| s.len() as u64 + 1);
+        let wide_string_bytes = self
+            .wide_strings
+            .iter()
+            .map(|s| 2 * (s.encode_utf16().count() as u64 + 1));
+        4 + 11 * 4
+            + 4
+            + self.text_data.serialized_size()
+            + 4 * self.numberless_names.len() as u64
+            + self.names.serialized_size()
+            + FAssetRegistryExportPath::NUMBERLESS_SIZE * self.numberless_export_paths.len() as u64
+            + self.export_paths.serialized_size()
+            + 4 * (self.ansi_strings.len() + self.wide_strings.len()) as u64
+            + ansi_string_bytes.sum::<u64>()
+            + wide_string_bytes.sum::<u64>()
+            + FNumberedPair::NUMBERLESS_SIZE * self.numberless_pairs.len() as u64
+            + self.pairs.serialized_size()
+            + 4
     }
 }
 
@@ -105,142 +393,237 @@ fn write_array_content<W: Write, T: Writable<W>>(writer: &mut W, elements: &[T])
 impl<R: Read> Readable<R> for StoreData {
     #[instrument(name = "StoreData_read", skip_all)]
     fn read(reader: &mut R) -> EResult<Self> {
+        StoreData::read_with(&mut Positioned::new(reader, 0), &mut Recovery::strict())
+    }
+}
+
+/// Split the packed strings in `blob` at `offsets`, each terminated by `nul` (one byte for ANSI
+/// strings, two for wide ones). Offsets count code units of the terminator's size, so UTF-16
+/// code units for wide strings. Strings that can't be sliced out are replaced by an empty string
+/// so that value indices stay valid.
+#[allow(clippy::too_many_arguments)]
+fn unpack_strings(
+    blob: &[u8],
+    blob_offset: u64,
+    offsets: &[u32],
+    kind: &str,
+    nul: &[u8],
+    recovery: &mut Recovery,
+    decode: fn(&[u8]) -> EResult<String>,
+    lossy: fn(&[u8]) -> String,
+) -> EResult<Vec<String>> {
+    let unit = nul.len() as u64;
+    let total = (blob.len() as u64 / unit) as u32;
+    let mut strings = Vec::with_capacity(offsets.len());
+    for (i, (offset, next_offset)) in offsets
+        .iter()
+        .chain(std::iter::once(&total))
+        .tuple_windows()
+        .enumerate()
+    {
+        let location = || format!("store.{}_strings[{i}]", kind.to_lowercase());
+        let file_offset = blob_offset + *offset.min(&total) as u64 * unit;
+        if offset >= next_offset {
+            recovery.recover(
+                file_offset,
+                location,
+                eyre!(
+                    "offset {:X} >= next offset {:X}, {} string offset is bad",
+                    offset,
+                    next_offset,
+                    kind
+                ),
+            )?;
+            strings.push(String::new());
+            continue;
+        }
+
+        if *offset > total || *next_offset > total {
+            recovery.recover(file_offset, location, eyre!("offset exceeds claimed size of {} strings, invalid offsets or size of {} strings", kind, kind))?;
+            strings.push(String::new());
+            continue;
+        }
+
+        let bytes = &blob[(*offset as u64 * unit) as usize..(*next_offset as u64 * unit) as usize];
+        let buf = match bytes.strip_suffix(nul) {
+            Some(buf) => buf,
+            None => {
+                recovery.recover(
+                    file_offset,
+                    location,
+                    eyre!("{} string is not NUL-terminated", kind),
+                )?;
+                &bytes[..bytes.len().saturating_sub(nul.len())]
+            }
+        };
+        strings.push(decode_string(
+            recovery,
+            file_offset,
+            location,
+            || decode(buf),
+            || lossy(buf),
+        )?);
+    }
+    Ok(strings)
+}
+
+/// Record the span of each packed string that has valid offsets, counted in code units of `unit`
+/// bytes.
+fn string_spans(strings: &spans::Entered, offsets: &[u32], total: u32, unit: u64) {
+    for (i, (offset, next_offset)) in offsets
+        .iter()
+        .chain(std::iter::once(&total))
+        .tuple_windows()
+        .enumerate()
+    {
+        if offset < next_offset && *next_offset <= total {
+            strings.index_at(i as u64, *offset as u64 * unit..*next_offset as u64 * unit);
+        }
+    }
+}
+
+impl StoreData {
+    /// Read the store, letting `recovery` decide what to do about bad magics and malformed
+    /// strings.
+    #[instrument(name = "StoreData_read_with", skip_all)]
+    pub(crate) fn read_with<R: Read>(
+        reader: &mut Positioned<R>,
+        recovery: &mut Recovery,
+    ) -> EResult<Self> {
         {
-            let start_magic = reader.read_u32::<LE>()?;
+            let offset = reader.offset;
+            let start_magic = spans::field("start_magic", || reader.read_u32::<LE>())?;
             if start_magic != START_MAGIC {
-                return Err(eyre!(
-                    "store data start magic mismatch: expected {:X} but found {:X}",
-                    START_MAGIC,
-                    start_magic,
-                ));
+                recovery.recover(
+                    offset,
+                    || "store".to_string(),
+                    eyre!(
+                        "store data start magic mismatch: expected {:X} but found {:X}",
+                        START_MAGIC,
+                        start_magic,
+                    ),
+                )?;
             }
         }
 
         // === Header ===
-        let numberless_names_count = reader.read_u32::<LE>()?;
-        let names_count = reader.read_u32::<LE>()?;
-        let numberless_export_paths_count = reader.read_u32::<LE>()?;
-        let text_data_count = reader.read_u32::<LE>()?;
-        let ansi_string_offsets_count = reader.read_u32::<LE>()?;
-        let wide_string_offsets_count = reader.read_u32::<LE>()?;
-        let ansi_string_bytes = reader.read_u32::<LE>()?;
-        let wide_string_bytes = reader.read_u32::<LE>()?;
-        let numberless_pairs_count = reader.read_u32::<LE>()?;
-        let pairs_count = reader.read_u32::<LE>()?;
+        let numberless_names_count =
+            spans::field("numberless_names_count", || reader.read_u32::<LE>())?;
+        let names_count = spans::field("names_count", || reader.read_u32::<LE>())?;
+        let numberless_export_paths_count =
+            spans::field("numberless_export_paths_count", || reader.read_u32::<LE>())?;
+        let export_paths_count = spans::field("export_paths_count", || reader.read_u32::<LE>())?;
+        let text_data_count = spans::field("text_data_count", || reader.read_u32::<LE>())?;
+        let ansi_string_offsets_count =
+            spans::field("ansi_string_offsets_count", || reader.read_u32::<LE>())?;
+        let wide_string_offsets_count =
+            spans::field("wide_string_offsets_count", || reader.read_u32::<LE>())?;
+        let ansi_string_bytes = spans::field("ansi_string_bytes", || reader.read_u32::<LE>())?;
+        let wide_string_units = spans::field("wide_string_units", || reader.read_u32::<LE>())?;
+        let numberless_pairs_count =
+            spans::field("numberless_pairs_count", || reader.read_u32::<LE>())?;
+        let pairs_count = spans::field("pairs_count", || reader.read_u32::<LE>())?;
 
         // === Content ===
-        let text_data = read_array(text_data_count, reader, FText::read)?;
-        let numberless_names = read_array(numberless_names_count, reader, FName::read)?;
-        let names = read_array(names_count, reader, FName::read)?;
-        let numberless_export_paths = read_array(
-            numberless_export_paths_count,
-            reader,
-            FAssetRegistryExportPath::read,
-        )?;
-        let ansi_string_offsets = read_array(ansi_string_offsets_count, reader, |reader| {
-            reader.read_u32::<LE>()
+        let text_data_bytes = spans::field("text_data_bytes", || reader.read_u32::<LE>())?;
+        let offset = reader.offset;
+        let text_data = spans::field("text_data", || {
+            read_array(text_data_count, reader, FText::read)
         })?;
-        let wide_string_offsets = read_array(wide_string_offsets_count, reader, |reader| {
-            reader.read_u32::<LE>()
+        if reader.offset - offset != text_data_bytes as u64 {
+            recovery.recover(
+                offset,
+                || "store.text_data".to_string(),
+                eyre!(
+                    "texts take {} bytes but the store claims {}",
+                    reader.offset - offset,
+                    text_data_bytes
+                ),
+            )?;
+        }
+        let numberless_names = spans::field("numberless_names", || {
+            read_array(numberless_names_count, reader, FName::read_numberless)
+        })?;
+        let names = spans::field("names", || read_array(names_count, reader, FName::read))?;
+        let numberless_export_paths = spans::field("numberless_export_paths", || {
+            read_array(
+                numberless_export_paths_count,
+                reader,
+                FAssetRegistryExportPath::read_numberless,
+            )
+        })?;
+        let export_paths = spans::field("export_paths", || {
+            read_array(export_paths_count, reader, FAssetRegistryExportPath::read)
+        })?;
+        let ansi_string_offsets = spans::field("ansi_string_offsets", || {
+            read_array(ansi_string_offsets_count, reader, |reader| {
+                reader.read_u32::<LE>()
+            })
+        })?;
+        let wide_string_offsets = spans::field("wide_string_offsets", || {
+            read_array(wide_string_offsets_count, reader, |reader| {
+                reader.read_u32::<LE>()
+            })
         })?;
 
-        let ansi_strings = {
-            // Packed ANSI strings
-            let mut strings = vec![];
-            for (offset, next_offset) in ansi_string_offsets
-                .iter()
-                .chain(std::iter::once(&ansi_string_bytes))
-                .tuple_windows()
-            {
-                if offset >= next_offset {
-                    return Err(eyre!(
-                        "offset {:X} >= next offset {:X}, ANSI string offset is bad",
-                        offset,
-                        next_offset
-                    ));
-                }
-
-                if *offset > ansi_string_bytes || *next_offset > ansi_string_bytes {
-                    return Err(eyre!("offset exceeds claimed number of ANSI string bytes, invalid offsets or number of ANSI string bytes"));
-                }
-
-                let len = next_offset - offset;
-
-                if len == 0 {
-                    return Err(eyre!("ANSI string len is 0, ANSI string offset is bad",));
-                } else if len > isize::MAX as u32 {
-                    return Err(eyre!(
-                        "ANSI string len {:X} larger than {:X}, ANSI string offset is bad",
-                        len,
-                        isize::MAX
-                    ));
-                }
-
-                let mut buf = vec![0u8; len as usize - 1];
-                reader.read_exact(&mut buf)?;
-                let nul = reader.read_u8()?;
-                if nul != b'\0' {
-                    return Err(eyre!("ANSI string is not NUL-terminated"));
-                }
-                let s = String::from_utf8(buf)?;
-                strings.push(s);
-            }
-
-            strings
-        };
-
-        let wide_strings = {
-            // Packed wide strings
-            let mut strings = vec![];
-            for (offset, next_offset) in wide_string_offsets
-                .iter()
-                .chain(std::iter::once(&wide_string_bytes))
-                .tuple_windows()
-            {
-                if offset >= next_offset {
-                    return Err(eyre!(
-                        "offset {:X} >= next offset {:X}, wide string offset is bad",
-                        offset,
-                        next_offset
-                    ));
-                }
-
-                let len = next_offset - offset;
-
-                if len == 0 {
-                    return Err(eyre!("ANSI string len is 0, wide string offset is bad",));
-                } else if len > isize::MAX as u32 {
-                    return Err(eyre!(
-                        "ANSI string len {:X} larger than {:X}, wide string offset is bad",
-                        len,
-                        isize::MAX
-                    ));
-                }
-
-                let mut buf = vec![0u8; len as usize - 1];
-                reader.read_exact(&mut buf)?;
-                let nul = reader.read_u8()?;
-                if nul != b'\0' {
-                    return Err(eyre!("wide string is not NUL-terminated"));
-                }
-                let s = String::from_utf16le(&buf)?;
-                strings.push(s);
-            }
-
-            strings
-        };
-
-        let numberless_pairs = read_array(numberless_pairs_count, reader, FNumberedPair::read)?;
-        let pairs = read_array(pairs_count, reader, FNumberedPair::read)?;
+        // Packed ANSI strings
+        let offset = reader.offset;
+        let _ansi_strings = spans::enter("ansi_strings");
+        let blob = read_bytes(reader, ansi_string_bytes as usize)?;
+        let ansi_strings = unpack_strings(
+            &blob,
+            offset,
+            &ansi_string_offsets,
+            "ANSI",
+            b"\0",
+            recovery,
+            |buf| Ok(String::from_utf8(buf.to_vec())?),
+            |buf| String::from_utf8_lossy(buf).into_owned(),
+        )?;
+        string_spans(&_ansi_strings, &ansi_string_offsets, ansi_string_bytes, 1);
+        drop(_ansi_strings);
+
+        // Packed wide strings
+        let offset = reader.offset;
+        let _wide_strings = spans::enter("wide_strings");
+        let blob = read_bytes(reader, 2 * wide_string_units as usize)?;
+        let wide_strings = unpack_strings(
+            &blob,
+            offset,
+            &wide_string_offsets,
+            "wide",
+            b"\0\0",
+            recovery,
+            |buf| Ok(String::from_utf16le(buf)?),
+            String::from_utf16le_lossy,
+        )?;
+        string_spans(&_wide_strings, &wide_string_offsets, wide_string_units, 2);
+        drop(_wide_strings);
+
+        let numberless_pairs = spans::field("numberless_pairs", || {
+            read_array(
+                numberless_pairs_count,
+                reader,
+                FNumberedPair::read_numberless,
+            )
+        })?;
+        let pairs = spans::field("pairs", || {
+            read_array(pairs_count, reader, FNumberedPair::read)
+        })?;
 
         {
-            let end_magic = reader.read_u32::<LE>()?;
+            let offset = reader.offset;
+            let end_magic = spans::field("end_magic", || reader.read_u32::<LE>())?;
             if end_magic != END_MAGIC {
-                return Err(eyre!(
-                    "store data end magic mismatch: expected {:X} but found {:X}",
-                    END_MAGIC,
-                    end_magic,
-                ));
+                recovery.recover(
+                    offset,
+                    || "store".to_string(),
+                    eyre!(
+                        "store data end magic mismatch: expected {:X} but found {:X}",
+                        END_MAGIC,
+                        end_magic,
+                    ),
+                )?;
             }
         }
 
@@ -249,6 +632,7 @@ impl<R: Read> Readable<R> for StoreData {
             numberless_names,
             names,
             numberless_export_paths,
+            export_paths,
             ansi_strings,
             wide_strings,
             numberless_pairs,
@@ -267,52 +651,70 @@ mod tests {
 
     #[test]
     fn test_roundtrip() {
+        let name = |index, number| FName { index, number };
         let store = StoreData {
             text_data: vec![FText::from("OwO")],
-            numberless_names: vec![FName {
-                index: 0,
-                number: 567,
-            }],
-            names: vec![
-                FName {
-                    index: 134,
-                    number: 536,
-                },
-                FName {
-                    index: 621,
-                    number: 999,
-                },
-            ],
+            numberless_names: vec![name(0, 0)],
+            names: vec![name(134, 536), name(621, 0)],
             numberless_export_paths: vec![FAssetRegistryExportPath {
-                class: FName {
-                    index: 123,
-                    number: 456,
-                },
-                object: FName {
-                    index: 789,
-                    number: 101,
-                },
-                package: FName {
-                    index: 194,
-                    number: 249,
-                },
+                class: name(123, 0),
+                object: name(789, 0),
+                package: name(194, 0),
+            }],
+            export_paths: vec![FAssetRegistryExportPath {
+                class: name(123, 0),
+                object: name(789, 101),
+                package: name(194, 0),
             }],
             ansi_strings: vec!["hewwo world".to_string(), "a".to_string()],
-            wide_strings: vec![],
+            wide_strings: vec!["ħéwwö".to_string(), "🦀".to_string()],
             numberless_pairs: vec![FNumberedPair {
-                key: FName {
-                    index: 192,
-                    number: 795,
-                },
+                key: name(192, 0),
+                value: 3492,
+            }],
+            pairs: vec![FNumberedPair {
+                key: name(192, 795),
                 value: 3492,
             }],
-            pairs: vec![],
         };
         let mut buf = vec![];
         let mut writer = Cursor::new(&mut buf);
         store.write(&mut writer).unwrap();
+        assert_eq!(buf.len() as u64, store.serialized_size());
         let mut reader = Cursor::new(&buf);
         let read_store = StoreData::read(&mut reader).unwrap();
         assert_eq!(read_store, store);
     }
+
+    #[test]
+    fn test_numbered_numberless_value() {
+        let store = StoreData {
+            numberless_names: vec![FName {
+                index: 0,
+                number: 567,
+            }],
+            ..Default::default()
+        };
+        assert!(store.write(&mut Cursor::new(vec![])).is_err());
+    }
+
+    #[test]
+    fn test_wide_strings_layout() {
+        let store = StoreData {
+            wide_strings: vec!["é".to_string(), "🦀".to_string()],
+            ..Default::default()
+        };
+        let mut buf = vec![];
+        store.write(&mut Cursor::new(&mut buf)).unwrap();
+
+        let header = buf[4..52]
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        // Two strings taking 1 + 1 and 2 + 1 UTF-16 code units with their NUL terminators, after
+        // an empty text section.
+        assert_eq!(header, [0, 0, 0, 0, 0, 0, 2, 0, 5, 0, 0, 0]);
+        assert_eq!(buf[52..64], [0, 0, 0, 0, 2, 0, 0, 0, 0xE9, 0, 0, 0]);
+        assert_eq!(buf[64..70], [0x3E, 0xD8, 0x80, 0xDD, 0, 0]);
+    }
 }
@@ -1,42 +1,45 @@
 use std::io::{Read, Write};
 
-use color_eyre::eyre::{Result as EResult};
-use tracing::*;
+use color_eyre::eyre::Result as EResult;
 
 use crate::read::Readable;
-use crate::write::Writable;
+use crate::spans;
+use crate::write::{SerializedSize, Writable};
 
 use super::FName;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Readable, Writable, SerializedSize)]
 pub struct FAssetRegistryExportPath {
     pub class: FName,
     pub object: FName,
     pub package: FName,
 }
 
-impl<W: Write> Writable<W> for FAssetRegistryExportPath {
-    #[instrument(name = "FAssetRegistryExportPath_write", skip_all)]
-    fn write(&self, writer: &mut W) -> EResult<()> {
-        self.class.write(writer)?;
-        self.object.write(writer)?;
-        self.package.write(writer)?;
-        Ok(())
-    }
-}
+impl FAssetRegistryExportPath {
+    /// The serialized size of an `FNumberlessExportPath`: the indices of its three names.
+    pub const NUMBERLESS_SIZE: u64 = 12;
 
-impl<R: Read> Readable<R> for FAssetRegistryExportPath {
-    #[instrument(name = "FAssetRegistryExportPath_read", skip_all)]
-    fn read(reader: &mut R) -> EResult<Self> {
-        let class = FName::read(reader)?;
-        let object = FName::read(reader)?;
-        let package = FName::read(reader)?;
+    /// Read an `FNumberlessExportPath`, whose names have no numbers.
+    pub fn read_numberless<R: Read>(reader: &mut R) -> EResult<Self> {
         Ok(FAssetRegistryExportPath {
-            class,
-            object,
-            package,
+            class: spans::field("class", || FName::read_numberless(reader))?,
+            object: spans::field("object", || FName::read_numberless(reader))?,
+            package: spans::field("package", || FName::read_numberless(reader))?,
         })
     }
+
+    pub fn write_numberless<W: Write>(&self, writer: &mut W) -> EResult<()> {
+        self.class.write_numberless(writer)?;
+        self.object.write_numberless(writer)?;
+        self.package.write_numberless(writer)
+    }
+
+    /// Whether none of the names has a number, so the path can be stored numberless.
+    pub fn is_numberless(&self) -> bool {
+        [self.class, self.object, self.package]
+            .iter()
+            .all(|name| name.number == 0)
+    }
 }
 
 #[cfg(test)]
@@ -1,33 +1,111 @@
 use std::io::{Read, Write};
 
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use color_eyre::eyre::{Result as EResult};
-use tracing::*;
+use color_eyre::eyre::{eyre, Result as EResult};
 
 use crate::read::Readable;
-use crate::write::Writable;
+use crate::spans;
+use crate::write::{SerializedSize, Writable};
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+/// A name batch index and an internal number (`0` for none, `N + 1` for a `_N` suffix).
+///
+/// Serialized as the `u32` index, with [`FName::HAS_NUMBER`] set when the number follows it as
+/// another `u32`. The store's numberless arrays only hold the index, see
+/// [`FName::read_numberless`].
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
 pub struct FName {
     pub index: u32,
     pub number: u32,
 }
 
+impl<R: Read> Readable<R> for FName {
+    fn read(reader: &mut R) -> EResult<Self> {
+        let index = spans::field("index", || reader.read_u32::<LE>())?;
+        let number = match index & FName::HAS_NUMBER {
+            0 => 0,
+            _ => spans::field("number", || reader.read_u32::<LE>())?,
+        };
+        Ok(FName {
+            index: index & !FName::HAS_NUMBER,
+            number,
+        })
+    }
+}
+
 impl<W: Write> Writable<W> for FName {
-    #[instrument(name = "FName_write", skip_all)]
     fn write(&self, writer: &mut W) -> EResult<()> {
-        writer.write_u32::<LE>(self.index)?;
-        writer.write_u32::<LE>(self.number)?;
+        let index = self.checked_index()?;
+        match self.number {
+            0 => writer.write_u32::<LE>(index)?,
+            number => {
+                writer.write_u32::<LE>(index | FName::HAS_NUMBER)?;
+                writer.write_u32::<LE>(number)?;
+            }
+        }
         Ok(())
     }
 }
 
-impl<R: Read> Readable<R> for FName {
-    #[instrument(name = "FName_read", skip_all)]
-    fn read(reader: &mut R) -> EResult<Self> {
-        let index = reader.read_u32::<LE>()?;
-        let number = reader.read_u32::<LE>()?;
-        Ok(FName { index, number })
+impl SerializedSize for FName {
+    fn serialized_size(&self) -> u64 {
+        match self.number {
+            0 => 4,
+            _ => 8,
+        }
+    }
+}
+
+impl FName {
+    /// The bit of the serialized index that says a number follows.
+    pub const HAS_NUMBER: u32 = 1 << 31;
+
+    fn checked_index(&self) -> EResult<u32> {
+        match self.index & FName::HAS_NUMBER {
+            0 => Ok(self.index),
+            _ => Err(eyre!(
+                "FName index {:#X} doesn't fit in 31 bits",
+                self.index
+            )),
+        }
+    }
+
+    /// Read a name from one of the store's numberless arrays, which only hold the index.
+    pub fn read_numberless<R: Read>(reader: &mut R) -> EResult<Self> {
+        Ok(FName {
+            index: spans::field("index", || reader.read_u32::<LE>())?,
+            number: 0,
+        })
+    }
+
+    /// Write a name into one of the store's numberless arrays.
+    pub fn write_numberless<W: Write>(&self, writer: &mut W) -> EResult<()> {
+        if self.number != 0 {
+            return Err(eyre!(
+                "numberless FName {:?} has number {}",
+                self,
+                self.number
+            ));
+        }
+        writer.write_u32::<LE>(self.checked_index()?)?;
+        Ok(())
+    }
+
+    /// Split a display string into its base string and internal number, following Unreal's
+    /// `FName` parsing: a trailing `_N` becomes number `N + 1`, unless `N` has a leading zero or
+    /// doesn't fit in an `i32`.
+    pub fn split_number(s: &str) -> (&str, u32) {
+        let digits = s.bytes().rev().take_while(u8::is_ascii_digit).count();
+        let (base, number) = s.split_at(s.len() - digits);
+        let Some(base) = base.strip_suffix('_') else {
+            return (s, 0);
+        };
+        if digits == 0 || digits > 10 || (digits > 1 && number.starts_with('0')) {
+            return (s, 0);
+        }
+        match number.parse::<i32>() {
+            Ok(number) if number < i32::MAX => (base, number as u32 + 1),
+            _ => (s, 0),
+        }
     }
 }
 
@@ -48,5 +126,49 @@ mod tests {
         let mut reader = Cursor::new(&buf);
         let read_name = FName::read(&mut reader).unwrap();
         assert_eq!(read_name, name);
+        assert_eq!(buf, [123, 0, 0, 0x80, 0xC8, 1, 0, 0]);
+        assert_eq!(name.serialized_size(), 8);
+    }
+
+    #[test]
+    fn test_numberless() {
+        let name = FName {
+            index: 123,
+            number: 0,
+        };
+        let mut buf = vec![];
+        name.write(&mut Cursor::new(&mut buf)).unwrap();
+        assert_eq!(buf, [123, 0, 0, 0]);
+        assert_eq!(name.serialized_size(), 4);
+
+        let mut numberless = vec![];
+        name.write_numberless(&mut numberless).unwrap();
+        assert_eq!(numberless, buf);
+        assert_eq!(FName::read_numberless(&mut &buf[..]).unwrap(), name);
+
+        let numbered = FName {
+            index: 123,
+            number: 1,
+        };
+        assert!(numbered.write_numberless(&mut vec![]).is_err());
+        let too_large = FName {
+            index: FName::HAS_NUMBER,
+            number: 0,
+        };
+        assert!(too_large.write(&mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_split_number() {
+        assert_eq!(FName::split_number("Actor"), ("Actor", 0));
+        assert_eq!(FName::split_number("Actor_0"), ("Actor", 1));
+        assert_eq!(FName::split_number("Actor_12"), ("Actor", 13));
+        assert_eq!(FName::split_number("Rocket_04"), ("Rocket_04", 0));
+        assert_eq!(FName::split_number("Actor12"), ("Actor12", 0));
+        assert_eq!(FName::split_number("Actor_"), ("Actor_", 0));
+        assert_eq!(
+            FName::split_number("Actor_99999999999"),
+            ("Actor_99999999999", 0)
+        );
     }
 }
@@ -3,35 +3,36 @@
 use std::io::{Read, Write};
 
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use color_eyre::eyre::{Result as EResult};
-use tracing::*;
+use color_eyre::eyre::Result as EResult;
 
 use crate::read::Readable;
-use crate::write::Writable;
+use crate::spans;
+use crate::write::{SerializedSize, Writable};
 
 use super::FName;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Copy, Clone, Readable, Writable, SerializedSize)]
 pub struct FNumberedPair {
     pub key: FName,
     pub value: u32, // FValueId
 }
 
-impl<W: Write> Writable<W> for FNumberedPair {
-    #[instrument(name = "FNumberedPair_write", skip_all)]
-    fn write(&self, writer: &mut W) -> EResult<()> {
-        self.key.write(writer)?;
-        writer.write_u32::<LE>(self.value)?;
-        Ok(())
+impl FNumberedPair {
+    /// The serialized size of an `FNumberlessPair`: the key's index and the value.
+    pub const NUMBERLESS_SIZE: u64 = 8;
+
+    /// Read an `FNumberlessPair`, whose key has no number.
+    pub fn read_numberless<R: Read>(reader: &mut R) -> EResult<Self> {
+        Ok(FNumberedPair {
+            key: spans::field("key", || FName::read_numberless(reader))?,
+            value: spans::field("value", || reader.read_u32::<LE>())?,
+        })
     }
-}
 
-impl<R: Read> Readable<R> for FNumberedPair {
-    #[instrument(name = "FNumberedPair_read", skip_all)]
-    fn read(reader: &mut R) -> EResult<Self> {
-        let key = FName::read(reader)?;
-        let value = reader.read_u32::<LE>()?;
-        Ok(FNumberedPair { key, value })
+    pub fn write_numberless<W: Write>(&self, writer: &mut W) -> EResult<()> {
+        self.key.write_numberless(writer)?;
+        writer.write_u32::<LE>(self.value)?;
+        Ok(())
     }
 }
 
@@ -55,5 +56,17 @@ mod tests {
         let mut reader = Cursor::new(&buf);
         let read_pair = FNumberedPair::read(&mut reader).unwrap();
         assert_eq!(read_pair, pair);
+
+        let pair = FNumberedPair {
+            key: FName {
+                index: 123,
+                number: 0,
+            },
+            value: 789,
+        };
+        let mut buf = vec![];
+        pair.write_numberless(&mut buf).unwrap();
+        assert_eq!(buf.len() as u64, FNumberedPair::NUMBERLESS_SIZE);
+        assert_eq!(FNumberedPair::read_numberless(&mut &buf[..]).unwrap(), pair);
     }
 }
@@ -0,0 +1,45 @@
+//! `FPartialMapHandle` is the packed `u64` stored in `AssetData::tags`, pointing at a contiguous
+//! run of pairs in the store.
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct FPartialMapHandle {
+    /// Whether the pairs live in `StoreData::numberless_pairs` rather than `StoreData::pairs`.
+    pub has_numberless_keys: bool,
+    pub num: u16,
+    pub pair_begin: u32,
+}
+
+impl FPartialMapHandle {
+    pub fn from_int(int: u64) -> Self {
+        FPartialMapHandle {
+            has_numberless_keys: (int >> 63) != 0,
+            num: (int >> 32) as u16,
+            pair_begin: int as u32,
+        }
+    }
+
+    pub fn to_int(self) -> u64 {
+        (self.has_numberless_keys as u64) << 63 | (self.num as u64) << 32 | self.pair_begin as u64
+    }
+
+    /// Range of pair indices covered by this handle.
+    pub fn pair_range(self) -> std::ops::Range<usize> {
+        self.pair_begin as usize..self.pair_begin as usize + self.num as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let handle = FPartialMapHandle {
+            has_numberless_keys: true,
+            num: 4,
+            pair_begin: 0x5BB,
+        };
+        assert_eq!(handle.to_int(), 0x8000_0004_0000_05BB);
+        assert_eq!(FPartialMapHandle::from_int(handle.to_int()), handle);
+    }
+}
@@ -2,16 +2,24 @@ use std::io::{Read, Write};
 
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 use color_eyre::eyre::{eyre, Result as EResult};
+#[cfg(feature = "trace-fields")]
 use tracing::*;
 
+use crate::lenient::read_bytes;
 use crate::read::Readable;
-use crate::write::Writable;
+use crate::write::{SerializedSize, Writable, WriteError};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct FString {
     inner: String,
 }
 
+impl FString {
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+}
+
 impl From<String> for FString {
     fn from(value: String) -> Self {
         FString { inner: value }
@@ -26,16 +34,26 @@ impl<'s> From<&'s str> for FString {
     }
 }
 
+/// The magnitude of an [`FString`]'s length field: its bytes including the NUL terminator.
+fn string_len(len: usize) -> Result<i32, WriteError> {
+    i32::try_from(len).map_err(|_| WriteError::TooLarge {
+        what: "FString",
+        bytes: len as u64,
+        max: i32::MAX as u64,
+    })
+}
+
 impl<W: Write> Writable<W> for FString {
-    #[instrument(name = "FString_write", skip_all)]
+    #[cfg_attr(feature = "trace-fields", instrument(name = "FString_write", skip_all))]
     fn write(&self, writer: &mut W) -> EResult<()> {
         if self.inner.is_ascii() {
-            writer.write_u32::<LE>(self.inner.len() as u32 + 1)?;
+            let len = self.inner.len() + 1;
+            writer.write_i32::<LE>(string_len(len)?)?;
             writer.write_all(self.inner.as_bytes())?;
         } else {
             let buf = self.inner.encode_utf16().collect::<Vec<_>>();
             let len = buf.len() * std::mem::size_of::<u16>();
-            writer.write_i32::<LE>(-(len as i32 + 1))?;
+            writer.write_i32::<LE>(-string_len(len + 1)?)?;
 
             for b in buf {
                 writer.write_u16::<LE>(b)?;
@@ -46,36 +64,48 @@ impl<W: Write> Writable<W> for FString {
     }
 }
 
+impl SerializedSize for FString {
+    fn serialized_size(&self) -> u64 {
+        // The length, then the ANSI bytes or UTF-16 code units, then a NUL byte.
+        let len = match self.inner.is_ascii() {
+            true => self.inner.len() as u64,
+            false => 2 * self.inner.encode_utf16().count() as u64,
+        };
+        4 + len + 1
+    }
+}
+
 impl<R: Read> Readable<R> for FString {
-    #[instrument(name = "FString_read", skip_all)]
+    #[cfg_attr(feature = "trace-fields", instrument(name = "FString_read", skip_all))]
     fn read(reader: &mut R) -> EResult<Self> {
         let len = reader.read_i32::<LE>()?;
+        #[cfg(feature = "trace-fields")]
         debug!(%len);
         let s = match len {
             len if len > 0 => {
-                let mut buf = vec![0u8; len as usize - 1];
-                reader.read_exact(&mut buf)?;
+                let buf = read_bytes(reader, len as usize - 1)?;
                 let nul = reader.read_u8()?;
                 if nul != b'\0' {
                     return Err(eyre!("FString not NUL-terminated"));
                 }
                 String::from_utf8(buf)?
             }
-            len if len == 0 => {
+            0 => {
                 return Err(eyre!("FString length cannot be 0"));
             }
             len if len < 0 => {
-                let len = (-len) as usize;
-                if (len - 1) % 2 != 0 {
+                let len = len
+                    .checked_neg()
+                    .ok_or_else(|| eyre!("FString length {} out of range", len))?
+                    as usize;
+                if !(len - 1).is_multiple_of(2) {
                     return Err(eyre!(
                         "len without NUL byte not a multiple of 2, invalid FString"
                     ));
                 }
-                let mut buf = vec![0u8; len as usize - 1];
-                reader.read_exact(&mut buf)?;
+                let buf = read_bytes(reader, len - 1)?;
                 let buf = buf
                     .chunks_exact(2)
-                    .into_iter()
                     .map(|a| u16::from_le_bytes([a[0], a[1]]))
                     .collect::<Vec<_>>();
 
@@ -2,13 +2,14 @@ use std::io::{Read, Write};
 
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 use color_eyre::eyre::{eyre, Result as EResult};
+#[cfg(feature = "trace-fields")]
 use tracing::*;
 
 use crate::read::Readable;
-use crate::write::Writable;
+use crate::write::{size_u32, SerializedSize, Writable};
 
 /// A [`FText`] is a NUL-terminated raw string with a len prepended when (de-)serializing.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct FText {
     raw: Vec<u8>,
 }
@@ -49,16 +50,22 @@ impl FText {
 }
 
 impl<W: Write> Writable<W> for FText {
-    #[instrument(name = "FText_write", skip_all)]
+    #[cfg_attr(feature = "trace-fields", instrument(name = "FText_write", skip_all))]
     fn write(&self, writer: &mut W) -> EResult<()> {
-        writer.write_u32::<LE>(self.raw.len() as u32)?;
+        writer.write_u32::<LE>(size_u32("FText", self.raw.len() as u64)?)?;
         writer.write_all(&self.raw)?;
         Ok(())
     }
 }
 
+impl SerializedSize for FText {
+    fn serialized_size(&self) -> u64 {
+        4 + self.raw.len() as u64
+    }
+}
+
 impl<R: Read> Readable<R> for FText {
-    #[instrument(name = "FText_read", skip_all)]
+    #[cfg_attr(feature = "trace-fields", instrument(name = "FText_read", skip_all))]
     fn read(reader: &mut R) -> EResult<Self> {
         let len = reader.read_u32::<LE>()?;
         if len >= isize::MAX as u32 {
@@ -0,0 +1,57 @@
+//! `FValueId` is how a tag value in the store is referenced: the low bits encode which store array
+//! the value lives in, the remaining bits are an index into that array.
+
+use color_eyre::eyre::{Result as EResult, WrapErr};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, IntoPrimitive, TryFromPrimitive)]
+#[repr(u32)]
+pub enum EValueType {
+    AnsiString = 0,
+    WideString,
+    NumberlessName,
+    Name,
+    NumberlessExportPath,
+    ExportPath,
+    LocalizedText,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct FValueId {
+    pub ty: EValueType,
+    pub index: u32,
+}
+
+impl FValueId {
+    pub const TYPE_BITS: u32 = 3;
+    pub const INDEX_BITS: u32 = 32 - Self::TYPE_BITS;
+
+    pub fn from_int(int: u32) -> EResult<Self> {
+        let ty = (int << Self::INDEX_BITS) >> Self::INDEX_BITS;
+        let ty = EValueType::try_from(ty)
+            .wrap_err_with(|| format!("unexpected value type in FValueId {int:X}"))?;
+        Ok(FValueId {
+            ty,
+            index: int >> Self::TYPE_BITS,
+        })
+    }
+
+    pub fn to_int(self) -> u32 {
+        u32::from(self.ty) | (self.index << Self::TYPE_BITS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let id = FValueId {
+            ty: EValueType::NumberlessExportPath,
+            index: 0x1234,
+        };
+        assert_eq!(FValueId::from_int(id.to_int()).unwrap(), id);
+        assert!(FValueId::from_int(0b111).is_err());
+    }
+}
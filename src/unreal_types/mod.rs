@@ -3,11 +3,15 @@
 mod fasset_registry_export_path;
 mod fname;
 mod fnumbered_pair;
+mod fpartial_map_handle;
 mod fstring;
 mod ftext;
+mod fvalue_id;
 
 pub use fasset_registry_export_path::*;
 pub use fname::*;
 pub use fnumbered_pair::*;
+pub use fpartial_map_handle::*;
 pub use fstring::*;
 pub use ftext::*;
+pub use fvalue_id::*;
@@ -0,0 +1,478 @@
+//! Consistency checks over a parsed registry.
+//!
+//! Parsing only checks what is needed to get through the bytes; a registry can parse fine and
+//! still contain dangling indices that crash or confuse the engine. [`AssetRegistry::validate`]
+//! walks every cross-reference and reports each problem with the path of the offending field,
+//! e.g. `store.pairs[42].key`.
+
+use std::collections::{HashMap, HashSet};
+
+use tracing::*;
+
+use crate::asset_registry::AssetRegistry;
+use crate::dependencies::{DependencySection, FDependency};
+use crate::names_batch::NamesBatch;
+use crate::serialized_name_header::SerializedNameHeader;
+use crate::unreal_types::*;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub enum Severity {
+    /// Suspicious, but the engine copes.
+    Warning,
+    /// The registry is broken.
+    Error,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Path of the offending field, e.g. `assets[3].tags`.
+    pub location: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", severity, self.location, self.message)
+    }
+}
+
+impl AssetRegistry {
+    /// Check every cross-reference in the registry, returning all problems found.
+    #[instrument(name = "AssetRegistry_validate", skip_all)]
+    pub fn validate(&self) -> Vec<Finding> {
+        let mut validator = Validator {
+            registry: self,
+            findings: vec![],
+        };
+        validator.names();
+        validator.store();
+        validator.assets();
+        validator.dependencies();
+        validator.package_data();
+        debug!(findings = validator.findings.len());
+        validator.findings
+    }
+}
+
+struct Validator<'a> {
+    registry: &'a AssetRegistry,
+    findings: Vec<Finding>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, severity: Severity, location: String, message: String) {
+        self.findings.push(Finding {
+            severity,
+            location,
+            message,
+        });
+    }
+
+    /// Check that `name` points into the name table, returning its display string.
+    fn name(&mut self, name: FName, location: impl Fn() -> String) -> Option<String> {
+        let resolved = self.registry.names.resolve(name);
+        if resolved.is_none() {
+            let len = self.registry.names.strings.len();
+            self.report(
+                Severity::Error,
+                location(),
+                format!("FName index {} out of bounds of {} names", name.index, len),
+            );
+        }
+        resolved
+    }
+
+    fn numberless_name(&mut self, name: FName, location: impl Fn() -> String) {
+        self.name(name, &location);
+        if name.number != 0 {
+            self.report(
+                Severity::Error,
+                location(),
+                format!("numberless name has number {}", name.number),
+            );
+        }
+    }
+
+    fn names(&mut self) {
+        let registry = self.registry;
+        let names = &registry.names;
+        let count = names.strings.len();
+        if names.hashes.len() != count || names.headers.len() != count {
+            self.report(
+                Severity::Error,
+                "names".to_string(),
+                format!(
+                    "{} strings, but {} hashes and {} headers",
+                    count,
+                    names.hashes.len(),
+                    names.headers.len()
+                ),
+            );
+        }
+
+        let check_hashes = names.hash_version == NamesBatch::HASH_VERSION;
+        if !check_hashes {
+            self.report(
+                Severity::Warning,
+                "names.hash_version".to_string(),
+                format!(
+                    "unknown hash version {:#X}, name hashes not checked",
+                    names.hash_version
+                ),
+            );
+        }
+
+        let mut seen = HashMap::new();
+        for (i, s) in names.strings.iter().enumerate() {
+            if let Some(header) = names.headers.get(i) {
+                let expected = match SerializedNameHeader::for_string(s) {
+                    Ok(expected) => expected,
+                    Err(e) => {
+                        self.report(
+                            Severity::Error,
+                            format!("names.strings[{i}]"),
+                            e.to_string(),
+                        );
+                        continue;
+                    }
+                };
+                if *header != expected {
+                    self.report(
+                        Severity::Error,
+                        format!("names.headers[{i}]"),
+                        format!(
+                            "header {:?} does not match `{}` ({:?})",
+                            header, s, expected
+                        ),
+                    );
+                }
+            }
+            if let Some(&hash) = names.hashes.get(i) {
+                let expected = NamesBatch::hash(s);
+                if check_hashes && hash != expected {
+                    self.report(
+                        Severity::Error,
+                        format!("names.hashes[{i}]"),
+                        format!("hash {:#X} of `{}` should be {:#X}", hash, s, expected),
+                    );
+                }
+            }
+            if let Some(first) = seen.insert(s.as_str(), i) {
+                self.report(
+                    Severity::Warning,
+                    format!("names.strings[{i}]"),
+                    format!("`{}` is already interned at index {}", s, first),
+                );
+            }
+        }
+    }
+
+    fn store(&mut self) {
+        let registry = self.registry;
+        let store = &registry.store;
+        for (i, name) in store.numberless_names.iter().enumerate() {
+            self.numberless_name(*name, || format!("store.numberless_names[{i}]"));
+        }
+        for (i, name) in store.names.iter().enumerate() {
+            self.name(*name, || format!("store.names[{i}]"));
+        }
+        for (i, path) in store.numberless_export_paths.iter().enumerate() {
+            let location = |field: &str| format!("store.numberless_export_paths[{i}].{field}");
+            self.numberless_name(path.class, || location("class"));
+            self.numberless_name(path.object, || location("object"));
+            self.numberless_name(path.package, || location("package"));
+        }
+        for (i, path) in store.export_paths.iter().enumerate() {
+            let location = |field: &str| format!("store.export_paths[{i}].{field}");
+            self.name(path.class, || location("class"));
+            self.name(path.object, || location("object"));
+            self.name(path.package, || location("package"));
+        }
+        for (i, s) in store.ansi_strings.iter().enumerate() {
+            if !s.is_ascii() {
+                self.report(
+                    Severity::Error,
+                    format!("store.ansi_strings[{i}]"),
+                    format!("`{}` is not ASCII", s),
+                );
+            }
+        }
+
+        for (array, pairs) in [
+            ("numberless_pairs", &store.numberless_pairs),
+            ("pairs", &store.pairs),
+        ] {
+            for (i, pair) in pairs.iter().enumerate() {
+                let location = |field: &str| format!("store.{array}[{i}].{field}");
+                match array {
+                    "numberless_pairs" => self.numberless_name(pair.key, || location("key")),
+                    _ => {
+                        self.name(pair.key, || location("key"));
+                    }
+                }
+                let message = match FValueId::from_int(pair.value) {
+                    Ok(id) => match store.value(id) {
+                        Ok(_) => continue,
+                        Err(e) => e.to_string(),
+                    },
+                    Err(e) => e.to_string(),
+                };
+                self.report(Severity::Error, location("value"), message);
+            }
+        }
+    }
+
+    fn assets(&mut self) {
+        let registry = self.registry;
+        let mut object_paths = HashMap::new();
+        for (i, asset) in registry.assets.assets.iter().enumerate() {
+            let location = |field: &str| format!("assets[{i}].{field}");
+            let object_path = self.name(asset.object_path, || location("object_path"));
+            self.name(asset.package_path, || location("package_path"));
+            self.name(asset.asset_class, || location("asset_class"));
+            let package_name = self.name(asset.package_name, || location("package_name"));
+            let asset_name = self.name(asset.asset_name, || location("asset_name"));
+
+            if let Some(object_path) = object_path {
+                if let (Some(package_name), Some(asset_name)) = (package_name, asset_name) {
+                    let expected = format!("{package_name}.{asset_name}");
+                    if object_path != expected {
+                        self.report(
+                            Severity::Warning,
+                            location("object_path"),
+                            format!("`{}` does not match `{}`", object_path, expected),
+                        );
+                    }
+                }
+                if let Some(first) = object_paths.insert(object_path.clone(), i) {
+                    self.report(
+                        Severity::Error,
+                        location("object_path"),
+                        format!("duplicate of `{}` at assets[{}]", object_path, first),
+                    );
+                }
+            }
+
+            let handle = FPartialMapHandle::from_int(asset.tags);
+            if let Err(e) = registry.store.pairs_for(handle) {
+                self.report(Severity::Error, location("tags"), e.to_string());
+            }
+
+            for (b, entry) in asset.bundles.iter().enumerate() {
+                self.name(entry.bundle_name, || {
+                    format!("assets[{i}].bundles[{b}].bundle_name")
+                });
+                for (p, path) in entry.bundles.iter().enumerate() {
+                    self.name(path.asset_path_name, || {
+                        format!("assets[{i}].bundles[{b}].bundles[{p}].asset_path_name")
+                    });
+                }
+            }
+        }
+    }
+
+    fn dependencies(&mut self) {
+        let registry = self.registry;
+        let section = &registry.dependencies;
+        let count = section.nodes.len();
+        let mut identifiers = HashMap::new();
+        for (i, node) in section.nodes.iter().enumerate() {
+            let identifier = &node.identifier;
+            for (field, name) in [
+                ("package_name", identifier.package_name),
+                ("primary_asset_type", identifier.primary_asset_type),
+                ("object_name", identifier.object_name),
+                ("value_name", identifier.value_name),
+            ] {
+                if let Some(name) = name {
+                    self.name(name, || {
+                        format!("dependencies.nodes[{i}].identifier.{field}")
+                    });
+                }
+            }
+            if let Ok(resolved) = identifier.resolve(&registry.names) {
+                if let Some(first) = identifiers.insert(resolved.to_string(), i) {
+                    self.report(
+                        Severity::Error,
+                        format!("dependencies.nodes[{i}].identifier"),
+                        format!(
+                            "duplicate of `{}` at dependencies.nodes[{}]",
+                            resolved, first
+                        ),
+                    );
+                }
+            }
+
+            let mut edge = |list: &str, j: usize, target: i32| {
+                if usize::try_from(target).map_or(true, |t| t >= count) {
+                    self.report(
+                        Severity::Error,
+                        format!("dependencies.nodes[{i}].{list}[{j}]"),
+                        format!("dependency on unknown node {} of {}", target, count),
+                    );
+                }
+            };
+            let nodes = |deps: &[FDependency]| deps.iter().map(|d| d.node).collect::<Vec<_>>();
+            for (list, targets) in [
+                ("package_dependencies", nodes(&node.package_dependencies)),
+                ("name_dependencies", node.name_dependencies.clone()),
+                ("manage_dependencies", nodes(&node.manage_dependencies)),
+                ("referencers", node.referencers.clone()),
+            ] {
+                for (j, target) in targets.into_iter().enumerate() {
+                    edge(list, j, target);
+                }
+            }
+        }
+
+        // Referencers are redundant with the dependency lists; the engine trusts them anyway.
+        let mut rebuilt = DependencySection {
+            nodes: section.nodes.clone(),
+        };
+        rebuilt.rebuild_referencers();
+        for (i, (node, expected)) in section.nodes.iter().zip(&rebuilt.nodes).enumerate() {
+            let actual = node.referencers.iter().copied().collect::<HashSet<_>>();
+            let expected = expected.referencers.iter().copied().collect::<HashSet<_>>();
+            if actual != expected {
+                let mut missing = expected.difference(&actual).copied().collect::<Vec<_>>();
+                let mut extra = actual.difference(&expected).copied().collect::<Vec<_>>();
+                missing.sort_unstable();
+                extra.sort_unstable();
+                self.report(
+                    Severity::Warning,
+                    format!("dependencies.nodes[{i}].referencers"),
+                    format!(
+                        "referencers do not match dependencies: missing {:?}, extra {:?}",
+                        missing, extra
+                    ),
+                );
+            }
+        }
+    }
+
+    fn package_data(&mut self) {
+        let registry = self.registry;
+        let asset_packages = registry
+            .assets
+            .assets
+            .iter()
+            .filter_map(|a| registry.names.resolve(a.package_name))
+            .collect::<HashSet<_>>();
+        let mut packages = HashMap::new();
+        for (i, package) in registry.package_data.packages.iter().enumerate() {
+            let location = || format!("package_data.packages[{i}].package_name");
+            let Some(name) = self.name(package.package_name, location) else {
+                continue;
+            };
+            if !asset_packages.contains(&name) {
+                self.report(
+                    Severity::Warning,
+                    location(),
+                    format!("package data for `{}`, which has no assets", name),
+                );
+            }
+            if let Some(first) = packages.insert(name.clone(), i) {
+                self.report(
+                    Severity::Error,
+                    location(),
+                    format!(
+                        "duplicate of `{}` at package_data.packages[{}]",
+                        name, first
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{RegistryBuilder, TagValue};
+    use crate::dependencies::{EDependencyCategory, EDependencyProperty};
+
+    use pretty_assertions::assert_eq;
+
+    fn fixture() -> AssetRegistry {
+        let mut builder = RegistryBuilder::new();
+        builder
+            .add_asset("/Game/A.A", "Blueprint")
+            .tag("ParentClass", TagValue::Name("Actor".to_string()))
+            .bundle("Client", &["/Game/B.B"]);
+        builder
+            .add_asset("/Game/B.B", "Texture2D")
+            .tag("Size", "big");
+        builder.add_dependency(
+            "/Game/A",
+            "/Game/B",
+            EDependencyCategory::Package,
+            EDependencyProperty::HARD,
+        );
+        builder.add_package_data("/Game/A", 1);
+        builder.build().unwrap()
+    }
+
+    fn locations(findings: &[Finding]) -> Vec<(Severity, &str)> {
+        findings
+            .iter()
+            .map(|f| (f.severity, f.location.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid() {
+        assert_eq!(fixture().validate(), vec![]);
+    }
+
+    #[test]
+    fn test_findings() {
+        let mut registry = fixture();
+        registry.names.hashes[0] ^= 1;
+        registry.store.numberless_pairs[0].value = FValueId {
+            ty: EValueType::AnsiString,
+            index: 7,
+        }
+        .to_int();
+        registry.assets.assets[0].asset_class.index = 1000;
+        registry.assets.assets[1].tags = FPartialMapHandle {
+            has_numberless_keys: true,
+            num: 5,
+            pair_begin: 1,
+        }
+        .to_int();
+        let duplicate = registry.assets.assets[0].clone();
+        registry.assets.assets.push(duplicate);
+        registry.dependencies.nodes[0].package_dependencies[0].node = 9;
+        let mut package = registry.package_data.packages[0].clone();
+        package.package_name = registry.assets.assets[0].package_path;
+        registry.package_data.packages.push(package);
+
+        let findings = registry.validate();
+        assert_eq!(
+            locations(&findings),
+            vec![
+                (Severity::Error, "names.hashes[0]"),
+                (Severity::Error, "store.numberless_pairs[0].value"),
+                (Severity::Error, "assets[0].asset_class"),
+                (Severity::Error, "assets[1].tags"),
+                (Severity::Error, "assets[2].asset_class"),
+                (Severity::Error, "assets[2].object_path"),
+                (
+                    Severity::Error,
+                    "dependencies.nodes[0].package_dependencies[0]"
+                ),
+                (Severity::Warning, "dependencies.nodes[1].referencers"),
+                (Severity::Warning, "package_data.packages[1].package_name"),
+            ]
+        );
+        assert_eq!(
+            findings[5].to_string(),
+            "error: assets[2].object_path: duplicate of `/Game/A.A` at assets[0]"
+        );
+    }
+}
@@ -1,11 +1,160 @@
-use color_eyre::eyre::Result as EResult;
+use std::io::{Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+
+use byteorder::{WriteBytesExt, LE};
+use color_eyre::eyre::{eyre, Result as EResult};
+#[cfg(feature = "trace-fields")]
 use tracing::*;
 
+use crate::serialized_name_header::SerializedNameHeader;
+
+pub use ar_derive::{SerializedSize, Writable};
+
 pub trait Writable<W> {
     fn write(&self, writer: &mut W) -> EResult<()>;
 }
 
-#[instrument(name = "write_array", skip_all, fields(len = array.len()))]
+/// Why a structure can't be written without producing a corrupt file. Writers return these
+/// inside their [`color_eyre::Report`]s, so callers can `downcast_ref` to tell them apart from
+/// I/O errors.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum WriteError {
+    /// More elements than the format's count field can hold.
+    TooMany {
+        what: &'static str,
+        count: u64,
+        max: u64,
+    },
+    /// More bytes than the format's size or offset field can hold.
+    TooLarge {
+        what: &'static str,
+        bytes: u64,
+        max: u64,
+    },
+    /// A name longer than the 15-bit length of a [`SerializedNameHeader`].
+    ///
+    /// [`SerializedNameHeader`]: crate::serialized_name_header::SerializedNameHeader
+    NameTooLong { len: u64 },
+    /// Parallel vectors or headers that disagree with the data they describe.
+    Mismatch {
+        what: &'static str,
+        expected: u64,
+        found: u64,
+    },
+}
+
+impl std::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::TooMany { what, count, max } => {
+                write!(f, "{count} {what} exceed the limit of {max}")
+            }
+            WriteError::TooLarge { what, bytes, max } => {
+                write!(f, "{what} of {bytes} bytes exceed the limit of {max}")
+            }
+            WriteError::NameTooLong { len } => write!(
+                f,
+                "name of {len} characters exceeds the limit of {}",
+                SerializedNameHeader::MAX_LEN
+            ),
+            WriteError::Mismatch {
+                what,
+                expected,
+                found,
+            } => write!(f, "expected {expected} {what} but found {found}"),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// The number of elements of `what` as a `u32` count field.
+pub fn count_u32(what: &'static str, count: usize) -> Result<u32, WriteError> {
+    u32::try_from(count).map_err(|_| WriteError::TooMany {
+        what,
+        count: count as u64,
+        max: u32::MAX as u64,
+    })
+}
+
+/// The number of elements of `what` as an `i32` count field.
+pub fn count_i32(what: &'static str, count: usize) -> Result<i32, WriteError> {
+    i32::try_from(count).map_err(|_| WriteError::TooMany {
+        what,
+        count: count as u64,
+        max: i32::MAX as u64,
+    })
+}
+
+/// A byte size or offset of `what` as a `u32` field.
+pub fn size_u32(what: &'static str, bytes: u64) -> Result<u32, WriteError> {
+    u32::try_from(bytes).map_err(|_| WriteError::TooLarge {
+        what,
+        bytes,
+        max: u32::MAX as u64,
+    })
+}
+
+/// The number of bytes [`Writable::write`] produces, so that a layout can be computed without
+/// writing anything.
+pub trait SerializedSize {
+    fn serialized_size(&self) -> u64;
+}
+
+impl<T: SerializedSize> SerializedSize for [T] {
+    fn serialized_size(&self) -> u64 {
+        self.iter().map(T::serialized_size).sum()
+    }
+}
+
+macro_rules! writable_int {
+    ($($ty:ty => $write:ident),* $(,)?) => {
+        $(
+            impl<W: Write> Writable<W> for $ty {
+                fn write(&self, writer: &mut W) -> EResult<()> {
+                    Ok(writer.$write::<LE>(*self)?)
+                }
+            }
+
+            impl SerializedSize for $ty {
+                fn serialized_size(&self) -> u64 {
+                    std::mem::size_of::<$ty>() as u64
+                }
+            }
+        )*
+    };
+}
+
+writable_int!(u16 => write_u16, u32 => write_u32, u64 => write_u64, i32 => write_i32, i64 => write_i64);
+
+impl<W: Write> Writable<W> for u8 {
+    fn write(&self, writer: &mut W) -> EResult<()> {
+        Ok(writer.write_u8(*self)?)
+    }
+}
+
+impl SerializedSize for u8 {
+    fn serialized_size(&self) -> u64 {
+        1
+    }
+}
+
+impl<W: Write, const N: usize> Writable<W> for [u8; N] {
+    fn write(&self, writer: &mut W) -> EResult<()> {
+        Ok(writer.write_all(self)?)
+    }
+}
+
+impl<const N: usize> SerializedSize for [u8; N] {
+    fn serialized_size(&self) -> u64 {
+        N as u64
+    }
+}
+
+#[cfg_attr(
+    feature = "trace-fields",
+    instrument(name = "write_array", skip_all, fields(len = array.len()))
+)]
 pub fn write_array<W, T, E>(
     writer: &mut W,
     array: &[T],
@@ -16,3 +165,201 @@ pub fn write_array<W, T, E>(
     }
     Ok(())
 }
+
+/// A fixed-size little-endian integer that can be reserved and patched later.
+pub trait Patchable: Copy {
+    const SIZE: u64;
+
+    fn write_le<W: Write>(self, writer: &mut W) -> std::io::Result<()>;
+}
+
+impl Patchable for u32 {
+    const SIZE: u64 = 4;
+
+    fn write_le<W: Write>(self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_u32::<LE>(self)
+    }
+}
+
+impl Patchable for i64 {
+    const SIZE: u64 = 8;
+
+    fn write_le<W: Write>(self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_i64::<LE>(self)
+    }
+}
+
+/// Space reserved for `len` values of type `T`, to be filled in by [`PatchWriter::patch`].
+#[must_use]
+#[derive(Debug)]
+pub struct Slot<T> {
+    position: u64,
+    len: usize,
+    value: PhantomData<T>,
+}
+
+/// A writer for sections whose header describes the payload that follows it: counts, byte sizes
+/// and offset tables are reserved as zeroes and patched once the payload has been written, so
+/// nothing has to be measured up front.
+///
+/// The position is tracked as bytes go through, so the underlying stream is only asked for it
+/// once and only seeks when patching.
+pub struct PatchWriter<'w, W> {
+    writer: &'w mut W,
+    position: u64,
+    /// Slots reserved but not yet patched.
+    pending: usize,
+}
+
+impl<'w, W: Write + Seek> PatchWriter<'w, W> {
+    pub fn new(writer: &'w mut W) -> EResult<Self> {
+        let position = writer.stream_position()?;
+        Ok(PatchWriter {
+            writer,
+            position,
+            pending: 0,
+        })
+    }
+
+    /// The absolute position in the underlying stream.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Reserve a single value.
+    pub fn reserve<T: Patchable>(&mut self) -> EResult<Slot<T>> {
+        self.reserve_table(1)
+    }
+
+    /// Reserve a table of `len` values, such as the offsets of packed strings.
+    pub fn reserve_table<T: Patchable>(&mut self, len: usize) -> EResult<Slot<T>> {
+        let position = self.position;
+        self.write_all(&vec![0; (len as u64 * T::SIZE) as usize])?;
+        self.pending += 1;
+        Ok(Slot {
+            position,
+            len,
+            value: PhantomData,
+        })
+    }
+
+    /// Fill in a single value reserved by [`PatchWriter::reserve`].
+    pub fn patch<T: Patchable>(&mut self, slot: Slot<T>, value: T) -> EResult<()> {
+        self.patch_table(slot, &[value])
+    }
+
+    /// Fill in a table reserved by [`PatchWriter::reserve_table`], leaving the stream positioned
+    /// where it was.
+    pub fn patch_table<T: Patchable>(&mut self, slot: Slot<T>, values: &[T]) -> EResult<()> {
+        if values.len() != slot.len {
+            return Err(eyre!(
+                "patching a slot of {} values with {} values",
+                slot.len,
+                values.len()
+            ));
+        }
+        self.writer.seek(SeekFrom::Start(slot.position))?;
+        values.iter().try_for_each(|v| v.write_le(self.writer))?;
+        self.writer.seek(SeekFrom::Start(self.position))?;
+        self.pending -= 1;
+        Ok(())
+    }
+
+    /// Check that every reserved slot has been patched.
+    pub fn finish(self) -> EResult<()> {
+        match self.pending {
+            0 => Ok(()),
+            n => Err(eyre!("{} reserved slots were never patched", n)),
+        }
+    }
+}
+
+impl<W: Write> Write for PatchWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.writer.write(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_patch_writer() {
+        let mut buf = Cursor::new(vec![]);
+        let mut writer = PatchWriter::new(&mut buf).unwrap();
+        let size = writer.reserve::<i64>().unwrap();
+        let offsets = writer.reserve_table::<u32>(2).unwrap();
+        let start = writer.position();
+        writer.write_all(b"ab\0").unwrap();
+        let second = writer.position();
+        writer.write_all(b"c\0").unwrap();
+        let end = writer.position();
+        writer
+            .patch_table(offsets, &[0, (second - start) as u32])
+            .unwrap();
+        writer.patch(size, (end - start) as i64).unwrap();
+        writer.write_u8(0xFF).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            buf.into_inner(),
+            [
+                &5i64.to_le_bytes()[..],
+                &0u32.to_le_bytes(),
+                &3u32.to_le_bytes(),
+                b"ab\0c\0",
+                &[0xFF],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_checked_conversions() {
+        assert_eq!(count_u32("assets", 3), Ok(3));
+        assert_eq!(count_i32("nodes", i32::MAX as usize), Ok(i32::MAX));
+        let err = count_i32("nodes", i32::MAX as usize + 1).unwrap_err();
+        assert_eq!(
+            err,
+            WriteError::TooMany {
+                what: "nodes",
+                count: 1 << 31,
+                max: i32::MAX as u64
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "2147483648 nodes exceed the limit of 2147483647"
+        );
+        assert_eq!(
+            size_u32("wide strings", 1 << 32).unwrap_err().to_string(),
+            "wide strings of 4294967296 bytes exceed the limit of 4294967295"
+        );
+    }
+
+    #[test]
+    fn test_patch_writer_errors() {
+        let mut buf = Cursor::new(vec![]);
+        let mut writer = PatchWriter::new(&mut buf).unwrap();
+        let table = writer.reserve_table::<u32>(2).unwrap();
+        assert_eq!(
+            writer.patch_table(table, &[1]).unwrap_err().to_string(),
+            "patching a slot of 2 values with 1 values"
+        );
+        assert_eq!(
+            writer.finish().unwrap_err().to_string(),
+            "1 reserved slots were never patched"
+        );
+    }
+}